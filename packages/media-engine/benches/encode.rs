@@ -0,0 +1,157 @@
+//! Criterion benchmarks for the per-frame encode path: BGRA upload,
+//! BGRA → NV12 conversion, and whole-frame encode at 1080p/1440p/4K with
+//! synthetic frames. Performance-motivated refactors (zero-copy, texture
+//! pooling) get before/after numbers from here instead of anecdotes.
+//!
+//! The GPU benchmarks need a D3D11 device and are Windows-only; the
+//! software-encoder benchmark runs anywhere.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use media_engine::config::EncoderConfig;
+use media_engine::encode::software::SoftwareEncoder;
+use media_engine::encode::{EncoderInput, VideoEncoder};
+
+const SIZES: &[(u32, u32, &str)] = &[
+    (1920, 1080, "1080p"),
+    (2560, 1440, "1440p"),
+    (3840, 2160, "4k"),
+];
+
+/// A gradient with per-frame motion baked in via `seed`, so encoders do
+/// real work instead of coding a static scene.
+fn bgra_frame(width: u32, height: u32, seed: u32) -> Vec<u8> {
+    let mut data = vec![0u8; (width * height * 4) as usize];
+    for row in 0..height {
+        for col in 0..width {
+            let p = ((row * width + col) * 4) as usize;
+            data[p] = (col + seed * 7) as u8;
+            data[p + 1] = (row + seed * 13) as u8;
+            data[p + 2] = (col ^ row) as u8;
+            data[p + 3] = 255;
+        }
+    }
+    data
+}
+
+/// Software path: scale/convert plus openh264, clamped to 720p like in
+/// production. The input size still matters — conversion reads it all.
+fn bench_software_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("software_encode");
+    group.sample_size(20);
+    for &(width, height, name) in SIZES {
+        let config = EncoderConfig {
+            width,
+            height,
+            fps: 30,
+            ..Default::default()
+        };
+        let mut encoder = SoftwareEncoder::new(&config).expect("software encoder");
+        let data = bgra_frame(width, height, 0);
+        let mut qpc = 0i64;
+        group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+            b.iter(|| {
+                qpc += 166_667;
+                encoder
+                    .encode(
+                        EncoderInput::Bgra {
+                            data,
+                            width,
+                            height,
+                        },
+                        qpc,
+                    )
+                    .expect("encode")
+            })
+        });
+    }
+    group.finish();
+}
+
+#[cfg(windows)]
+mod gpu {
+    use super::{bgra_frame, SIZES};
+    use criterion::{BenchmarkId, Criterion};
+    use media_engine::config::EncoderConfig;
+    use media_engine::encode::convert::Converter;
+    use media_engine::encode::d3d::create_d3d_device;
+    use media_engine::encode::pipeline::EncodePipeline;
+
+    /// System-memory BGRA → GPU texture, the entry cost of every frame on
+    /// the hardware path.
+    pub fn bench_upload(c: &mut Criterion) {
+        let (device, context) = create_d3d_device().expect("d3d device");
+        let mut group = c.benchmark_group("bgra_upload");
+        for &(width, height, name) in SIZES {
+            let data = bgra_frame(width, height, 0);
+            group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+                b.iter(|| Converter::upload_bgra(&device, &context, data, width, height).unwrap())
+            });
+        }
+        group.finish();
+    }
+
+    /// Video-processor BGRA → NV12 blit at the same output size.
+    pub fn bench_convert(c: &mut Criterion) {
+        let (device, context) = create_d3d_device().expect("d3d device");
+        let mut group = c.benchmark_group("bgra_to_nv12");
+        for &(width, height, name) in SIZES {
+            let data = bgra_frame(width, height, 0);
+            let texture =
+                Converter::upload_bgra(&device, &context, &data, width, height).unwrap();
+            let converter =
+                Converter::new(&device, &context, width, height, width, height).unwrap();
+            group.bench_with_input(BenchmarkId::from_parameter(name), &texture, |b, texture| {
+                b.iter(|| converter.convert(texture).unwrap())
+            });
+        }
+        group.finish();
+    }
+
+    /// Whole per-frame cost through the pipeline: upload, convert, MFT
+    /// encode. Needs a hardware encoder; fails fast without one.
+    pub fn bench_pipeline_encode(c: &mut Criterion) {
+        let mut group = c.benchmark_group("pipeline_encode");
+        group.sample_size(30);
+        for &(width, height, name) in SIZES {
+            let (device, context) = create_d3d_device().expect("d3d device");
+            let config = EncoderConfig {
+                width,
+                height,
+                fps: 60,
+                ..Default::default()
+            };
+            let mut pipeline =
+                EncodePipeline::new(device, context, width, height, &config).expect("pipeline");
+            let mut seed = 0u32;
+            group.bench_function(BenchmarkId::from_parameter(name), |b| {
+                b.iter_batched(
+                    || {
+                        seed += 1;
+                        media_engine::capture::CaptureFrame {
+                            data: bgra_frame(width, height, seed).into(),
+                            width,
+                            height,
+                            qpc: seed as i64 * 166_667,
+                        }
+                    },
+                    |frame| pipeline.encode(&frame).expect("encode"),
+                    criterion::BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+#[cfg(windows)]
+criterion_group!(
+    benches,
+    bench_software_encode,
+    gpu::bench_upload,
+    gpu::bench_convert,
+    gpu::bench_pipeline_encode
+);
+#[cfg(not(windows))]
+criterion_group!(benches, bench_software_encode);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+fn main() {
+    // Only the NAPI addon needs the node linker setup; plain Rust builds
+    // (default-features = false) skip it.
+    if std::env::var_os("CARGO_FEATURE_NODE").is_some() {
+        napi_build::setup();
+    }
+}
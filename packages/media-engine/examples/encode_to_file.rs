@@ -0,0 +1,51 @@
+//! Captures the primary display for a few seconds and records it to
+//! `capture.mp4` — a real container with timestamps and a faststart moov,
+//! playable anywhere. Use a `.h264` path for the raw Annex-B stream.
+
+use std::time::Duration;
+
+use media_engine::capture::CaptureTarget;
+use media_engine::config::{EncoderConfig, ScreenShareConfig};
+use media_engine::engine::{EngineCallbacks, MediaEngine};
+
+fn main() {
+    tracing_subscriber::fmt::init();
+
+    let config = ScreenShareConfig {
+        // Record-only mode: an empty server URL skips signaling and WebRTC
+        // entirely and the encoded stream goes straight to the recorder.
+        server_url: String::new(),
+        fallback_urls: Vec::new(),
+        token: String::new(),
+        ice_servers: Vec::new(),
+        target: CaptureTarget::Display(0),
+        encoder: EncoderConfig::default(),
+        track: Default::default(),
+        audio_mode: None,
+        show_cursor: true,
+        record_path: Some("capture.mp4".into()),
+        ..Default::default()
+    };
+
+    let callbacks = EngineCallbacks {
+        on_stats: Box::new(|stats| {
+            println!(
+                "fps={:.1} bitrate={:.0}kbps encoded={} dropped={}",
+                stats.fps, stats.bitrate_kbps, stats.frames_encoded, stats.frames_dropped
+            );
+        }),
+        on_error: Box::new(|error| eprintln!("error [{}]: {error}", error.code())),
+        on_warning: Box::new(|code, message| eprintln!("warning [{code}]: {message}")),
+        on_stopped: Box::new(|reason| println!("stopped: {}", reason.as_str())),
+        on_room_event: Box::new(|_| {}),
+        on_started: Box::new(|timings| println!("started: {timings:?}")),
+        on_connected: Box::new(|_| {}),
+        on_connection_state: Box::new(|_| {}),
+    };
+
+    let engine = MediaEngine::start(config, callbacks).expect("start failed");
+    std::thread::sleep(Duration::from_secs(10));
+    engine.stop();
+    drop(engine);
+    println!("wrote capture.mp4");
+}
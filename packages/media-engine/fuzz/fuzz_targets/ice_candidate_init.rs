@@ -0,0 +1,17 @@
+//! Fuzzes the trickle ICE path: the `candidateInit` JSON the server
+//! relays comes from arbitrary remote peers, and after deserializing, the
+//! candidate line goes through str0m's SDP candidate parser.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use media_engine::transport::livekit::IceCandidateInit;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(init) = serde_json::from_str::<IceCandidateInit>(text) {
+        let _ = str0m::Candidate::from_sdp_string(&init.candidate);
+    }
+});
@@ -0,0 +1,14 @@
+//! Fuzzes SDP answer parsing — the server's answer to our publish offer
+//! goes straight into `SdpAnswer::from_sdp_string` on the transport
+//! thread.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = str0m::change::SdpAnswer::from_sdp_string(text);
+});
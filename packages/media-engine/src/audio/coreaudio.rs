@@ -0,0 +1,437 @@
+//! Core Audio process-tap capture (macOS 14.2+), the macOS counterpart of
+//! the WASAPI loopback path: a `CATapDescription` tap wrapped in a private
+//! aggregate device, pulled through an IOProc. Raw C/objc FFI rather than
+//! an objc binding crate — one class and a handful of calls don't justify
+//! the dependency tree.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{AudioPacket, OPUS_CHANNELS, OPUS_FRAME_MS, OPUS_SAMPLE_RATE};
+use crate::config::{AudioCaptureConfig, AudioMode};
+use crate::error::{EngineError, EngineResult};
+
+type OSStatus = i32;
+type AudioObjectID = u32;
+type CFTypeRef = *const c_void;
+type Id = *mut c_void;
+
+const SYSTEM_OBJECT: AudioObjectID = 1;
+const GLOBAL_SCOPE: u32 = fourcc(b"glob");
+const MAIN_ELEMENT: u32 = 0;
+const TRANSLATE_PID_TO_PROCESS_OBJECT: u32 = fourcc(b"id2p");
+const TAP_FORMAT: u32 = fourcc(b"tfmt");
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*code)
+}
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+#[repr(C)]
+struct AudioStreamBasicDescription {
+    sample_rate: f64,
+    format_id: u32,
+    format_flags: u32,
+    bytes_per_packet: u32,
+    frames_per_packet: u32,
+    bytes_per_frame: u32,
+    channels_per_frame: u32,
+    bits_per_channel: u32,
+    reserved: u32,
+}
+
+#[repr(C)]
+struct AudioBuffer {
+    number_channels: u32,
+    data_byte_size: u32,
+    data: *mut c_void,
+}
+
+#[repr(C)]
+struct AudioBufferList {
+    number_buffers: u32,
+    // Variable-length in C; only the first entry is addressed directly.
+    buffers: [AudioBuffer; 1],
+}
+
+type IoProc = extern "C" fn(
+    AudioObjectID,
+    *const c_void,
+    *const AudioBufferList,
+    *const c_void,
+    *mut AudioBufferList,
+    *const c_void,
+    *mut c_void,
+) -> OSStatus;
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyData(
+        object: AudioObjectID,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> OSStatus;
+    fn AudioHardwareCreateProcessTap(description: Id, tap: *mut AudioObjectID) -> OSStatus;
+    fn AudioHardwareDestroyProcessTap(tap: AudioObjectID) -> OSStatus;
+    fn AudioHardwareCreateAggregateDevice(
+        description: CFTypeRef,
+        device: *mut AudioObjectID,
+    ) -> OSStatus;
+    fn AudioHardwareDestroyAggregateDevice(device: AudioObjectID) -> OSStatus;
+    fn AudioDeviceCreateIOProcID(
+        device: AudioObjectID,
+        proc: IoProc,
+        client_data: *mut c_void,
+        proc_id: *mut *mut c_void,
+    ) -> OSStatus;
+    fn AudioDeviceDestroyIOProcID(device: AudioObjectID, proc_id: *mut c_void) -> OSStatus;
+    fn AudioDeviceStart(device: AudioObjectID, proc_id: *mut c_void) -> OSStatus;
+    fn AudioDeviceStop(device: AudioObjectID, proc_id: *mut c_void) -> OSStatus;
+}
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Id;
+    fn objc_msgSend();
+}
+
+fn check(status: OSStatus, what: &str) -> EngineResult<()> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(EngineError::Audio(format!("{what} failed: OSStatus {status}")))
+    }
+}
+
+/// `[receiver selector]` returning an object pointer.
+unsafe fn msg_send_0(receiver: Id, selector: &CStr) -> Id {
+    let send: extern "C" fn(Id, Id) -> Id = std::mem::transmute(objc_msgSend as *const c_void);
+    send(receiver, sel_registerName(selector.as_ptr()))
+}
+
+/// `[receiver selector:arg]` returning an object pointer.
+unsafe fn msg_send_1(receiver: Id, selector: &CStr, arg: Id) -> Id {
+    let send: extern "C" fn(Id, Id, Id) -> Id = std::mem::transmute(objc_msgSend as *const c_void);
+    send(receiver, sel_registerName(selector.as_ptr()), arg)
+}
+
+/// Translates a PID into the Core Audio process object the tap API wants.
+fn process_object_for_pid(pid: u32) -> EngineResult<AudioObjectID> {
+    let address = AudioObjectPropertyAddress {
+        selector: TRANSLATE_PID_TO_PROCESS_OBJECT,
+        scope: GLOBAL_SCOPE,
+        element: MAIN_ELEMENT,
+    };
+    let pid = pid as i32;
+    let mut object: AudioObjectID = 0;
+    let mut size = std::mem::size_of::<AudioObjectID>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            SYSTEM_OBJECT,
+            &address,
+            std::mem::size_of::<i32>() as u32,
+            &pid as *const i32 as *const c_void,
+            &mut size,
+            &mut object as *mut AudioObjectID as *mut c_void,
+        )
+    };
+    check(status, "pid translation")?;
+    if object == 0 {
+        return Err(EngineError::Audio(format!(
+            "process {pid} has no audio object (not playing audio?)"
+        )));
+    }
+    Ok(object)
+}
+
+/// Builds the `CATapDescription` for the mode: a global stereo mixdown
+/// excluding our own process for `System` (no voice-chat echo), or a
+/// mixdown of just the target process tree for `Process`.
+fn tap_description(mode: AudioMode) -> EngineResult<Id> {
+    unsafe {
+        let class = objc_getClass(c"CATapDescription".as_ptr());
+        if class.is_null() {
+            return Err(EngineError::Audio(
+                "CATapDescription unavailable (macOS 14.2+ required)".into(),
+            ));
+        }
+        let alloc = msg_send_0(class, c"alloc");
+        let (selector, pid) = match mode {
+            AudioMode::System => (c"initStereoGlobalTapButExcludeProcesses:", std::process::id()),
+            AudioMode::Process(pid) => (c"initStereoMixdownOfProcesses:", pid),
+        };
+        let object = process_object_for_pid(pid)?;
+        // NSArray of one NSNumber, via the NSNumber class rather than CF
+        // bridging so the array owns its element.
+        let number_class = objc_getClass(c"NSNumber".as_ptr());
+        let send_u32: extern "C" fn(Id, Id, u32) -> Id =
+            std::mem::transmute(objc_msgSend as *const c_void);
+        let number = send_u32(
+            number_class,
+            sel_registerName(c"numberWithUnsignedInt:".as_ptr()),
+            object,
+        );
+        let array_class = objc_getClass(c"NSArray".as_ptr());
+        let array = msg_send_1(array_class, c"arrayWithObject:", number);
+        let description = msg_send_1(alloc, selector, array);
+        if description.is_null() {
+            return Err(EngineError::Audio("CATapDescription init failed".into()));
+        }
+        Ok(description)
+    }
+}
+
+/// The tap's stream UID, needed to hang it off the aggregate device.
+unsafe fn tap_uid_string(description: Id) -> Id {
+    let uuid = msg_send_0(description, c"UUID");
+    msg_send_0(uuid, c"UUIDString")
+}
+
+/// Builds the aggregate-device description: private (invisible in sound
+/// settings), no sub-devices, one tap with drift compensation.
+unsafe fn aggregate_description(tap_uid: Id) -> Id {
+    let dict_class = objc_getClass(c"NSMutableDictionary".as_ptr());
+    let dict = msg_send_0(dict_class, c"dictionary");
+    let number_class = objc_getClass(c"NSNumber".as_ptr());
+    let send_i32: extern "C" fn(Id, Id, i32) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let yes = send_i32(number_class, sel_registerName(c"numberWithInt:".as_ptr()), 1);
+    let string_class = objc_getClass(c"NSString".as_ptr());
+    let send_str: extern "C" fn(Id, Id, *const c_char) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let utf8 = sel_registerName(c"stringWithUTF8String:".as_ptr());
+    let key = |name: &CStr| send_str(string_class, utf8, name.as_ptr());
+    let set: extern "C" fn(Id, Id, Id, Id) =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let set_sel = sel_registerName(c"setObject:forKey:".as_ptr());
+    set(dict, set_sel, key(c"migo-share-tap"), key(c"uid"));
+    set(dict, set_sel, yes, key(c"private"));
+    let tap_entry = msg_send_0(dict_class, c"dictionary");
+    set(tap_entry, set_sel, tap_uid, key(c"uid"));
+    set(tap_entry, set_sel, yes, key(c"drift"));
+    let array_class = objc_getClass(c"NSArray".as_ptr());
+    let taps = msg_send_1(array_class, c"arrayWithObject:", tap_entry);
+    set(dict, set_sel, taps, key(c"taps"));
+    dict
+}
+
+/// Interleaved f32 chunks handed from the IOProc to the encode loop.
+struct IoState {
+    tx: Sender<Vec<f32>>,
+    channels: u32,
+}
+
+extern "C" fn io_proc(
+    _device: AudioObjectID,
+    _now: *const c_void,
+    input: *const AudioBufferList,
+    _input_time: *const c_void,
+    _output: *mut AudioBufferList,
+    _output_time: *const c_void,
+    client_data: *mut c_void,
+) -> OSStatus {
+    let state = unsafe { &*(client_data as *const IoState) };
+    let list = unsafe { &*input };
+    if list.number_buffers == 0 {
+        return 0;
+    }
+    let buffer = &list.buffers[0];
+    let samples = unsafe {
+        std::slice::from_raw_parts(
+            buffer.data as *const f32,
+            buffer.data_byte_size as usize / std::mem::size_of::<f32>(),
+        )
+    };
+    // Mono taps get duplicated to stereo; anything wider keeps its first
+    // two channels. The tap is requested stereo, so this is belt and
+    // braces.
+    let stereo: Vec<f32> = match state.channels {
+        2 => samples.to_vec(),
+        1 => samples.iter().flat_map(|&s| [s, s]).collect(),
+        n => samples
+            .chunks_exact(n as usize)
+            .flat_map(|frame| [frame[0], frame[1]])
+            .collect(),
+    };
+    let _ = state.tx.send(stereo);
+    0
+}
+
+/// Linear resample of interleaved stereo to the Opus rate. Preview-grade
+/// but inaudible for screen-share content; most devices run 48 kHz and
+/// skip this entirely.
+fn resample_stereo(input: &[f32], from_rate: f64) -> Vec<f32> {
+    let in_frames = input.len() / 2;
+    let out_frames = (in_frames as f64 * f64::from(OPUS_SAMPLE_RATE) / from_rate) as usize;
+    let mut out = Vec::with_capacity(out_frames * 2);
+    for i in 0..out_frames {
+        let pos = i as f64 * from_rate / f64::from(OPUS_SAMPLE_RATE);
+        let base = (pos as usize).min(in_frames.saturating_sub(2));
+        let frac = (pos - base as f64) as f32;
+        for ch in 0..2 {
+            let a = input[base * 2 + ch];
+            let b = input[(base + 1) * 2 + ch];
+            out.push(a + (b - a) * frac);
+        }
+    }
+    out
+}
+
+/// Runs the tap capture loop: pull f32 PCM from the IOProc, resample to
+/// 48 kHz if the device runs at another rate, accumulate 10 ms Opus
+/// frames, encode, and push packets to the transport.
+pub fn run_loopback(
+    config: AudioCaptureConfig,
+    packet_tx: Sender<AudioPacket>,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    let description = tap_description(config.mode)?;
+    let mut tap: AudioObjectID = 0;
+    check(
+        unsafe { AudioHardwareCreateProcessTap(description, &mut tap) },
+        "tap creation",
+    )?;
+
+    // Everything below must tear the tap (and later the device) down on
+    // error, hence the explicit cleanup closure instead of early returns.
+    let result = (|| {
+        let mut format = AudioStreamBasicDescription {
+            sample_rate: 0.0,
+            format_id: 0,
+            format_flags: 0,
+            bytes_per_packet: 0,
+            frames_per_packet: 0,
+            bytes_per_frame: 0,
+            channels_per_frame: 0,
+            bits_per_channel: 0,
+            reserved: 0,
+        };
+        let address = AudioObjectPropertyAddress {
+            selector: TAP_FORMAT,
+            scope: GLOBAL_SCOPE,
+            element: MAIN_ELEMENT,
+        };
+        let mut size = std::mem::size_of::<AudioStreamBasicDescription>() as u32;
+        check(
+            unsafe {
+                AudioObjectGetPropertyData(
+                    tap,
+                    &address,
+                    0,
+                    std::ptr::null(),
+                    &mut size,
+                    &mut format as *mut _ as *mut c_void,
+                )
+            },
+            "tap format query",
+        )?;
+
+        let mut device: AudioObjectID = 0;
+        let aggregate = unsafe { aggregate_description(tap_uid_string(description)) };
+        check(
+            unsafe { AudioHardwareCreateAggregateDevice(aggregate as CFTypeRef, &mut device) },
+            "aggregate device creation",
+        )?;
+
+        let (chunk_tx, chunk_rx) = std::sync::mpsc::channel::<Vec<f32>>();
+        let state = Box::into_raw(Box::new(IoState {
+            tx: chunk_tx,
+            channels: format.channels_per_frame.max(1),
+        }));
+        let mut proc_id: *mut c_void = std::ptr::null_mut();
+        let started = check(
+            unsafe { AudioDeviceCreateIOProcID(device, io_proc, state as *mut c_void, &mut proc_id) },
+            "IOProc creation",
+        )
+        .and_then(|()| check(unsafe { AudioDeviceStart(device, proc_id) }, "device start"));
+        if let Err(e) = started {
+            unsafe {
+                drop(Box::from_raw(state));
+                AudioHardwareDestroyAggregateDevice(device);
+            }
+            return Err(e);
+        }
+
+        let mut encoder = opus::Encoder::new(
+            OPUS_SAMPLE_RATE,
+            opus::Channels::Stereo,
+            opus::Application::Audio,
+        )
+        .map_err(|e| EngineError::Audio(format!("opus init: {e}")))?;
+
+        let frame_samples = (OPUS_SAMPLE_RATE * OPUS_FRAME_MS / 1000) as usize;
+        let mut pcm: Vec<f32> = Vec::with_capacity(frame_samples * OPUS_CHANNELS as usize * 2);
+        let mut sample_offset: u64 = 0;
+        let mut out = vec![0u8; 4000];
+        let needs_resample = (format.sample_rate - f64::from(OPUS_SAMPLE_RATE)).abs() > 1.0;
+
+        let loop_result = loop {
+            if stop.load(Ordering::SeqCst) {
+                break Ok(());
+            }
+            let chunk = match chunk_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(chunk) => chunk,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break Ok(()),
+            };
+            if needs_resample {
+                pcm.extend_from_slice(&resample_stereo(&chunk, format.sample_rate));
+            } else {
+                pcm.extend_from_slice(&chunk);
+            }
+            let mut failed = None;
+            let mut transport_gone = false;
+            while pcm.len() >= frame_samples * OPUS_CHANNELS as usize {
+                let frame: Vec<f32> =
+                    pcm.drain(..frame_samples * OPUS_CHANNELS as usize).collect();
+                let len = match encoder.encode_float(&frame, &mut out) {
+                    Ok(len) => len,
+                    Err(e) => {
+                        failed = Some(EngineError::Audio(format!("opus encode: {e}")));
+                        break;
+                    }
+                };
+                let packet = AudioPacket {
+                    data: out[..len].to_vec(),
+                    sample_offset,
+                };
+                sample_offset += frame_samples as u64;
+                if packet_tx.send(packet).is_err() {
+                    // Transport is gone; stop quietly.
+                    transport_gone = true;
+                    break;
+                }
+            }
+            if let Some(e) = failed {
+                break Err(e);
+            }
+            if transport_gone {
+                break Ok(());
+            }
+        };
+
+        unsafe {
+            AudioDeviceStop(device, proc_id);
+            AudioDeviceDestroyIOProcID(device, proc_id);
+            AudioHardwareDestroyAggregateDevice(device);
+            drop(Box::from_raw(state));
+        }
+        loop_result
+    })();
+
+    unsafe { AudioHardwareDestroyProcessTap(tap) };
+    result
+}
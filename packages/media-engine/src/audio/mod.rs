@@ -0,0 +1,54 @@
+//! Process-loopback audio capture and Opus encoding.
+
+#[cfg(target_os = "macos")]
+pub mod coreaudio;
+#[cfg(target_os = "linux")]
+pub mod pulse;
+#[cfg(windows)]
+pub mod wasapi;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::config::AudioCaptureConfig;
+use crate::error::{EngineError, EngineResult};
+
+/// One Opus packet ready for RTP packetization, 10 ms of 48 kHz stereo.
+pub struct AudioPacket {
+    pub data: Vec<u8>,
+    /// Samples per channel since session start.
+    pub sample_offset: u64,
+}
+
+pub const OPUS_SAMPLE_RATE: u32 = 48_000;
+pub const OPUS_CHANNELS: u32 = 2;
+pub const OPUS_FRAME_MS: u32 = 10;
+
+/// Runs loopback capture + Opus encode on the calling thread until `stop`
+/// is set.
+pub fn run_audio_capture(
+    config: AudioCaptureConfig,
+    packet_tx: Sender<AudioPacket>,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    #[cfg(windows)]
+    {
+        wasapi::run_loopback(config, packet_tx, stop)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        coreaudio::run_loopback(config, packet_tx, stop)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        pulse::run_loopback(config, packet_tx, stop)
+    }
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (config, packet_tx, stop);
+        Err(EngineError::Audio(
+            "audio capture is not implemented on this platform".into(),
+        ))
+    }
+}
@@ -0,0 +1,150 @@
+//! PulseAudio capture for Linux (also served by PipeWire's PulseAudio
+//! shim, which is what every current desktop runs). `System` mode records
+//! the default sink's monitor source; the server resamples to the Opus
+//! format for us, so the loop is just read → accumulate → encode.
+//!
+//! `Process` mode needs native PipeWire per-application streams — the
+//! PulseAudio API can't follow one app across sinks — and is reported as
+//! unsupported rather than approximated with the whole mix.
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use super::{AudioPacket, OPUS_CHANNELS, OPUS_FRAME_MS, OPUS_SAMPLE_RATE};
+use crate::config::{AudioCaptureConfig, AudioMode};
+use crate::error::{EngineError, EngineResult};
+
+const PA_SAMPLE_FLOAT32LE: c_int = 5;
+const PA_STREAM_RECORD: c_int = 2;
+
+#[repr(C)]
+struct PaSampleSpec {
+    format: c_int,
+    rate: u32,
+    channels: u8,
+}
+
+#[link(name = "pulse-simple")]
+#[link(name = "pulse")]
+extern "C" {
+    fn pa_simple_new(
+        server: *const c_char,
+        name: *const c_char,
+        dir: c_int,
+        dev: *const c_char,
+        stream_name: *const c_char,
+        ss: *const PaSampleSpec,
+        map: *const c_void,
+        attr: *const c_void,
+        error: *mut c_int,
+    ) -> *mut c_void;
+    fn pa_simple_read(s: *mut c_void, data: *mut c_void, bytes: usize, error: *mut c_int) -> c_int;
+    fn pa_simple_free(s: *mut c_void);
+    fn pa_strerror(error: c_int) -> *const c_char;
+}
+
+fn pulse_error(error: c_int) -> String {
+    unsafe {
+        let msg = pa_strerror(error);
+        if msg.is_null() {
+            format!("error {error}")
+        } else {
+            CStr::from_ptr(msg).to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// Runs the monitor capture loop: read f32 PCM from the default sink's
+/// monitor at the Opus format, accumulate 10 ms frames, encode, and push
+/// packets to the transport.
+pub fn run_loopback(
+    config: AudioCaptureConfig,
+    packet_tx: Sender<AudioPacket>,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    if let AudioMode::Process(pid) = config.mode {
+        return Err(EngineError::Audio(format!(
+            "per-process audio (pid {pid}) is not supported on Linux; use system audio"
+        )));
+    }
+
+    let spec = PaSampleSpec {
+        format: PA_SAMPLE_FLOAT32LE,
+        rate: OPUS_SAMPLE_RATE,
+        channels: OPUS_CHANNELS as u8,
+    };
+    let mut error: c_int = 0;
+    let stream = unsafe {
+        pa_simple_new(
+            std::ptr::null(),
+            c"migo".as_ptr(),
+            PA_STREAM_RECORD,
+            c"@DEFAULT_MONITOR@".as_ptr(),
+            c"screen share audio".as_ptr(),
+            &spec,
+            std::ptr::null(),
+            std::ptr::null(),
+            &mut error,
+        )
+    };
+    if stream.is_null() {
+        return Err(EngineError::Audio(format!(
+            "monitor capture failed: {}",
+            pulse_error(error)
+        )));
+    }
+
+    let result = (|| {
+        let mut encoder = opus::Encoder::new(
+            OPUS_SAMPLE_RATE,
+            opus::Channels::Stereo,
+            opus::Application::Audio,
+        )
+        .map_err(|e| EngineError::Audio(format!("opus init: {e}")))?;
+
+        let frame_samples = (OPUS_SAMPLE_RATE * OPUS_FRAME_MS / 1000) as usize;
+        let frame_floats = frame_samples * OPUS_CHANNELS as usize;
+        let mut frame = vec![0f32; frame_floats];
+        let mut sample_offset: u64 = 0;
+        let mut out = vec![0u8; 4000];
+
+        // pa_simple_read blocks until a full frame arrives, so each
+        // iteration is one 10 ms Opus frame and stop latency is bounded
+        // by the frame length.
+        while !stop.load(Ordering::SeqCst) {
+            let mut error: c_int = 0;
+            let read = unsafe {
+                pa_simple_read(
+                    stream,
+                    frame.as_mut_ptr() as *mut c_void,
+                    frame_floats * std::mem::size_of::<f32>(),
+                    &mut error,
+                )
+            };
+            if read < 0 {
+                return Err(EngineError::Audio(format!(
+                    "monitor read failed: {}",
+                    pulse_error(error)
+                )));
+            }
+            let len = encoder
+                .encode_float(&frame, &mut out)
+                .map_err(|e| EngineError::Audio(format!("opus encode: {e}")))?;
+            let packet = AudioPacket {
+                data: out[..len].to_vec(),
+                sample_offset,
+            };
+            sample_offset += frame_samples as u64;
+            if packet_tx.send(packet).is_err() {
+                // Transport is gone; stop quietly.
+                return Ok(());
+            }
+        }
+        Ok(())
+    })();
+
+    unsafe { pa_simple_free(stream) };
+    result
+}
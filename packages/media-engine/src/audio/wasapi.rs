@@ -0,0 +1,182 @@
+//! WASAPI process-loopback capture (Windows 10 2004+), mirroring the approach
+//! of the client's C++ addon: `ActivateAudioInterfaceAsync` with
+//! `AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use windows::core::HSTRING;
+use windows::Win32::Media::Audio::{
+    ActivateAudioInterfaceAsync, IActivateAudioInterfaceAsyncOperation,
+    IActivateAudioInterfaceCompletionHandler, IAudioCaptureClient, IAudioClient,
+    AUDIOCLIENT_ACTIVATION_PARAMS, AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+    AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS, AUDCLNT_SHAREMODE_SHARED,
+    AUDCLNT_STREAMFLAGS_LOOPBACK, PROCESS_LOOPBACK_MODE_EXCLUDE_TARGET_PROCESS_TREE,
+    PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE, VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+    WAVEFORMATEX,
+};
+
+use super::{AudioPacket, OPUS_CHANNELS, OPUS_FRAME_MS, OPUS_SAMPLE_RATE};
+use crate::config::{AudioCaptureConfig, AudioMode};
+use crate::error::{EngineError, EngineResult};
+
+/// Activates a process-loopback audio client for the given mode. For
+/// `System` mode we exclude our own process tree so voice chat isn't echoed
+/// back into the share.
+fn activate_loopback_client(mode: AudioMode) -> EngineResult<IAudioClient> {
+    let (pid, loopback_mode) = match mode {
+        AudioMode::System => (
+            std::process::id(),
+            PROCESS_LOOPBACK_MODE_EXCLUDE_TARGET_PROCESS_TREE,
+        ),
+        AudioMode::Process(pid) => (pid, PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE),
+    };
+
+    let mut params = AUDIOCLIENT_ACTIVATION_PARAMS {
+        ActivationType: AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+        ..Default::default()
+    };
+    params.Anonymous.ProcessLoopbackParams = AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
+        TargetProcessId: pid,
+        ProcessLoopbackMode: loopback_mode,
+    };
+
+    // ActivateAudioInterfaceAsync completes on a worker thread; wait on a
+    // channel from the completion handler.
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<windows::core::Result<IAudioClient>>();
+
+    #[windows::core::implement(IActivateAudioInterfaceCompletionHandler)]
+    struct Handler(std::sync::mpsc::Sender<windows::core::Result<IAudioClient>>);
+
+    impl IActivateAudioInterfaceCompletionHandler_Impl for Handler_Impl {
+        fn ActivateCompleted(
+            &self,
+            op: Option<&IActivateAudioInterfaceAsyncOperation>,
+        ) -> windows::core::Result<()> {
+            let result = (|| {
+                let op = op.ok_or_else(windows::core::Error::empty)?;
+                let mut hr = windows::core::HRESULT(0);
+                let mut unk = None;
+                unsafe { op.GetActivateResult(&mut hr, &mut unk)? };
+                hr.ok()?;
+                let unk: windows::core::IUnknown =
+                    unk.ok_or_else(windows::core::Error::empty)?;
+                use windows::core::Interface;
+                unk.cast::<IAudioClient>()
+            })();
+            let _ = self.0.send(result);
+            Ok(())
+        }
+    }
+
+    let handler: IActivateAudioInterfaceCompletionHandler = Handler(done_tx).into();
+    let prop_variant = windows::Win32::System::Com::StructuredStorage::PROPVARIANT::default();
+    let _ = &prop_variant;
+    unsafe {
+        let params_blob = windows::Win32::System::Com::StructuredStorage::PROPVARIANT::from(
+            std::slice::from_raw_parts(
+                &params as *const _ as *const u8,
+                std::mem::size_of::<AUDIOCLIENT_ACTIVATION_PARAMS>(),
+            ),
+        );
+        ActivateAudioInterfaceAsync(
+            &HSTRING::from(VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK),
+            &IAudioClient::IID,
+            Some(&params_blob),
+            &handler,
+        )?;
+    }
+
+    done_rx
+        .recv_timeout(Duration::from_secs(5))
+        .map_err(|_| EngineError::Audio("audio activation timed out".into()))?
+        .map_err(|e| EngineError::Audio(format!("audio activation failed: {e}")))
+}
+
+/// Runs the loopback capture loop: pull 32-bit float PCM from WASAPI,
+/// accumulate 10 ms Opus frames, encode, and push packets to the transport.
+pub fn run_loopback(
+    config: AudioCaptureConfig,
+    packet_tx: Sender<AudioPacket>,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    let client = activate_loopback_client(config.mode)?;
+
+    let format = WAVEFORMATEX {
+        wFormatTag: 3, // WAVE_FORMAT_IEEE_FLOAT
+        nChannels: OPUS_CHANNELS as u16,
+        nSamplesPerSec: OPUS_SAMPLE_RATE,
+        nAvgBytesPerSec: OPUS_SAMPLE_RATE * OPUS_CHANNELS * 4,
+        nBlockAlign: (OPUS_CHANNELS * 4) as u16,
+        wBitsPerSample: 32,
+        cbSize: 0,
+    };
+
+    unsafe {
+        client.Initialize(
+            AUDCLNT_SHAREMODE_SHARED,
+            AUDCLNT_STREAMFLAGS_LOOPBACK,
+            // 100 ms buffer in 100-ns units.
+            1_000_000,
+            0,
+            &format,
+            None,
+        )?;
+    }
+    let capture: IAudioCaptureClient = unsafe { client.GetService()? };
+    unsafe { client.Start()? };
+
+    let mut encoder = opus::Encoder::new(
+        OPUS_SAMPLE_RATE,
+        opus::Channels::Stereo,
+        opus::Application::Audio,
+    )
+    .map_err(|e| EngineError::Audio(format!("opus init: {e}")))?;
+
+    let frame_samples = (OPUS_SAMPLE_RATE * OPUS_FRAME_MS / 1000) as usize;
+    let mut pcm: Vec<f32> = Vec::with_capacity(frame_samples * OPUS_CHANNELS as usize * 2);
+    let mut sample_offset: u64 = 0;
+    let mut out = vec![0u8; 4000];
+
+    while !stop.load(Ordering::SeqCst) {
+        let packet_len = unsafe { capture.GetNextPacketSize()? };
+        if packet_len == 0 {
+            std::thread::sleep(Duration::from_millis(2));
+            continue;
+        }
+        unsafe {
+            let mut data_ptr = std::ptr::null_mut();
+            let mut frames_read = 0u32;
+            let mut flags = 0u32;
+            capture.GetBuffer(&mut data_ptr, &mut frames_read, &mut flags, None, None)?;
+            let samples = std::slice::from_raw_parts(
+                data_ptr as *const f32,
+                frames_read as usize * OPUS_CHANNELS as usize,
+            );
+            pcm.extend_from_slice(samples);
+            capture.ReleaseBuffer(frames_read)?;
+        }
+
+        while pcm.len() >= frame_samples * OPUS_CHANNELS as usize {
+            let frame: Vec<f32> = pcm.drain(..frame_samples * OPUS_CHANNELS as usize).collect();
+            let len = encoder
+                .encode_float(&frame, &mut out)
+                .map_err(|e| EngineError::Audio(format!("opus encode: {e}")))?;
+            let packet = AudioPacket {
+                data: out[..len].to_vec(),
+                sample_offset,
+            };
+            sample_offset += frame_samples as u64;
+            if packet_tx.send(packet).is_err() {
+                // Transport is gone; stop quietly.
+                unsafe { client.Stop().ok() };
+                return Ok(());
+            }
+        }
+    }
+
+    unsafe { client.Stop().ok() };
+    Ok(())
+}
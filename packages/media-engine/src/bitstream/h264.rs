@@ -0,0 +1,237 @@
+//! H.264 Annex-B parsing: NAL unit splitting and SPS/PPS bookkeeping.
+
+/// NAL unit type for an IDR slice.
+pub const NAL_IDR: u8 = 5;
+/// NAL unit type for a sequence parameter set.
+pub const NAL_SPS: u8 = 7;
+/// NAL unit type for a picture parameter set.
+pub const NAL_PPS: u8 = 8;
+/// NAL unit type for an access unit delimiter.
+pub const NAL_AUD: u8 = 9;
+
+/// The NAL unit type of a unit's first byte.
+pub fn nal_type(nal: &[u8]) -> u8 {
+    nal[0] & 0x1f
+}
+
+/// Iterates the NAL units of an Annex-B stream (3- or 4-byte start
+/// codes). Shared by the sinks that re-frame the encoder's output (FLV,
+/// RTP, MP4/MKV).
+pub fn annex_b_units(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 {
+            if data[i + 2] == 1 {
+                starts.push((i + 3, i));
+                i += 3;
+                continue;
+            }
+            if i + 4 <= data.len() && data[i + 2] == 0 && data[i + 3] == 1 {
+                starts.push((i + 4, i));
+                i += 4;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    let mut units = Vec::with_capacity(starts.len());
+    for (idx, &(begin, _)) in starts.iter().enumerate() {
+        let end = starts.get(idx + 1).map(|&(_, sc)| sc).unwrap_or(data.len());
+        if end > begin {
+            units.push(&data[begin..end]);
+        }
+    }
+    units.into_iter()
+}
+
+/// Fields parsed out of a sequence parameter set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpsInfo {
+    pub profile_idc: u8,
+    pub level_idc: u8,
+    /// Display frame size after cropping, in pixels.
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Parses profile, level, and cropped dimensions from an SPS NAL unit
+/// (header byte included, start code stripped). Returns `None` for
+/// malformed input, and for the rare scaling-matrix streams this parser
+/// doesn't follow — callers validating encoder output never see those.
+pub fn parse_sps(nal: &[u8]) -> Option<SpsInfo> {
+    if nal.is_empty() || nal_type(nal) != NAL_SPS {
+        return None;
+    }
+    let rbsp = strip_emulation_prevention(&nal[1..]);
+    let mut r = BitReader::new(&rbsp);
+    let profile_idc = r.bits(8)? as u8;
+    let _constraint_flags = r.bits(8)?;
+    let level_idc = r.bits(8)? as u8;
+    let _sps_id = r.ue()?;
+    let mut chroma_format_idc = 1;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            r.bits(1)?; // separate_colour_plane_flag
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.bits(1)?; // qpprime_y_zero_transform_bypass_flag
+        if r.bits(1)? == 1 {
+            return None; // seq_scaling_matrix_present_flag
+        }
+    }
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.bits(1)?; // delta_pic_order_always_zero_flag
+            r.se()?; // offset_for_non_ref_pic
+            r.se()?; // offset_for_top_to_bottom_field
+            let cycle = r.ue()?;
+            for _ in 0..cycle {
+                r.se()?;
+            }
+        }
+        _ => {}
+    }
+    r.ue()?; // max_num_ref_frames
+    r.bits(1)?; // gaps_in_frame_num_value_allowed_flag
+    let width_in_mbs = r.ue()? + 1;
+    let height_in_map_units = r.ue()? + 1;
+    let frame_mbs_only = r.bits(1)?;
+    if frame_mbs_only == 0 {
+        r.bits(1)?; // mb_adaptive_frame_field_flag
+    }
+    r.bits(1)?; // direct_8x8_inference_flag
+    let (mut crop_left, mut crop_right, mut crop_top, mut crop_bottom) = (0, 0, 0, 0);
+    if r.bits(1)? == 1 {
+        crop_left = r.ue()?;
+        crop_right = r.ue()?;
+        crop_top = r.ue()?;
+        crop_bottom = r.ue()?;
+    }
+    // Crop units per the spec's table: chroma subsampling decides how many
+    // pixels one crop step removes.
+    let (crop_unit_x, crop_unit_y) = match chroma_format_idc {
+        0 => (1, 2 - frame_mbs_only),
+        2 => (2, 2 - frame_mbs_only),
+        3 => (1, 2 - frame_mbs_only),
+        _ => (2, 2 * (2 - frame_mbs_only)), // 4:2:0
+    };
+    Some(SpsInfo {
+        profile_idc,
+        level_idc,
+        width: width_in_mbs * 16 - (crop_left + crop_right) * crop_unit_x,
+        height: (2 - frame_mbs_only) * height_in_map_units * 16
+            - (crop_top + crop_bottom) * crop_unit_y,
+    })
+}
+
+/// Removes the 00 00 03 emulation-prevention bytes from RBSP data.
+fn strip_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &byte in data {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        out.push(byte);
+    }
+    out
+}
+
+/// MSB-first bit reader over RBSP bytes, with the Exp-Golomb reads the
+/// SPS syntax uses. Every read is checked; running off the end yields
+/// `None` rather than garbage.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            let bit = (byte >> (7 - self.pos % 8)) & 1;
+            value = value << 1 | bit as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Unsigned Exp-Golomb.
+    fn ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while self.bits(1)? == 0 {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+        Some((1u32 << leading_zeros) - 1 + self.bits(leading_zeros)?)
+    }
+
+    /// Signed Exp-Golomb.
+    fn se(&mut self) -> Option<i32> {
+        let code = self.ue()?;
+        let magnitude = code.div_ceil(2) as i32;
+        Some(if code % 2 == 1 { magnitude } else { -magnitude })
+    }
+}
+
+/// Caches the most recent SPS/PPS and re-inserts them ahead of keyframes
+/// that lack them. Some hardware MFTs emit parameter sets only on the
+/// very first IDR; anything joining mid-stream — a late subscriber, a
+/// recorder started later, a reconnected ingest — then gets undecodable
+/// video. The encode loop runs every frame through this before fan-out.
+#[derive(Default)]
+pub struct ParameterSets {
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+}
+
+impl ParameterSets {
+    /// Caches any parameter sets present in the access unit.
+    pub fn observe(&mut self, data: &[u8]) {
+        for nal in annex_b_units(data) {
+            match nal_type(nal) {
+                NAL_SPS => self.sps = Some(nal.to_vec()),
+                NAL_PPS => self.pps = Some(nal.to_vec()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Prepends the cached SPS/PPS (4-byte start codes) to a keyframe
+    /// access unit that doesn't already carry its own. No-op until both
+    /// have been observed.
+    pub fn ensure_keyframe_parameter_sets(&self, data: &mut Vec<u8>) {
+        let (Some(sps), Some(pps)) = (&self.sps, &self.pps) else {
+            return;
+        };
+        if annex_b_units(data).any(|nal| nal_type(nal) == NAL_SPS) {
+            return;
+        }
+        let mut with_sets = Vec::with_capacity(sps.len() + pps.len() + 8 + data.len());
+        with_sets.extend_from_slice(&[0, 0, 0, 1]);
+        with_sets.extend_from_slice(sps);
+        with_sets.extend_from_slice(&[0, 0, 0, 1]);
+        with_sets.extend_from_slice(pps);
+        with_sets.extend_from_slice(data);
+        *data = with_sets;
+    }
+}
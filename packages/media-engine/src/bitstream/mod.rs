@@ -0,0 +1,3 @@
+//! Codec bitstream utilities shared across the sinks and the transport.
+
+pub mod h264;
@@ -0,0 +1,152 @@
+//! Capability probing: which hardware encoders, capture, and audio paths
+//! this machine supports, so the app can pre-configure UI options instead
+//! of failing at start time.
+
+/// One available hardware encoder MFT.
+pub struct EncoderInfo {
+    /// "h264", "hevc", or "av1".
+    pub codec: &'static str,
+    /// MFT friendly name, e.g. "NVIDIA H.264 Encoder MFT".
+    pub name: String,
+    /// "nvidia", "amd", "intel", or "unknown", inferred from the name.
+    pub vendor: &'static str,
+}
+
+pub struct Capabilities {
+    pub encoders: Vec<EncoderInfo>,
+    /// Windows Graphics Capture is available (Windows 10 1803+).
+    pub wgc: bool,
+    /// Process-specific audio loopback is available (Windows 10 2004+).
+    pub process_loopback_audio: bool,
+    /// Hard ceiling the pipeline accepts — the H.264 level 5.2 limit, not
+    /// a per-device probe.
+    pub max_encode_width: u32,
+    pub max_encode_height: u32,
+    /// Always false today: the pipeline is 8-bit BGRA → NV12 end to end.
+    pub hdr: bool,
+}
+
+const MAX_ENCODE_WIDTH: u32 = 4096;
+const MAX_ENCODE_HEIGHT: u32 = 2304;
+
+/// "nvidia", "amd", "intel", or "unknown", inferred from an MFT friendly
+/// name. Also used to tag the active encoder in session stats.
+pub(crate) fn vendor_from_name(name: &str) -> &'static str {
+    let lower = name.to_ascii_lowercase();
+    if lower.contains("nvidia") {
+        "nvidia"
+    } else if lower.contains("amd") || lower.contains("radeon") {
+        "amd"
+    } else if lower.contains("intel") || lower.contains("quick sync") {
+        "intel"
+    } else {
+        "unknown"
+    }
+}
+
+#[cfg(windows)]
+pub fn probe() -> Capabilities {
+    use windows::Win32::Media::MediaFoundation::{
+        MFVideoFormat_AV1, MFVideoFormat_H264, MFVideoFormat_HEVC,
+    };
+
+    let mut encoders = Vec::new();
+    for (codec, subtype) in [
+        ("h264", MFVideoFormat_H264),
+        ("hevc", MFVideoFormat_HEVC),
+        ("av1", MFVideoFormat_AV1),
+    ] {
+        encoders.extend(windows_impl::enumerate_encoders(codec, subtype));
+    }
+
+    Capabilities {
+        encoders,
+        wgc: windows::Graphics::Capture::GraphicsCaptureSession::IsSupported()
+            .unwrap_or(false),
+        process_loopback_audio: windows_impl::has_process_loopback(),
+        max_encode_width: MAX_ENCODE_WIDTH,
+        max_encode_height: MAX_ENCODE_HEIGHT,
+        hdr: false,
+    }
+}
+
+#[cfg(not(windows))]
+pub fn probe() -> Capabilities {
+    Capabilities {
+        encoders: Vec::new(),
+        wgc: false,
+        process_loopback_audio: false,
+        max_encode_width: MAX_ENCODE_WIDTH,
+        max_encode_height: MAX_ENCODE_HEIGHT,
+        hdr: false,
+    }
+}
+
+#[cfg(windows)]
+mod windows_impl {
+    use windows::core::{GUID, HSTRING, PWSTR};
+    use windows::Win32::Media::MediaFoundation::{
+        MFStartup, MFTEnumEx, MFMediaType_Video, MFSTARTUP_FULL, MFT_CATEGORY_VIDEO_ENCODER,
+        MFT_ENUM_FLAG_HARDWARE, MFT_ENUM_FLAG_SORTANDFILTER, MFT_FRIENDLY_NAME_Attribute,
+        MFT_REGISTER_TYPE_INFO,
+    };
+    use windows::Win32::System::Com::CoTaskMemFree;
+
+    use super::{vendor_from_name, EncoderInfo};
+
+    pub fn enumerate_encoders(codec: &'static str, subtype: GUID) -> Vec<EncoderInfo> {
+        unsafe {
+            let _ = MFStartup(
+                windows::Win32::Media::MediaFoundation::MF_VERSION,
+                MFSTARTUP_FULL,
+            );
+            let output_info = MFT_REGISTER_TYPE_INFO {
+                guidMajorType: MFMediaType_Video,
+                guidSubtype: subtype,
+            };
+            let mut activates = std::ptr::null_mut();
+            let mut count = 0u32;
+            if MFTEnumEx(
+                MFT_CATEGORY_VIDEO_ENCODER,
+                MFT_ENUM_FLAG_HARDWARE | MFT_ENUM_FLAG_SORTANDFILTER,
+                None,
+                Some(&output_info),
+                &mut activates,
+                &mut count,
+            )
+            .is_err()
+            {
+                return Vec::new();
+            }
+            let activates = std::slice::from_raw_parts(activates, count as usize);
+            activates
+                .iter()
+                .flatten()
+                .filter_map(|activate| {
+                    let mut value = PWSTR::null();
+                    let mut length = 0u32;
+                    activate
+                        .GetAllocatedString(&MFT_FRIENDLY_NAME_Attribute, &mut value, &mut length)
+                        .ok()?;
+                    let name = value.to_string().ok()?;
+                    CoTaskMemFree(Some(value.as_ptr() as *const _));
+                    Some(EncoderInfo {
+                        codec,
+                        vendor: vendor_from_name(&name),
+                        name,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Process-specific loopback shipped in Windows 10 2004, which is
+    /// Universal API Contract 10.
+    pub fn has_process_loopback() -> bool {
+        windows::Foundation::Metadata::ApiInformation::IsApiContractPresentByMajor(
+            &HSTRING::from("Windows.Foundation.UniversalApiContract"),
+            10,
+        )
+        .unwrap_or(false)
+    }
+}
@@ -0,0 +1,150 @@
+//! Enumeration of capturable displays and windows.
+
+/// A top-level window that can be captured.
+#[derive(Debug, Clone)]
+pub struct WindowInfo {
+    /// Used as `target_id` for window shares: the raw HWND value on
+    /// Windows, scap's window id elsewhere.
+    pub hwnd: u64,
+    pub title: String,
+    /// Zero on platforms where the enumeration doesn't expose it.
+    pub process_id: u32,
+}
+
+/// A connected display.
+#[derive(Debug, Clone)]
+pub struct DisplayInfo {
+    /// Index in enumeration order, used as `target_id` for display shares.
+    pub index: usize,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub is_primary: bool,
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::{DisplayInfo, WindowInfo};
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT, TRUE};
+    use windows::Win32::Graphics::Gdi::{
+        EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFOEXW, MONITORINFOF_PRIMARY,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowLongW, GetWindowTextLengthW, GetWindowTextW,
+        GetWindowThreadProcessId, IsWindowVisible, GWL_EXSTYLE, WS_EX_TOOLWINDOW,
+    };
+
+    pub fn list_windows() -> Vec<WindowInfo> {
+        let mut out: Vec<WindowInfo> = Vec::new();
+        unsafe extern "system" fn cb(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let out = &mut *(lparam.0 as *mut Vec<WindowInfo>);
+            if !IsWindowVisible(hwnd).as_bool() {
+                return TRUE;
+            }
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+            if ex_style & WS_EX_TOOLWINDOW.0 != 0 {
+                return TRUE;
+            }
+            let len = GetWindowTextLengthW(hwnd);
+            if len == 0 {
+                return TRUE;
+            }
+            let mut buf = vec![0u16; len as usize + 1];
+            let read = GetWindowTextW(hwnd, &mut buf);
+            let title = String::from_utf16_lossy(&buf[..read as usize]);
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            out.push(WindowInfo {
+                hwnd: hwnd.0 as u64,
+                title,
+                process_id: pid,
+            });
+            TRUE
+        }
+        unsafe {
+            let _ = EnumWindows(Some(cb), LPARAM(&mut out as *mut _ as isize));
+        }
+        out
+    }
+
+    pub fn list_displays() -> Vec<DisplayInfo> {
+        let mut out: Vec<DisplayInfo> = Vec::new();
+        unsafe extern "system" fn cb(
+            hmonitor: HMONITOR,
+            _hdc: HDC,
+            _rect: *mut RECT,
+            lparam: LPARAM,
+        ) -> BOOL {
+            let out = &mut *(lparam.0 as *mut Vec<DisplayInfo>);
+            let mut info = MONITORINFOEXW::default();
+            info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+            if GetMonitorInfoW(hmonitor, &mut info.monitorInfo).as_bool() {
+                let rect = info.monitorInfo.rcMonitor;
+                let name = String::from_utf16_lossy(
+                    &info.szDevice[..info
+                        .szDevice
+                        .iter()
+                        .position(|&c| c == 0)
+                        .unwrap_or(info.szDevice.len())],
+                );
+                out.push(DisplayInfo {
+                    index: out.len(),
+                    name,
+                    width: (rect.right - rect.left) as u32,
+                    height: (rect.bottom - rect.top) as u32,
+                    is_primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+                });
+            }
+            TRUE
+        }
+        unsafe {
+            let _ = EnumDisplayMonitors(None, None, Some(cb), LPARAM(&mut out as *mut _ as isize));
+        }
+        out
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    //! scap-backed enumeration matching the ids the scap capture backend
+    //! resolves. scap's target list carries no size, process, or primary
+    //! flag, so those fields are zeroed and the first display is treated
+    //! as primary.
+
+    use super::{DisplayInfo, WindowInfo};
+    use scap::Target;
+
+    pub fn list_windows() -> Vec<WindowInfo> {
+        scap::get_all_targets()
+            .into_iter()
+            .filter_map(|target| match target {
+                Target::Window(window) if !window.title.is_empty() => Some(WindowInfo {
+                    hwnd: window.id as u64,
+                    title: window.title,
+                    process_id: 0,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn list_displays() -> Vec<DisplayInfo> {
+        scap::get_all_targets()
+            .into_iter()
+            .filter_map(|target| match target {
+                Target::Display(display) => Some(display.title),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(index, name)| DisplayInfo {
+                index,
+                name,
+                width: 0,
+                height: 0,
+                is_primary: index == 0,
+            })
+            .collect()
+    }
+}
+
+pub use imp::{list_displays, list_windows};
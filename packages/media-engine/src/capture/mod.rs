@@ -0,0 +1,319 @@
+//! Frame sources: screen capture via Windows Graphics Capture (WGC) with
+//! a scap-based fallback for other platforms, and webcams via the Media
+//! Foundation source reader.
+
+#[cfg(windows)]
+pub mod camera;
+pub mod enumerate;
+pub mod scap_backend;
+#[cfg(windows)]
+pub mod wgc;
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use crate::error::{EngineError, EngineResult};
+
+/// What to capture, resolved from `ScreenShareConfig` before the capture
+/// thread starts.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureTarget {
+    /// Index into the monitor enumeration order.
+    Display(usize),
+    /// Raw HWND value.
+    Window(u64),
+    /// Index into the camera enumeration order.
+    Camera(usize),
+}
+
+/// A single captured frame in BGRA, tightly packed (`width * 4` stride).
+pub struct CaptureFrame {
+    pub data: FrameBuffer,
+    pub width: u32,
+    pub height: u32,
+    /// QPC value from the WGC frame's `SystemRelativeTime`.
+    pub qpc: i64,
+}
+
+/// How many buffers a [`FramePool`] keeps around. The capture → encode
+/// channel holds two frames, plus one in flight on each side; beyond
+/// that, buffers are resolution-change leftovers worth freeing.
+const MAX_POOLED_BUFFERS: usize = 4;
+
+/// Recycles the multi-megabyte BGRA buffers between capture and encode,
+/// so steady-state capture does zero heap allocation instead of a fresh
+/// `Vec` per frame (allocator churn and memory spikes at 4K60). Backends
+/// acquire from the pool; the buffer finds its own way back when the
+/// frame is dropped, wherever that happens.
+pub struct FramePool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl FramePool {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            buffers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// A buffer of exactly `len` bytes, reusing a pooled allocation when
+    /// one is available. Contents are whatever the last frame left; the
+    /// caller overwrites every byte.
+    pub fn acquire(self: &Arc<Self>, len: usize) -> FrameBuffer {
+        let mut data = self
+            .buffers
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_default();
+        data.resize(len, 0);
+        FrameBuffer {
+            data,
+            pool: Arc::downgrade(self),
+        }
+    }
+}
+
+/// A BGRA buffer that returns to its [`FramePool`] on drop. Unpooled
+/// buffers (the scap and camera backends hand over owned `Vec`s) just
+/// deallocate, as do buffers whose pool is already gone.
+pub struct FrameBuffer {
+    data: Vec<u8>,
+    pool: Weak<FramePool>,
+}
+
+impl From<Vec<u8>> for FrameBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            pool: Weak::new(),
+        }
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.data
+    }
+}
+
+impl DerefMut for FrameBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            let mut buffers = pool.buffers.lock().unwrap();
+            if buffers.len() < MAX_POOLED_BUFFERS {
+                buffers.push(std::mem::take(&mut self.data));
+            }
+        }
+    }
+}
+
+/// Creates a latest-frame slot connecting a capture thread to an encode
+/// loop. Capture always publishes into the single slot, replacing
+/// whatever the encoder hasn't picked up yet, so the encoder reads the
+/// newest frame directly — no queue to drain, no backlog to work
+/// through after a stall. Replaced frames are counted and their buffers
+/// go straight back to the [`FramePool`].
+pub fn frame_slot() -> (FrameSender, FrameReceiver) {
+    let slot = Arc::new(Slot {
+        state: Mutex::new(SlotState {
+            frame: None,
+            dropped: 0,
+            senders: 1,
+            receiver_alive: true,
+        }),
+        arrived: Condvar::new(),
+    });
+    (
+        FrameSender { slot: slot.clone() },
+        FrameReceiver { slot },
+    )
+}
+
+struct Slot {
+    state: Mutex<SlotState>,
+    arrived: Condvar,
+}
+
+struct SlotState {
+    frame: Option<CaptureFrame>,
+    /// Frames replaced before the receiver took them, since the last
+    /// `take_dropped`.
+    dropped: u64,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+/// The capture side of a [`frame_slot`]. Cloned when backends hand the
+/// session between threads; the receiver sees a disconnect once every
+/// clone is gone.
+pub struct FrameSender {
+    slot: Arc<Slot>,
+}
+
+impl FrameSender {
+    /// Publishes a frame, replacing any frame the receiver hasn't taken
+    /// yet. Never blocks; fails only when the receiver is gone.
+    pub fn send(&self, frame: CaptureFrame) -> Result<(), SendError<CaptureFrame>> {
+        let mut state = self.slot.state.lock().unwrap();
+        if !state.receiver_alive {
+            return Err(SendError(frame));
+        }
+        if state.frame.replace(frame).is_some() {
+            state.dropped += 1;
+        }
+        drop(state);
+        self.slot.arrived.notify_one();
+        Ok(())
+    }
+}
+
+impl Clone for FrameSender {
+    fn clone(&self) -> Self {
+        self.slot.state.lock().unwrap().senders += 1;
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl Drop for FrameSender {
+    fn drop(&mut self) {
+        let senders = {
+            let mut state = self.slot.state.lock().unwrap();
+            state.senders -= 1;
+            state.senders
+        };
+        if senders == 0 {
+            self.slot.arrived.notify_all();
+        }
+    }
+}
+
+/// The encode side of a [`frame_slot`]. Mirrors the `mpsc` receiver API
+/// the encode loop already speaks, minus iteration — there is never more
+/// than one frame to take.
+pub struct FrameReceiver {
+    slot: Arc<Slot>,
+}
+
+impl FrameReceiver {
+    /// Takes the pending frame, if any.
+    pub fn try_recv(&self) -> Result<CaptureFrame, TryRecvError> {
+        let mut state = self.slot.state.lock().unwrap();
+        match state.frame.take() {
+            Some(frame) => Ok(frame),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Waits up to `timeout` for a frame.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<CaptureFrame, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.slot.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frame.take() {
+                return Ok(frame);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(RecvTimeoutError::Timeout);
+            };
+            state = self.slot.arrived.wait_timeout(state, remaining).unwrap().0;
+        }
+    }
+
+    /// Frames the capture side replaced since the last call, for the
+    /// dropped-frame stats.
+    pub fn take_dropped(&self) -> u64 {
+        std::mem::take(&mut self.slot.state.lock().unwrap().dropped)
+    }
+}
+
+impl Drop for FrameReceiver {
+    fn drop(&mut self) {
+        self.slot.state.lock().unwrap().receiver_alive = false;
+    }
+}
+
+/// A frame source implementation. Backends block the calling thread for
+/// the whole session and publish frames into the slot they're given, so
+/// plugging in a new platform (DDA, a test pattern) is one impl and one
+/// line in `backends()` — no `cfg` branching outside this module.
+pub trait CaptureBackend {
+    /// Short name for logs and diagnostics, e.g. `"wgc"`.
+    fn name(&self) -> &'static str;
+
+    /// Whether this backend can serve the target at all. Used for
+    /// selection; a supported target can still fail at `run` time.
+    fn supports(&self, target: CaptureTarget) -> bool;
+
+    /// Runs the capture session on the calling thread until `stop` is set
+    /// or the source closes.
+    fn run(
+        &self,
+        target: CaptureTarget,
+        fps: u32,
+        show_cursor: bool,
+        frame_tx: FrameSender,
+        stop: Arc<AtomicBool>,
+    ) -> EngineResult<()>;
+}
+
+/// Backends eligible for `target`, in preference order: the native
+/// platform paths first, the scap fallback last.
+fn backends(target: CaptureTarget) -> Vec<Box<dyn CaptureBackend>> {
+    let all: Vec<Box<dyn CaptureBackend>> = vec![
+        #[cfg(windows)]
+        Box::new(camera::CameraBackend),
+        #[cfg(windows)]
+        Box::new(wgc::WgcBackend),
+        Box::new(scap_backend::ScapBackend),
+    ];
+    all.into_iter().filter(|b| b.supports(target)).collect()
+}
+
+/// Runs a capture session on the calling thread until `stop` is set or the
+/// capture item closes. Frames are published into `frame_tx`; when the
+/// encoder is behind, the newest frame replaces the pending one.
+///
+/// Backends are tried in preference order; when one fails without the stop
+/// flag being set (WGC on Windows Server, capture disabled by policy) the
+/// next takes over.
+pub fn run_capture(
+    target: CaptureTarget,
+    fps: u32,
+    show_cursor: bool,
+    frame_tx: FrameSender,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    let candidates = backends(target);
+    let last = candidates.len().saturating_sub(1);
+    for (i, backend) in candidates.iter().enumerate() {
+        match backend.run(target, fps, show_cursor, frame_tx.clone(), stop.clone()) {
+            Ok(()) => return Ok(()),
+            Err(e) if i < last && !stop.load(std::sync::atomic::Ordering::SeqCst) => {
+                tracing::warn!("{} capture failed ({e}); falling back", backend.name());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(EngineError::Capture(
+        "no capture backend supports this target on this platform".into(),
+    ))
+}
@@ -0,0 +1,129 @@
+//! scap-based capture backend: the cross-platform fallback for the WGC
+//! path, used on non-Windows platforms and when WGC setup fails. Screen
+//! and window targets only — cameras stay on the Media Foundation reader.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use scap::capturer::{Capturer, Options};
+use scap::frame::Frame;
+use scap::Target;
+
+use super::{CaptureBackend, CaptureFrame, CaptureTarget, FrameSender};
+use crate::error::{EngineError, EngineResult};
+
+/// The scap fallback as a selectable backend. Lowest preference — the
+/// native paths win whenever they work.
+pub struct ScapBackend;
+
+impl CaptureBackend for ScapBackend {
+    fn name(&self) -> &'static str {
+        "scap"
+    }
+
+    fn supports(&self, target: CaptureTarget) -> bool {
+        !matches!(target, CaptureTarget::Camera(_))
+    }
+
+    fn run(
+        &self,
+        target: CaptureTarget,
+        fps: u32,
+        show_cursor: bool,
+        frame_tx: FrameSender,
+        stop: Arc<AtomicBool>,
+    ) -> EngineResult<()> {
+        run_capture(target, fps, show_cursor, frame_tx, stop)
+    }
+}
+
+/// Resolves an engine capture target onto scap's target list. Displays
+/// map by enumeration index. Window ids are HWNDs on Windows, which scap
+/// doesn't expose, so those are matched by title against the Win32
+/// enumeration; elsewhere the id is scap's own window id — exactly what
+/// `enumerate` hands out there.
+fn resolve_target(target: CaptureTarget) -> EngineResult<Target> {
+    match target {
+        CaptureTarget::Display(index) => scap::get_all_targets()
+            .into_iter()
+            .filter(|t| matches!(t, Target::Display(_)))
+            .nth(index)
+            .ok_or_else(|| EngineError::Capture(format!("display {index} not found"))),
+        CaptureTarget::Window(id) => {
+            #[cfg(windows)]
+            let title = super::enumerate::list_windows()
+                .into_iter()
+                .find(|w| w.hwnd == id)
+                .map(|w| w.title)
+                .ok_or_else(|| EngineError::Capture(format!("window {id:#x} not found")))?;
+            scap::get_all_targets()
+                .into_iter()
+                .find(|t| match t {
+                    #[cfg(windows)]
+                    Target::Window(w) => w.title == title,
+                    #[cfg(not(windows))]
+                    Target::Window(w) => w.id as u64 == id,
+                    _ => false,
+                })
+                .ok_or_else(|| EngineError::Capture(format!("window {id:#x} not found")))
+        }
+        CaptureTarget::Camera(_) => Err(EngineError::Capture(
+            "camera capture has no scap fallback".into(),
+        )),
+    }
+}
+
+/// Runs a scap capture session on the calling thread until `stop` is set
+/// or the capturer dies. Same contract as the WGC loop: frames are
+/// published into `frame_tx`, replacing any frame the encoder hasn't
+/// taken yet.
+pub fn run_capture(
+    target: CaptureTarget,
+    fps: u32,
+    show_cursor: bool,
+    frame_tx: FrameSender,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    if !scap::has_permission() {
+        return Err(EngineError::Capture(
+            "screen capture permission not granted".into(),
+        ));
+    }
+    let target = resolve_target(target)?;
+    let options = Options {
+        fps,
+        target: Some(target),
+        show_cursor,
+        show_highlight: false,
+        ..Default::default()
+    };
+    let mut capturer = Capturer::build(options)
+        .map_err(|e| EngineError::Capture(format!("scap capturer: {e}")))?;
+    capturer.start_capture();
+    let result = loop {
+        if stop.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        match capturer.get_next_frame() {
+            Ok(Frame::BGRA(frame)) => {
+                // scap timestamps are nanoseconds; the pipeline expects the
+                // 100 ns units WGC's SystemRelativeTime uses.
+                let frame = CaptureFrame {
+                    width: frame.width as u32,
+                    height: frame.height as u32,
+                    qpc: (frame.display_time / 100) as i64,
+                    data: frame.data.into(),
+                };
+                if frame_tx.send(frame).is_err() {
+                    break Ok(());
+                }
+            }
+            Ok(_) => {
+                // Other pixel formats aren't requested.
+            }
+            Err(e) => break Err(EngineError::Capture(format!("scap frame: {e}"))),
+        }
+    };
+    capturer.stop_capture();
+    result
+}
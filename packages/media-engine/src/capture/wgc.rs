@@ -0,0 +1,247 @@
+//! Windows Graphics Capture session driving frames into the encode channel.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use windows::core::Interface;
+use windows::Foundation::TypedEventHandler;
+use windows::Graphics::Capture::{
+    Direct3D11CaptureFramePool, GraphicsCaptureItem, GraphicsCaptureSession,
+};
+use windows::Graphics::DirectX::DirectXPixelFormat;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Direct3D11::{
+    ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_CPU_ACCESS_READ,
+    D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+};
+use windows::Win32::Graphics::Gdi::HMONITOR;
+use windows::Win32::System::WinRT::Direct3D11::IDirect3DDxgiInterfaceAccess;
+use windows::Win32::System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop;
+
+use super::{CaptureBackend, CaptureFrame, CaptureTarget, FrameBuffer, FramePool, FrameSender};
+use crate::encode::d3d::{create_d3d_device, create_winrt_device};
+use crate::error::{EngineError, EngineResult};
+
+/// The WGC session as a selectable backend; the preferred screen path on
+/// Windows.
+pub struct WgcBackend;
+
+impl CaptureBackend for WgcBackend {
+    fn name(&self) -> &'static str {
+        "wgc"
+    }
+
+    fn supports(&self, target: CaptureTarget) -> bool {
+        !matches!(target, CaptureTarget::Camera(_))
+    }
+
+    fn run(
+        &self,
+        target: CaptureTarget,
+        fps: u32,
+        show_cursor: bool,
+        frame_tx: FrameSender,
+        stop: Arc<AtomicBool>,
+    ) -> EngineResult<()> {
+        run_capture(target, fps, show_cursor, frame_tx, stop)
+    }
+}
+
+fn create_capture_item(target: CaptureTarget) -> EngineResult<GraphicsCaptureItem> {
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    unsafe {
+        match target {
+            CaptureTarget::Display(index) => {
+                let monitors = crate::capture::enumerate::list_displays();
+                if index >= monitors.len() {
+                    return Err(EngineError::Capture(format!(
+                        "display index {index} out of range ({} displays)",
+                        monitors.len()
+                    )));
+                }
+                let hmonitor = monitor_handle_for_index(index)?;
+                Ok(interop.CreateForMonitor(hmonitor)?)
+            }
+            CaptureTarget::Window(hwnd) => {
+                Ok(interop.CreateForWindow(HWND(hwnd as isize as *mut _))?)
+            }
+        }
+    }
+}
+
+fn monitor_handle_for_index(index: usize) -> EngineResult<HMONITOR> {
+    use windows::Win32::Foundation::{BOOL, LPARAM, RECT, TRUE};
+    use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC};
+
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+    unsafe extern "system" fn cb(
+        hmonitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        lparam: LPARAM,
+    ) -> BOOL {
+        let monitors = unsafe { &mut *(lparam.0 as *mut Vec<HMONITOR>) };
+        monitors.push(hmonitor);
+        TRUE
+    }
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            None,
+            None,
+            Some(cb),
+            LPARAM(&mut monitors as *mut _ as isize),
+        );
+    }
+    monitors
+        .get(index)
+        .copied()
+        .ok_or_else(|| EngineError::Capture(format!("no monitor at index {index}")))
+}
+
+/// Copies a GPU frame texture through a staging texture into a tightly packed
+/// BGRA `Vec<u8>`.
+fn readback_frame(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+    pool: &Arc<FramePool>,
+) -> EngineResult<(FrameBuffer, u32, u32)> {
+    unsafe {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        texture.GetDesc(&mut desc);
+
+        let mut staging_desc = desc;
+        staging_desc.Usage = D3D11_USAGE_STAGING;
+        staging_desc.BindFlags = 0;
+        staging_desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+        staging_desc.MiscFlags = 0;
+
+        let _staging_live = crate::leak::TEXTURES.track();
+        let mut staging: Option<ID3D11Texture2D> = None;
+        device.CreateTexture2D(&staging_desc, None, Some(&mut staging))?;
+        let staging = staging.unwrap();
+        context.CopyResource(&staging, texture);
+
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        context.Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+
+        let row_bytes = desc.Width as usize * 4;
+        let mut data = pool.acquire(row_bytes * desc.Height as usize);
+        let src = mapped.pData as *const u8;
+        for row in 0..desc.Height as usize {
+            let src_row = src.add(row * mapped.RowPitch as usize);
+            let dst_row = &mut data[row * row_bytes..(row + 1) * row_bytes];
+            std::ptr::copy_nonoverlapping(src_row, dst_row.as_mut_ptr(), row_bytes);
+        }
+        context.Unmap(&staging, 0);
+
+        Ok((data, desc.Width, desc.Height))
+    }
+}
+
+/// Runs a WGC session until `stop` is set or the item closes.
+pub fn run_capture(
+    target: CaptureTarget,
+    fps: u32,
+    show_cursor: bool,
+    frame_tx: FrameSender,
+    stop: Arc<AtomicBool>,
+) -> EngineResult<()> {
+    let item = create_capture_item(target)?;
+    let (device, context) = create_d3d_device()?;
+    let winrt_device = create_winrt_device(&device)?;
+
+    let size = item.Size()?;
+    let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+        &winrt_device,
+        DirectXPixelFormat::B8G8R8A8UIntNormalized,
+        2,
+        size,
+    )?;
+    let session: GraphicsCaptureSession = frame_pool.CreateCaptureSession(&item)?;
+    session.SetIsCursorCaptureEnabled(show_cursor)?;
+    if let Ok(true) = GraphicsCaptureSession::IsBorderRequired() {
+        let _ = session.SetIsBorderRequired(false);
+    }
+
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let closed = closed.clone();
+        item.Closed(&TypedEventHandler::new(move |_, _| {
+            closed.store(true, Ordering::SeqCst);
+            Ok(())
+        }))?;
+    }
+
+    // Pace frames to the configured fps: WGC delivers on every desktop
+    // update, which can exceed what the encoder is configured for.
+    let frame_interval = Duration::from_secs_f64(1.0 / fps as f64);
+    let last_sent = Arc::new(std::sync::Mutex::new(Instant::now() - frame_interval));
+
+    // One pool per session; readback buffers circulate through the encode
+    // channel and return here when the encoder drops the frame.
+    let buffer_pool = FramePool::new();
+
+    {
+        let frame_tx = frame_tx.clone();
+        let device = device.clone();
+        let context = context.clone();
+        let last_sent = last_sent.clone();
+        let buffer_pool = buffer_pool.clone();
+        frame_pool.FrameArrived(&TypedEventHandler::new(
+            move |pool: &Option<Direct3D11CaptureFramePool>, _| {
+                let Some(pool) = pool.as_ref() else {
+                    return Ok(());
+                };
+                let frame = pool.TryGetNextFrame()?;
+                {
+                    let mut last = last_sent.lock().unwrap();
+                    if last.elapsed() < frame_interval {
+                        return Ok(());
+                    }
+                    *last = Instant::now();
+                }
+
+                let qpc = frame.SystemRelativeTime()?.Duration;
+                let surface = frame.Surface()?;
+                let access: IDirect3DDxgiInterfaceAccess = surface.cast()?;
+                let texture: ID3D11Texture2D = unsafe { access.GetInterface()? };
+
+                let _span = crate::trace::span("capture");
+                match readback_frame(&device, &context, &texture, &buffer_pool) {
+                    Ok((data, width, height)) => {
+                        let frame = CaptureFrame {
+                            data,
+                            width,
+                            height,
+                            qpc,
+                        };
+                        // The slot replaces the pending frame if the
+                        // encoder is behind; receiver gone means the
+                        // engine is shutting down anyway.
+                        let _ = frame_tx.send(frame);
+                    }
+                    Err(e) => {
+                        tracing::error!("frame readback failed: {e}");
+                    }
+                }
+                Ok(())
+            },
+        ))?;
+    }
+
+    session.StartCapture()?;
+
+    while !stop.load(Ordering::SeqCst) && !closed.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    session.Close().ok();
+    frame_pool.Close().ok();
+
+    if closed.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+        return Err(EngineError::Capture("capture item closed".into()));
+    }
+    Ok(())
+}
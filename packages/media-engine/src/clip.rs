@@ -0,0 +1,266 @@
+//! Animated clip export from the replay buffer: decode the buffered
+//! H.264 (openh264, same library as the CPU encode fallback), downscale,
+//! quantize to a fixed palette, and write an animated GIF — a bug repro
+//! you can paste into any chat.
+//!
+//! GIF only for now: animated WebP needs a VP8/VP8L encoder, which is a
+//! full codec, and GIF previews everywhere the clip would be pasted.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use openh264::decoder::Decoder;
+use openh264::formats::YUVSource;
+
+use crate::error::{EngineError, EngineResult};
+
+/// Output shaping for [`export_gif`].
+#[derive(Debug, Clone, Copy)]
+pub struct ClipOptions {
+    /// Output width cap; height follows the aspect ratio. Source smaller
+    /// than this is left alone.
+    pub max_width: u32,
+    /// Output frame rate; source frames in between are skipped.
+    pub fps: u32,
+}
+
+impl Default for ClipOptions {
+    /// 480 px / 10 fps: readable, and small enough to paste anywhere.
+    fn default() -> Self {
+        Self {
+            max_width: 480,
+            fps: 10,
+        }
+    }
+}
+
+/// One decoded, downscaled frame as palette indices plus its delay.
+struct GifFrame {
+    indices: Vec<u8>,
+    /// Hundredths of a second, GIF's native unit.
+    delay_cs: u16,
+}
+
+/// Decodes `frames` (Annex-B access units with QPC timestamps, starting
+/// at a keyframe) and writes the part at or after `start_qpc` to `path`
+/// as a looping GIF. Frames before `start_qpc` still decode — they prime
+/// the decoder — but aren't emitted. Returns the number of GIF frames.
+pub fn export_gif(
+    path: &Path,
+    frames: &[(&[u8], i64)],
+    start_qpc: i64,
+    options: &ClipOptions,
+) -> EngineResult<u64> {
+    if options.fps == 0 || options.max_width == 0 {
+        return Err(EngineError::Config(
+            "clip fps and maxWidth must be non-zero".into(),
+        ));
+    }
+    let mut decoder =
+        Decoder::new().map_err(|e| EngineError::Encode(format!("gif decoder init: {e}")))?;
+
+    // Minimum QPC spacing between emitted frames (100 ns ticks).
+    let interval = 10_000_000 / options.fps as i64;
+    let mut emitted: Vec<GifFrame> = Vec::new();
+    let mut emitted_qpc: Vec<i64> = Vec::new();
+    let mut size: Option<(u16, u16)> = None;
+
+    for &(data, qpc) in frames {
+        let decoded = match decoder.decode(data) {
+            Ok(Some(yuv)) => yuv,
+            // The decoder may need more input before producing output.
+            Ok(None) => continue,
+            Err(e) => return Err(EngineError::Encode(format!("gif decode: {e}"))),
+        };
+        if qpc < start_qpc {
+            continue;
+        }
+        if let Some(&last) = emitted_qpc.last() {
+            if qpc - last < interval {
+                continue;
+            }
+        }
+        let (src_w, src_h) = decoded.dimensions();
+        let (out_w, out_h) = output_size(src_w as u32, src_h as u32, options.max_width);
+        let expected = size.get_or_insert((out_w as u16, out_h as u16));
+        if *expected != (out_w as u16, out_h as u16) {
+            // Mid-buffer resolution change; stop at the size the clip
+            // started with rather than producing a corrupt file.
+            break;
+        }
+        emitted.push(GifFrame {
+            indices: quantize(&decoded, out_w, out_h),
+            delay_cs: 0,
+        });
+        emitted_qpc.push(qpc);
+    }
+    let Some((width, height)) = size else {
+        return Err(EngineError::Config(
+            "clip window contains no decodable frames".into(),
+        ));
+    };
+    // Real delays from the emitted timestamps; the last frame holds for
+    // one nominal interval.
+    for i in 0..emitted.len() {
+        let ticks = match emitted_qpc.get(i + 1) {
+            Some(next) => next - emitted_qpc[i],
+            None => interval,
+        };
+        emitted[i].delay_cs = (ticks / 100_000).clamp(2, u16::MAX as i64) as u16;
+    }
+
+    let mut gif = Vec::new();
+    gif.extend_from_slice(b"GIF89a");
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    gif.push(0xf7); // global palette, 256 entries, 8-bit color
+    gif.push(0); // background index
+    gif.push(0); // pixel aspect
+    gif.extend_from_slice(&palette());
+    // NETSCAPE extension: loop forever.
+    gif.extend_from_slice(&[0x21, 0xff, 0x0b]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[3, 1, 0, 0, 0]);
+    for frame in &emitted {
+        gif.extend_from_slice(&[0x21, 0xf9, 4, 0x04]); // GCE, disposal 1
+        gif.extend_from_slice(&frame.delay_cs.to_le_bytes());
+        gif.extend_from_slice(&[0, 0]); // no transparency, terminator
+        gif.push(0x2c); // image descriptor
+        gif.extend_from_slice(&[0, 0, 0, 0]); // origin
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.push(0); // no local palette
+        gif.push(8); // LZW minimum code size
+        for chunk in lzw_encode(8, &frame.indices).chunks(255) {
+            gif.push(chunk.len() as u8);
+            gif.extend_from_slice(chunk);
+        }
+        gif.push(0); // end of image data
+    }
+    gif.push(0x3b); // trailer
+
+    std::fs::write(path, gif)
+        .map_err(|e| EngineError::Config(format!("cannot write {}: {e}", path.display())))?;
+    Ok(emitted.len() as u64)
+}
+
+fn output_size(src_w: u32, src_h: u32, max_width: u32) -> (u32, u32) {
+    if src_w <= max_width {
+        return (src_w, src_h);
+    }
+    let h = (src_h * max_width / src_w).max(1);
+    (max_width, h)
+}
+
+/// Levels per channel of the fixed 6×7×6 palette (252 colors). Fixed
+/// rather than per-clip median cut: screen content is mostly flat fills
+/// and text, and a fixed palette keeps every frame consistent.
+const R_LEVELS: u32 = 6;
+const G_LEVELS: u32 = 7;
+const B_LEVELS: u32 = 6;
+
+fn palette() -> Vec<u8> {
+    let mut table = Vec::with_capacity(256 * 3);
+    for r in 0..R_LEVELS {
+        for g in 0..G_LEVELS {
+            for b in 0..B_LEVELS {
+                table.push((r * 255 / (R_LEVELS - 1)) as u8);
+                table.push((g * 255 / (G_LEVELS - 1)) as u8);
+                table.push((b * 255 / (B_LEVELS - 1)) as u8);
+            }
+        }
+    }
+    table.resize(256 * 3, 0);
+    table
+}
+
+/// Downscales (nearest neighbour) and converts BT.601 limited-range YUV
+/// to palette indices in one pass.
+fn quantize(yuv: &openh264::decoder::DecodedYUV, out_w: u32, out_h: u32) -> Vec<u8> {
+    let (src_w, src_h) = yuv.dimensions();
+    let (ys, us, vs) = yuv.strides();
+    let (y_plane, u_plane, v_plane) = (yuv.y(), yuv.u(), yuv.v());
+    let mut indices = Vec::with_capacity((out_w * out_h) as usize);
+    for oy in 0..out_h {
+        let sy = (oy as usize * src_h / out_h as usize).min(src_h - 1);
+        for ox in 0..out_w {
+            let sx = (ox as usize * src_w / out_w as usize).min(src_w - 1);
+            let y = y_plane[sy * ys + sx] as i32;
+            let u = u_plane[(sy / 2) * us + sx / 2] as i32;
+            let v = v_plane[(sy / 2) * vs + sx / 2] as i32;
+            let c = y - 16;
+            let d = u - 128;
+            let e = v - 128;
+            let r = ((298 * c + 409 * e + 128) >> 8).clamp(0, 255) as u32;
+            let g = ((298 * c - 100 * d - 208 * e + 128) >> 8).clamp(0, 255) as u32;
+            let b = ((298 * c + 516 * d + 128) >> 8).clamp(0, 255) as u32;
+            let index = (r * R_LEVELS / 256) * G_LEVELS * B_LEVELS
+                + (g * G_LEVELS / 256) * B_LEVELS
+                + (b * B_LEVELS / 256);
+            indices.push(index as u8);
+        }
+    }
+    indices
+}
+
+/// GIF-flavour LZW: variable code width starting at `min_code_size + 1`,
+/// dictionary reset via the clear code when it fills at 4096 entries.
+fn lzw_encode(min_code_size: u8, indices: &[u8]) -> Vec<u8> {
+    let clear: u16 = 1 << min_code_size;
+    let end: u16 = clear + 1;
+    let mut out = Vec::new();
+    let mut acc: u32 = 0;
+    let mut acc_bits: u32 = 0;
+    let mut push = |code: u16, width: u32, acc: &mut u32, acc_bits: &mut u32, out: &mut Vec<u8>| {
+        *acc |= (code as u32) << *acc_bits;
+        *acc_bits += width;
+        while *acc_bits >= 8 {
+            out.push((*acc & 0xff) as u8);
+            *acc >>= 8;
+            *acc_bits -= 8;
+        }
+    };
+
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = min_code_size as u32 + 1;
+    push(clear, width, &mut acc, &mut acc_bits, &mut out);
+
+    let mut prefix: Option<u16> = None;
+    for &k in indices {
+        let p = match prefix {
+            None => {
+                prefix = Some(k as u16);
+                continue;
+            }
+            Some(p) => p,
+        };
+        if let Some(&code) = dict.get(&(p, k)) {
+            prefix = Some(code);
+            continue;
+        }
+        push(p, width, &mut acc, &mut acc_bits, &mut out);
+        dict.insert((p, k), next_code);
+        next_code += 1;
+        // Decoders bump their read width when the table reaches the
+        // current width's capacity; mirror that exactly.
+        if next_code == (1 << width) && width < 12 {
+            width += 1;
+        }
+        if next_code == 4096 {
+            push(clear, width, &mut acc, &mut acc_bits, &mut out);
+            dict.clear();
+            next_code = end + 1;
+            width = min_code_size as u32 + 1;
+        }
+        prefix = Some(k as u16);
+    }
+    if let Some(p) = prefix {
+        push(p, width, &mut acc, &mut acc_bits, &mut out);
+    }
+    push(end, width, &mut acc, &mut acc_bits, &mut out);
+    if acc_bits > 0 {
+        out.push((acc & 0xff) as u8);
+    }
+    out
+}
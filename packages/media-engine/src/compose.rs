@@ -0,0 +1,54 @@
+//! Picture-in-picture compositing.
+//!
+//! The overlay is blitted into the main frame's BGRA buffer right before
+//! upload — capture frames are CPU-side at this point, so compositing here
+//! costs one scaled copy and keeps the encode pipeline single-stream.
+
+use crate::capture::CaptureFrame;
+use crate::config::OverlayAnchor;
+
+/// Margin between the overlay and the frame edge, in pixels.
+const OVERLAY_MARGIN: u32 = 16;
+
+/// Scales `overlay` to `fraction` of the main frame's width (preserving
+/// aspect) and blits it into the corner given by `anchor`.
+pub fn blit_overlay(
+    main: &mut CaptureFrame,
+    overlay: &CaptureFrame,
+    anchor: OverlayAnchor,
+    fraction: f32,
+) {
+    if overlay.width == 0 || overlay.height == 0 || main.width == 0 || main.height == 0 {
+        return;
+    }
+    let fraction = fraction.clamp(0.05, 0.5);
+    let out_w = ((main.width as f32 * fraction) as u32).max(1);
+    let out_h = ((out_w as u64 * overlay.height as u64) / overlay.width as u64).max(1) as u32;
+    if out_w + OVERLAY_MARGIN > main.width || out_h + OVERLAY_MARGIN > main.height {
+        return;
+    }
+
+    let (dst_x, dst_y) = match anchor {
+        OverlayAnchor::TopLeft => (OVERLAY_MARGIN, OVERLAY_MARGIN),
+        OverlayAnchor::TopRight => (main.width - out_w - OVERLAY_MARGIN, OVERLAY_MARGIN),
+        OverlayAnchor::BottomLeft => (OVERLAY_MARGIN, main.height - out_h - OVERLAY_MARGIN),
+        OverlayAnchor::BottomRight => (
+            main.width - out_w - OVERLAY_MARGIN,
+            main.height - out_h - OVERLAY_MARGIN,
+        ),
+    };
+
+    // Nearest-neighbour scale, row by row. BGRA, tightly packed.
+    let main_stride = main.width as usize * 4;
+    let overlay_stride = overlay.width as usize * 4;
+    for row in 0..out_h {
+        let src_row = (row as u64 * overlay.height as u64 / out_h as u64) as usize;
+        let src = &overlay.data[src_row * overlay_stride..(src_row + 1) * overlay_stride];
+        let dst_start = (dst_y + row) as usize * main_stride + dst_x as usize * 4;
+        let dst = &mut main.data[dst_start..dst_start + out_w as usize * 4];
+        for col in 0..out_w as usize {
+            let src_col = col * overlay.width as usize / out_w as usize;
+            dst[col * 4..col * 4 + 4].copy_from_slice(&src[src_col * 4..src_col * 4 + 4]);
+        }
+    }
+}
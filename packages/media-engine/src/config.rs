@@ -0,0 +1,640 @@
+use serde::{Deserialize, Serialize};
+
+use crate::capture::CaptureTarget;
+use crate::error::{EngineError, EngineResult};
+
+/// Top-level configuration for a screen share session.
+///
+/// Built from `JsScreenShareConfig` at the NAPI boundary; everything past
+/// `lib.rs` works with this struct. Also loadable from a saved JSON/TOML
+/// profile via [`ScreenShareConfig::load_profile`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScreenShareConfig {
+    /// LiveKit signal URL, e.g. `wss://livekit.example.com`. Empty means
+    /// record-only: capture + encode to `record_path` with no transport.
+    pub server_url: String,
+    /// Additional signal URLs tried in order when `server_url` is
+    /// unreachable (multi-region self-hosted deployments).
+    pub fallback_urls: Vec<String>,
+    /// LiveKit access token authorizing the publish.
+    pub token: String,
+    /// Extra STUN/TURN servers tried alongside the ones in the join
+    /// response (self-hosted deployments running their own coturn).
+    pub ice_servers: Vec<IceServerConfig>,
+    /// What to capture.
+    pub target: CaptureTarget,
+    pub encoder: EncoderConfig,
+    /// How the published track is announced to the room. Unset fields are
+    /// derived from the capture target.
+    pub track: TrackPublishConfig,
+    /// `None` = no audio.
+    pub audio_mode: Option<AudioMode>,
+    pub show_cursor: bool,
+    /// When set, the Annex-B elementary stream is also written to this path.
+    pub record_path: Option<String>,
+    /// When set with `record_path`, the recording splits into numbered
+    /// segments with automatic rollover — a crashed session only loses
+    /// the open segment.
+    pub record_rotate: Option<RecordRotateConfig>,
+    /// When set, keep a rolling buffer of the last N seconds of encoded
+    /// video for instant `save_replay` dumps.
+    pub replay_seconds: Option<u32>,
+    /// RTMP(S) ingest URL (`rtmp://host/app/streamKey`) the encoded video
+    /// is also pushed to, FLV-muxed — Twitch/YouTube live streaming next
+    /// to, or with an empty `server_url` instead of, the LiveKit publish.
+    pub rtmp_url: Option<String>,
+    /// Raw RTP/UDP output to a fixed address, for pipeline debugging with
+    /// ffplay/GStreamer and for custom SFUs. Like `rtmp_url`, works next
+    /// to or instead of the LiveKit transport.
+    pub rtp_out: Option<RtpOutConfig>,
+    /// Directory the encoded video is also segmented into as fMP4 + an
+    /// HLS playlist (`stream.m3u8`). Serve it over HTTP and any browser
+    /// can watch — "share to a link" without WebRTC.
+    pub hls_dir: Option<String>,
+    /// Optional webcam published alongside the screen share as a second
+    /// video track on the same transport connection.
+    pub camera: Option<CameraShareConfig>,
+    /// Optional second source composited into a corner of the main capture
+    /// before encoding, producing a single combined track.
+    pub overlay: Option<OverlayConfig>,
+    /// Extra encoders fed from the same captured frames, each writing to
+    /// its own file — e.g. a 2 Mbps local recording next to an 8 Mbps
+    /// stream.
+    pub tees: Vec<EncoderTee>,
+    /// Per-room E2EE shared secret. When set, encoded payloads are sealed
+    /// before RTP packetization (LiveKit-compatible frame encryption).
+    pub e2ee_key: Option<String>,
+    /// How long the encode thread waits for the first captured frame
+    /// before declaring the capture dead.
+    pub first_frame_timeout_ms: u64,
+    /// How long the signal WebSocket may take to dial and complete the
+    /// Join handshake.
+    pub signal_connect_timeout_ms: u64,
+    /// How long negotiation + ICE may take after the join before the
+    /// session fails.
+    pub ice_connect_timeout_ms: u64,
+    /// How often the stats callback fires. Dashboards polling at their own
+    /// cadence can also read snapshots synchronously via `get_screen_share_stats`.
+    pub stats_interval_ms: u64,
+    /// When set, serve Prometheus metrics at `http://127.0.0.1:port/metrics`
+    /// for the session. Loopback only — put a scraper on the same box.
+    pub metrics_port: Option<u16>,
+    /// When set, profile the pipeline and write a Chrome trace (open in
+    /// `chrome://tracing` or Perfetto) to this path when the session ends.
+    /// One traced session at a time.
+    pub trace_path: Option<String>,
+    /// TLS trust settings for `wss://` signal connections.
+    pub tls: TlsConfig,
+    /// How hard the engine tries to re-establish a dropped signal
+    /// connection before giving the session up.
+    pub reconnect: ReconnectPolicy,
+    /// Multimedia Class Scheduler registration for the realtime threads.
+    pub mmcss: MmcssConfig,
+    /// When set, the encoder starts below the configured bitrate and ramps
+    /// up while delivery stays healthy, instead of hitting slow links with
+    /// the full rate before congestion control has any signal.
+    pub ramp_up: Option<RampUpPolicy>,
+}
+
+impl Default for ScreenShareConfig {
+    /// Record-only baseline: primary display, default encoder, no
+    /// transport. Saved profiles override from here, so a profile only
+    /// needs the fields it cares about.
+    fn default() -> Self {
+        Self {
+            server_url: String::new(),
+            fallback_urls: Vec::new(),
+            token: String::new(),
+            ice_servers: Vec::new(),
+            target: CaptureTarget::Display(0),
+            encoder: EncoderConfig::default(),
+            track: TrackPublishConfig::default(),
+            audio_mode: None,
+            show_cursor: true,
+            record_path: None,
+            record_rotate: None,
+            replay_seconds: None,
+            rtmp_url: None,
+            rtp_out: None,
+            hls_dir: None,
+            camera: None,
+            overlay: None,
+            tees: Vec::new(),
+            e2ee_key: None,
+            first_frame_timeout_ms: DEFAULT_FIRST_FRAME_TIMEOUT_MS,
+            signal_connect_timeout_ms: DEFAULT_SIGNAL_CONNECT_TIMEOUT_MS,
+            ice_connect_timeout_ms: DEFAULT_ICE_CONNECT_TIMEOUT_MS,
+            stats_interval_ms: DEFAULT_STATS_INTERVAL_MS,
+            metrics_port: None,
+            trace_path: None,
+            tls: TlsConfig::default(),
+            reconnect: ReconnectPolicy::default(),
+            mmcss: MmcssConfig::default(),
+            ramp_up: None,
+        }
+    }
+}
+
+impl ScreenShareConfig {
+    /// Loads and validates a saved profile. The format is decided by the
+    /// file extension: `.json` or `.toml`.
+    pub fn load_profile(path: &std::path::Path) -> EngineResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| EngineError::Config(format!("read profile {}: {e}", path.display())))?;
+        let config: Self = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text)
+                .map_err(|e| EngineError::Config(format!("parse JSON profile: {e}")))?,
+            Some("toml") => toml::from_str(&text)
+                .map_err(|e| EngineError::Config(format!("parse TOML profile: {e}")))?,
+            _ => {
+                return Err(EngineError::Config(format!(
+                    "profile {} must end in .json or .toml",
+                    path.display()
+                )))
+            }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validates every encoder in the config against probed machine limits
+    /// before any capture or GPU resources are created. Messages are
+    /// user-facing: they name the field and the accepted range.
+    pub fn validate(&self) -> EngineResult<()> {
+        let caps = crate::capabilities::probe();
+        let check_limits = |what: &str, encoder: &EncoderConfig| -> EngineResult<()> {
+            encoder.validate(what)?;
+            if encoder.width > caps.max_encode_width || encoder.height > caps.max_encode_height {
+                return Err(EngineError::Config(format!(
+                    "{what}: {}x{} exceeds the supported maximum of {}x{}",
+                    encoder.width, encoder.height, caps.max_encode_width, caps.max_encode_height
+                )));
+            }
+            Ok(())
+        };
+        check_limits("encoder", &self.encoder)?;
+        if let Some(camera) = &self.camera {
+            check_limits("camera", &camera.encoder)?;
+        }
+        for (i, tee) in self.tees.iter().enumerate() {
+            check_limits(&format!("tee {i}"), &tee.encoder)?;
+        }
+        if let Some(rtp) = &self.rtp_out {
+            if !rtp.address.contains(':') {
+                return Err(EngineError::Config(
+                    "rtpOut address must be host:port".into(),
+                ));
+            }
+            if rtp.audio_address.as_deref().is_some_and(|a| !a.contains(':')) {
+                return Err(EngineError::Config(
+                    "rtpOut audioAddress must be host:port".into(),
+                ));
+            }
+        }
+        if let Some(url) = self.rtmp_url.as_deref() {
+            if !url.starts_with("rtmp://") && !url.starts_with("rtmps://") {
+                return Err(EngineError::Config(
+                    "rtmpUrl must start with rtmp:// or rtmps://".into(),
+                ));
+            }
+        }
+        if let Some(rotate) = &self.record_rotate {
+            if self.record_path.is_none() {
+                return Err(EngineError::Config(
+                    "recordRotate requires recordPath".into(),
+                ));
+            }
+            if rotate.max_seconds.is_none() && rotate.max_mb.is_none() {
+                return Err(EngineError::Config(
+                    "recordRotate needs maxSeconds or maxMb".into(),
+                ));
+            }
+            if rotate.max_seconds == Some(0) || rotate.max_mb == Some(0) {
+                return Err(EngineError::Config(
+                    "recordRotate limits must be non-zero when set".into(),
+                ));
+            }
+            if rotate.keep_segments == Some(0) {
+                return Err(EngineError::Config(
+                    "recordRotate keepSegments must be non-zero when set".into(),
+                ));
+            }
+        }
+        if self.hls_dir.as_deref().is_some_and(str::is_empty) {
+            return Err(EngineError::Config(
+                "hlsDir must be a directory path when set".into(),
+            ));
+        }
+        if self.metrics_port == Some(0) {
+            return Err(EngineError::Config(
+                "metricsPort must be non-zero when set".into(),
+            ));
+        }
+        if self.trace_path.as_deref().is_some_and(str::is_empty) {
+            return Err(EngineError::Config(
+                "tracePath must be a file path when set".into(),
+            ));
+        }
+        if self.replay_seconds == Some(0) {
+            return Err(EngineError::Config(
+                "replaySeconds must be non-zero when set".into(),
+            ));
+        }
+        if let Some(ramp) = &self.ramp_up {
+            if ramp.start_kbps == 0 {
+                return Err(EngineError::Config(
+                    "rampUpStartKbps must be non-zero".into(),
+                ));
+            }
+            if ramp.step_ms == 0 {
+                return Err(EngineError::Config("rampUpStepMs must be non-zero".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gradual bitrate ramp at session start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RampUpPolicy {
+    /// Bitrate the session starts at, in kbps.
+    pub start_kbps: u32,
+    /// How often the bitrate doubles toward the configured target while
+    /// the server reports healthy delivery. Degraded delivery holds the
+    /// ramp at its current step.
+    pub step_ms: u64,
+}
+
+impl Default for RampUpPolicy {
+    fn default() -> Self {
+        Self {
+            start_kbps: 1_000,
+            step_ms: 3_000,
+        }
+    }
+}
+
+/// Retry policy for signal reconnects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    /// Attempts before the session is considered lost.
+    pub max_retries: u32,
+    /// First backoff delay in milliseconds; doubles per attempt.
+    pub backoff_ms: u64,
+    /// Random extra delay (0..jitter_ms) added to each backoff so many
+    /// clients don't reconnect in lockstep after a server restart.
+    pub jitter_ms: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            backoff_ms: 500,
+            jitter_ms: 250,
+        }
+    }
+}
+
+/// Multimedia Class Scheduler registration for the realtime worker
+/// threads (Windows). MMCSS keeps the capture/encode/transport threads
+/// scheduled ahead of bulk work — a busy renderer or a background
+/// compile — without resorting to realtime process priority.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MmcssConfig {
+    /// Register the worker threads with MMCSS. On by default; turn off
+    /// if the embedder manages scheduling itself.
+    pub enabled: bool,
+    /// Relative priority within the MMCSS class.
+    pub priority: MmcssPriority,
+}
+
+impl Default for MmcssConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: MmcssPriority::Normal,
+        }
+    }
+}
+
+/// AVRT thread priority within an MMCSS class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MmcssPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    /// Reserved for audio-glitch territory; use sparingly.
+    Critical,
+}
+
+/// How the published video track is announced in the AddTrack request.
+/// Every field is optional; the defaults reproduce the old hardcoded
+/// behaviour (name and source derived from the capture target, server-chosen
+/// stream grouping).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrackPublishConfig {
+    /// Display name receiving clients see, e.g. `"Monitor 2"`.
+    pub name: Option<String>,
+    /// Source kind advertised to receivers.
+    pub source: Option<TrackSource>,
+    /// Stream grouping key; tracks sharing it are bundled together by
+    /// receivers. The screen-share audio track always joins this stream.
+    pub stream: Option<String>,
+    /// Free-form app label, kept so saved profiles can carry it. LiveKit's
+    /// AddTrack request has no per-track metadata slot, so it is not
+    /// transmitted to the server.
+    pub metadata: Option<String>,
+}
+
+/// Source kind for [`TrackPublishConfig`], matching LiveKit's `TrackSource`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSource {
+    Screenshare,
+    Camera,
+    Unknown,
+}
+
+impl TrackSource {
+    pub fn parse(raw: &str) -> EngineResult<Self> {
+        match raw {
+            "screenshare" => Ok(TrackSource::Screenshare),
+            "camera" => Ok(TrackSource::Camera),
+            "unknown" => Ok(TrackSource::Unknown),
+            other => Err(EngineError::Config(format!(
+                "unknown track source: {other}"
+            ))),
+        }
+    }
+}
+
+/// One STUN/TURN server, mirroring the `RTCIceServer` dictionary. Used for
+/// server-reflexive candidate gathering on the media socket; `turn:` URLs
+/// contribute through their STUN side (relay allocation is not implemented,
+/// so the credentials are carried but unused for now).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IceServerConfig {
+    /// `stun:host[:port]` / `turn:host[:port]?transport=udp` URLs.
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+/// TLS trust settings for the signal WebSocket. Defaults mean "system roots,
+/// full verification" — the same behaviour as before these knobs existed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TlsConfig {
+    /// Extra root certificates (PEM bundle) to trust, e.g. an internal CA
+    /// for a self-hosted deployment.
+    pub ca_pem: Option<String>,
+    /// Don't load the OS trust store; only `ca_pem` roots are trusted.
+    pub no_system_roots: bool,
+    /// Accept any server certificate. Explicit opt-in only; logs a warning.
+    pub accept_invalid_certs: bool,
+}
+
+/// Default for [`ScreenShareConfig::first_frame_timeout_ms`].
+pub const DEFAULT_FIRST_FRAME_TIMEOUT_MS: u64 = 5_000;
+/// Default for [`ScreenShareConfig::signal_connect_timeout_ms`].
+pub const DEFAULT_SIGNAL_CONNECT_TIMEOUT_MS: u64 = 10_000;
+/// Default for [`ScreenShareConfig::ice_connect_timeout_ms`].
+pub const DEFAULT_ICE_CONNECT_TIMEOUT_MS: u64 = 15_000;
+/// Default for [`ScreenShareConfig::stats_interval_ms`].
+pub const DEFAULT_STATS_INTERVAL_MS: u64 = 1_000;
+
+/// Video encoder settings, consumed by `MftEncoder`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EncoderConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub bitrate_kbps: u32,
+    /// Keyframe interval in seconds.
+    pub gop_seconds: u32,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 60,
+            bitrate_kbps: 8000,
+            gop_seconds: 2,
+        }
+    }
+}
+
+impl EncoderConfig {
+    /// Checks ranges up front so a bad value fails with a specific message
+    /// instead of a cryptic HRESULT from `SetOutputType` mid-start. `what`
+    /// names the encoder in messages ("encoder", "camera", "tee 1").
+    pub fn validate(&self, what: &str) -> EngineResult<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(EngineError::Config(format!(
+                "{what}: resolution must be non-zero, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if self.width % 2 != 0 || self.height % 2 != 0 {
+            return Err(EngineError::Config(format!(
+                "{what}: width and height must be even for NV12, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if self.fps == 0 || self.fps > 240 {
+            return Err(EngineError::Config(format!(
+                "{what}: fps must be 1..=240, got {}",
+                self.fps
+            )));
+        }
+        if self.bitrate_kbps == 0 {
+            return Err(EngineError::Config(format!(
+                "{what}: bitrate must be non-zero"
+            )));
+        }
+        if self.gop_seconds == 0 {
+            return Err(EngineError::Config(format!(
+                "{what}: gopSeconds must be non-zero"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Bits-per-pixel target used when no bitrate is configured. 0.064 bpp
+/// reproduces the long-standing 8 Mbps default at 1080p60 and scales it
+/// to other monitor sizes and frame rates.
+const DEFAULT_BITS_PER_PIXEL: f64 = 0.064;
+
+/// Computes a default bitrate from output resolution and frame rate, for
+/// callers that don't specify one. Clamped to 1–20 Mbps so odd target
+/// sizes can't produce useless or hostile values.
+pub fn default_bitrate_kbps(width: u32, height: u32, fps: u32) -> u32 {
+    let bits = width as f64 * height as f64 * fps as f64 * DEFAULT_BITS_PER_PIXEL;
+    ((bits / 1000.0).round() as u32).clamp(1_000, 20_000)
+}
+
+/// Named encoder presets for callers who don't want to pick numbers.
+/// A preset supplies the full coordinated set of encoder settings;
+/// explicitly passed fields still override individual values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    /// Motion first: 60 fps at 720p with a moderate bitrate. Games,
+    /// video playback.
+    Smooth,
+    /// The previous defaults: 1080p60 at 8 Mbps.
+    Balanced,
+    /// Detail first: 30 fps at a high bitrate with long GOPs. Text,
+    /// code, design work.
+    Sharp,
+}
+
+impl QualityPreset {
+    pub fn parse(raw: &str) -> EngineResult<Self> {
+        match raw {
+            "smooth" => Ok(QualityPreset::Smooth),
+            "balanced" => Ok(QualityPreset::Balanced),
+            "sharp" => Ok(QualityPreset::Sharp),
+            other => Err(EngineError::Config(format!("unknown preset: {other}"))),
+        }
+    }
+
+    /// The encoder settings this preset stands for.
+    pub fn encoder(self) -> EncoderConfig {
+        match self {
+            QualityPreset::Smooth => EncoderConfig {
+                width: 1280,
+                height: 720,
+                fps: 60,
+                bitrate_kbps: 4000,
+                gop_seconds: 2,
+            },
+            QualityPreset::Balanced => EncoderConfig::default(),
+            QualityPreset::Sharp => EncoderConfig {
+                width: 1920,
+                height: 1080,
+                fps: 30,
+                bitrate_kbps: 12000,
+                gop_seconds: 4,
+            },
+        }
+    }
+}
+
+/// Raw RTP/UDP output destination. The SDP file describes both streams so
+/// `ffplay -protocol_whitelist file,rtp,udp` can play them directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RtpOutConfig {
+    /// `host:port` the H.264 RTP packets are sent to.
+    pub address: String,
+    /// `host:port` for the Opus RTP packets. Only used in stream-only
+    /// mode (empty `server_url`); next to the LiveKit transport the audio
+    /// belongs to the publish.
+    pub audio_address: Option<String>,
+    /// When set, an SDP file describing the streams is written here at
+    /// session start.
+    pub sdp_path: Option<String>,
+}
+
+/// Segment rollover policy for long recordings. Segments cut at the first
+/// keyframe past a limit, so the encoder's GOP length bounds the
+/// overshoot; at least one of the limits must be set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordRotateConfig {
+    /// Close the segment after this many seconds of video.
+    pub max_seconds: Option<u32>,
+    /// Close the segment once its encoded payload reaches this many MiB.
+    pub max_mb: Option<u32>,
+    /// Keep at most this many segments on disk, deleting the oldest —
+    /// a dashcam-style rolling recording. Unset keeps everything.
+    pub keep_segments: Option<u32>,
+}
+
+/// A camera published next to the screen share, with its own encoder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraShareConfig {
+    /// Index into the camera enumeration order.
+    pub index: usize,
+    pub encoder: EncoderConfig,
+}
+
+/// A second source blended into the main capture before encoding —
+/// "facecam in the corner" without a second track or receiver-side layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayConfig {
+    /// What to blend in, same variants as the main target.
+    pub target: CaptureTarget,
+    /// Which corner the overlay sits in.
+    pub anchor: OverlayAnchor,
+    /// Overlay width as a fraction of the main frame width, clamped to
+    /// 0.05..=0.5. Height follows the overlay's aspect ratio.
+    pub fraction: f32,
+}
+
+/// Corner placement for a composited overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayAnchor {
+    pub fn parse(raw: &str) -> EngineResult<Self> {
+        match raw {
+            "top_left" => Ok(OverlayAnchor::TopLeft),
+            "top_right" => Ok(OverlayAnchor::TopRight),
+            "bottom_left" => Ok(OverlayAnchor::BottomLeft),
+            "bottom_right" => Ok(OverlayAnchor::BottomRight),
+            other => Err(EngineError::Config(format!(
+                "unknown overlay anchor: {other}"
+            ))),
+        }
+    }
+}
+
+/// A secondary encoder sharing the capture, with its own output file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderTee {
+    pub encoder: EncoderConfig,
+    /// Annex-B output path for this branch.
+    pub record_path: String,
+}
+
+/// Audio capture settings, consumed by the WASAPI loopback thread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioCaptureConfig {
+    pub mode: AudioMode,
+    pub sample_rate: u32,
+    pub channels: u32,
+}
+
+/// What the loopback capture should include.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioMode {
+    /// System mix minus our own process tree (display share).
+    System,
+    /// Only the given process tree (window share).
+    Process(u32),
+}
@@ -0,0 +1,216 @@
+//! Pre-flight diagnostics: exercises each pipeline stage in isolation —
+//! capture, encode, signaling, ICE — and reports which one failed, so
+//! support can read a structured report instead of reproducing blind.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use livekit_protocol as proto;
+
+use crate::capture::{self, CaptureFrame, CaptureTarget};
+use crate::config::{ReconnectPolicy, TlsConfig};
+use crate::error::{EngineError, EngineResult};
+use crate::transport::signal::SignalClient;
+use crate::transport::stun;
+
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(10);
+const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Outcome of one diagnostic stage.
+#[derive(Debug)]
+pub struct StageReport {
+    pub ok: bool,
+    /// A short summary when the stage passed, the error when it failed.
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+impl StageReport {
+    fn pass(detail: String, started: Instant) -> Self {
+        Self {
+            ok: true,
+            detail,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    fn fail(detail: String, started: Instant) -> Self {
+        Self {
+            ok: false,
+            detail,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    fn skipped(reason: &str) -> Self {
+        Self {
+            ok: false,
+            detail: format!("skipped: {reason}"),
+            elapsed_ms: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DiagnosticsReport {
+    pub capture: StageReport,
+    pub encode: StageReport,
+    pub signal: StageReport,
+    pub ice: StageReport,
+}
+
+/// Runs every stage even when an earlier one fails, except where a stage
+/// needs the previous stage's output (encode needs a frame, ICE needs the
+/// servers from the join response).
+pub fn run(server_url: &str, token: &str) -> DiagnosticsReport {
+    let (capture, frame) = capture_stage();
+    let encode = match frame.as_ref() {
+        Some(frame) => encode_stage(frame),
+        None => StageReport::skipped("no captured frame"),
+    };
+    let (signal, ice_servers) = signal_stage(server_url, token);
+    let ice = match ice_servers {
+        Some(servers) => ice_stage(&servers),
+        None => StageReport::skipped("signal stage produced no ICE servers"),
+    };
+    DiagnosticsReport {
+        capture,
+        encode,
+        signal,
+        ice,
+    }
+}
+
+/// Grabs a single frame from the primary display.
+fn capture_stage() -> (StageReport, Option<CaptureFrame>) {
+    let started = Instant::now();
+    let stop = Arc::new(AtomicBool::new(false));
+    let (frame_tx, frame_rx) = capture::frame_slot();
+    let thread_stop = stop.clone();
+    let handle = std::thread::spawn(move || {
+        let _ = capture::run_capture(CaptureTarget::Display(0), 30, false, frame_tx, thread_stop);
+    });
+    let result = frame_rx.recv_timeout(CAPTURE_TIMEOUT);
+    stop.store(true, Ordering::SeqCst);
+    let _ = handle.join();
+    match result {
+        Ok(frame) => (
+            StageReport::pass(format!("captured {}x{}", frame.width, frame.height), started),
+            Some(frame),
+        ),
+        Err(_) => (
+            StageReport::fail("no frame from the primary display within 5s".into(), started),
+            None,
+        ),
+    }
+}
+
+/// Runs the captured frame through a throwaway encode pipeline until the
+/// async encoder emits an access unit.
+#[cfg(windows)]
+fn encode_stage(frame: &CaptureFrame) -> StageReport {
+    use crate::config::EncoderConfig;
+    use crate::encode::pipeline::EncodePipeline;
+
+    let started = Instant::now();
+    let result = (|| -> EngineResult<Option<usize>> {
+        let (device, context) = crate::encode::d3d::create_d3d_device()?;
+        let mut pipeline = EncodePipeline::new(
+            device,
+            context,
+            frame.width,
+            frame.height,
+            &EncoderConfig::default(),
+        )?;
+        pipeline.force_keyframe();
+        for _ in 0..10 {
+            if let Some(encoded) = pipeline.encode(frame)? {
+                return Ok(Some(encoded.data.len()));
+            }
+        }
+        Ok(None)
+    })();
+    match result {
+        Ok(Some(bytes)) => StageReport::pass(format!("encoded {bytes} bytes"), started),
+        Ok(None) => StageReport::fail("encoder accepted input but produced no output".into(), started),
+        Err(e) => StageReport::fail(e.to_string(), started),
+    }
+}
+
+#[cfg(not(windows))]
+fn encode_stage(_frame: &CaptureFrame) -> StageReport {
+    StageReport::skipped("encoding is only implemented on Windows")
+}
+
+/// Joins the room over the signal WebSocket and immediately leaves.
+fn signal_stage(server_url: &str, token: &str) -> (StageReport, Option<Vec<proto::IceServer>>) {
+    let started = Instant::now();
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            return (
+                StageReport::fail(format!("tokio runtime: {e}"), started),
+                None,
+            )
+        }
+    };
+    let token = Arc::new(Mutex::new(token.to_string()));
+    let result = runtime.block_on(async {
+        let connect = SignalClient::connect(
+            server_url,
+            token,
+            &TlsConfig::default(),
+            ReconnectPolicy::default(),
+        );
+        let (mut signal, join) = tokio::time::timeout(SIGNAL_TIMEOUT, connect)
+            .await
+            .map_err(|_| EngineError::SignalTimeout(server_url.to_string()))??;
+        let _ = signal
+            .leave(proto::DisconnectReason::ClientInitiated)
+            .await;
+        Ok::<_, EngineError>(join)
+    });
+    match result {
+        Ok(join) => {
+            let room = join.room.as_ref().map(|r| r.name.clone()).unwrap_or_default();
+            (
+                StageReport::pass(format!("joined room \"{room}\""), started),
+                Some(join.ice_servers),
+            )
+        }
+        Err(e) => (StageReport::fail(e.to_string(), started), None),
+    }
+}
+
+/// Sends a STUN binding request to the first UDP ICE server from the join
+/// response and waits for the reflexive address.
+fn ice_stage(servers: &[proto::IceServer]) -> StageReport {
+    let started = Instant::now();
+    let Some((host, port)) = servers
+        .iter()
+        .flat_map(|s| s.urls.iter())
+        .find_map(|url| stun::parse_stun_url(url))
+    else {
+        return StageReport::fail("join response contained no STUN/TURN servers".into(), started);
+    };
+    let result = (|| -> EngineResult<Option<std::net::SocketAddr>> {
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| EngineError::Transport(format!("bind: {e}")))?;
+        socket
+            .set_read_timeout(Some(STUN_TIMEOUT))
+            .map_err(|e| EngineError::Transport(format!("socket timeout: {e}")))?;
+        stun::binding(&socket, &host, port, STUN_TIMEOUT)
+    })();
+    match result {
+        Ok(Some(addr)) => {
+            StageReport::pass(format!("reflexive address {addr} via {host}:{port}"), started)
+        }
+        Ok(None) => StageReport::pass(format!("binding response from {host}:{port}"), started),
+        Err(e) => StageReport::fail(format!("{host}:{port}: {e}"), started),
+    }
+}
@@ -0,0 +1,169 @@
+//! BGRA → NV12 conversion using the D3D11 video processor.
+
+use windows::core::Interface;
+use windows::Win32::Graphics::Direct3D11::{
+    ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, ID3D11VideoContext, ID3D11VideoDevice,
+    ID3D11VideoProcessor, ID3D11VideoProcessorEnumerator, ID3D11VideoProcessorInputView,
+    ID3D11VideoProcessorOutputView, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE,
+    D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_VIDEO_PROCESSOR_CONTENT_DESC,
+    D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC, D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC,
+    D3D11_VIDEO_PROCESSOR_STREAM, D3D11_VIDEO_USAGE_PLAYBACK_NORMAL, D3D11_VPIV_DIMENSION_TEXTURE2D,
+    D3D11_VPOV_DIMENSION_TEXTURE2D,
+};
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_NV12};
+
+use crate::error::{EngineError, EngineResult};
+
+/// Converts BGRA capture textures into NV12 textures the H.264 MFT accepts,
+/// scaling to the configured output size in the same pass.
+pub struct Converter {
+    video_device: ID3D11VideoDevice,
+    video_context: ID3D11VideoContext,
+    processor: ID3D11VideoProcessor,
+    enumerator: ID3D11VideoProcessorEnumerator,
+    output_texture: ID3D11Texture2D,
+    output_view: ID3D11VideoProcessorOutputView,
+    out_width: u32,
+    out_height: u32,
+}
+
+impl Converter {
+    pub fn new(
+        device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        in_width: u32,
+        in_height: u32,
+        out_width: u32,
+        out_height: u32,
+    ) -> EngineResult<Self> {
+        let _ = context;
+        let video_device: ID3D11VideoDevice = device
+            .cast()
+            .map_err(|_| EngineError::Encode("device does not support video APIs".into()))?;
+
+        let content_desc = D3D11_VIDEO_PROCESSOR_CONTENT_DESC {
+            InputWidth: in_width,
+            InputHeight: in_height,
+            OutputWidth: out_width,
+            OutputHeight: out_height,
+            Usage: D3D11_VIDEO_USAGE_PLAYBACK_NORMAL,
+            ..Default::default()
+        };
+        let enumerator = unsafe { video_device.CreateVideoProcessorEnumerator(&content_desc)? };
+        let processor = unsafe { video_device.CreateVideoProcessor(&enumerator, 0)? };
+
+        let mut tex_desc = D3D11_TEXTURE2D_DESC {
+            Width: out_width,
+            Height: out_height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_NV12,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: (D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0) as u32,
+            ..Default::default()
+        };
+        tex_desc.SampleDesc.Count = 1;
+        let mut output_texture: Option<ID3D11Texture2D> = None;
+        unsafe { device.CreateTexture2D(&tex_desc, None, Some(&mut output_texture))? };
+        let output_texture = output_texture.unwrap();
+
+        let out_view_desc = D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC {
+            ViewDimension: D3D11_VPOV_DIMENSION_TEXTURE2D,
+            ..Default::default()
+        };
+        let mut output_view: Option<ID3D11VideoProcessorOutputView> = None;
+        unsafe {
+            video_device.CreateVideoProcessorOutputView(
+                &output_texture,
+                &enumerator,
+                &out_view_desc,
+                Some(&mut output_view),
+            )?;
+        }
+
+        let video_context: ID3D11VideoContext = unsafe {
+            device
+                .GetImmediateContext()?
+                .cast()
+                .map_err(|_| EngineError::Encode("no video context".into()))?
+        };
+
+        Ok(Self {
+            video_device,
+            video_context,
+            processor,
+            enumerator,
+            output_texture,
+            output_view: output_view.unwrap(),
+            out_width,
+            out_height,
+        })
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        (self.out_width, self.out_height)
+    }
+
+    /// Runs the BGRA input texture through the video processor, returning the
+    /// shared NV12 output texture. The input view is created per call and
+    /// dropped before returning.
+    pub fn convert(&self, input: &ID3D11Texture2D) -> EngineResult<&ID3D11Texture2D> {
+        let in_view_desc = D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC {
+            ViewDimension: D3D11_VPIV_DIMENSION_TEXTURE2D,
+            ..Default::default()
+        };
+        let _input_view_live = crate::leak::INPUT_VIEWS.track();
+        let mut input_view: Option<ID3D11VideoProcessorInputView> = None;
+        unsafe {
+            self.video_device.CreateVideoProcessorInputView(
+                input,
+                &self.enumerator,
+                &in_view_desc,
+                Some(&mut input_view),
+            )?;
+        }
+
+        let stream = D3D11_VIDEO_PROCESSOR_STREAM {
+            Enable: true.into(),
+            pInputSurface: unsafe { std::mem::transmute_copy(&input_view) },
+            ..Default::default()
+        };
+        unsafe {
+            self.video_context
+                .VideoProcessorBlt(&self.processor, &self.output_view, 0, &[stream])?;
+        }
+        drop(input_view);
+        Ok(&self.output_texture)
+    }
+
+    /// Uploads a packed BGRA buffer into a GPU texture for conversion. Used
+    /// by the CPU path where capture hands us bytes rather than a texture.
+    pub fn upload_bgra(
+        device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> EngineResult<ID3D11Texture2D> {
+        let mut desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            ..Default::default()
+        };
+        desc.SampleDesc.Count = 1;
+        let init = windows::Win32::Graphics::Direct3D11::D3D11_SUBRESOURCE_DATA {
+            pSysMem: data.as_ptr() as *const _,
+            SysMemPitch: width * 4,
+            SysMemSlicePitch: 0,
+        };
+        let mut texture: Option<ID3D11Texture2D> = None;
+        unsafe { device.CreateTexture2D(&desc, Some(&init), Some(&mut texture))? };
+        let _ = context;
+        Ok(texture.unwrap())
+    }
+}
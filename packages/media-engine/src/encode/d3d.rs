@@ -0,0 +1,79 @@
+//! D3D11 device creation shared by capture and encode.
+
+use windows::core::Interface;
+use windows::Graphics::DirectX::Direct3D11::IDirect3DDevice;
+use windows::Win32::Graphics::Direct3D::{D3D_DRIVER_TYPE_HARDWARE, D3D_FEATURE_LEVEL_11_0};
+use windows::Win32::Graphics::Direct3D11::{
+    D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+    D3D11_CREATE_DEVICE_VIDEO_SUPPORT, D3D11_SDK_VERSION,
+};
+use windows::Win32::Graphics::Dxgi::IDXGIDevice;
+use windows::Win32::System::WinRT::Direct3D11::CreateDirect3D11DeviceFromDXGIDevice;
+
+use std::sync::Mutex;
+
+use crate::error::EngineResult;
+
+/// Device prewarmed by [`prewarm`], handed to the next session instead of
+/// paying device creation at share start.
+static PREWARMED: Mutex<Option<(ID3D11Device, ID3D11DeviceContext)>> = Mutex::new(None);
+
+/// Creates the D3D11 device used by both the WGC frame pool and the video
+/// processor / MFT. `VIDEO_SUPPORT` is required for the NV12 conversion,
+/// `BGRA_SUPPORT` for interop with the capture surfaces.
+pub fn create_d3d_device() -> EngineResult<(ID3D11Device, ID3D11DeviceContext)> {
+    let mut device: Option<ID3D11Device> = None;
+    let mut context: Option<ID3D11DeviceContext> = None;
+    unsafe {
+        D3D11CreateDevice(
+            None,
+            D3D_DRIVER_TYPE_HARDWARE,
+            None,
+            D3D11_CREATE_DEVICE_BGRA_SUPPORT | D3D11_CREATE_DEVICE_VIDEO_SUPPORT,
+            Some(&[D3D_FEATURE_LEVEL_11_0]),
+            D3D11_SDK_VERSION,
+            Some(&mut device),
+            None,
+            Some(&mut context),
+        )?;
+    }
+    Ok((device.unwrap(), context.unwrap()))
+}
+
+/// Returns the prewarmed device if one is waiting, otherwise creates one.
+/// Sessions call this so a preceding [`prewarm`] makes start near-instant.
+pub fn take_or_create_device() -> EngineResult<(ID3D11Device, ID3D11DeviceContext)> {
+    if let Some(pair) = PREWARMED.lock().unwrap().take() {
+        return Ok(pair);
+    }
+    create_d3d_device()
+}
+
+/// Creates the expensive encode-side resources ahead of time: the D3D11
+/// device is cached for the next session, and a throwaway video processor
+/// and MFT encoder are spun up so driver DLLs and shader caches are hot
+/// when the user clicks "share".
+pub fn prewarm() -> EngineResult<()> {
+    let (device, context) = create_d3d_device()?;
+    let config = crate::config::EncoderConfig::default();
+    let converter = super::convert::Converter::new(
+        &device,
+        &context,
+        config.width,
+        config.height,
+        config.width,
+        config.height,
+    )?;
+    let encoder = super::mft::MftEncoder::new(&device, &config)?;
+    drop((converter, encoder));
+    *PREWARMED.lock().unwrap() = Some((device, context));
+    Ok(())
+}
+
+/// Wraps the D3D11 device in the WinRT `IDirect3DDevice` the WGC frame pool
+/// expects.
+pub fn create_winrt_device(device: &ID3D11Device) -> EngineResult<IDirect3DDevice> {
+    let dxgi: IDXGIDevice = device.cast()?;
+    let inspectable = unsafe { CreateDirect3D11DeviceFromDXGIDevice(&dxgi)? };
+    Ok(inspectable.cast()?)
+}
@@ -0,0 +1,320 @@
+//! H.264 encoding through a hardware Media Foundation Transform.
+
+use windows::core::{Interface, GUID};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11Texture2D};
+use windows::Win32::Media::MediaFoundation::{
+    IMFAttributes, IMFDXGIDeviceManager, IMFMediaBuffer, IMFMediaEvent, IMFMediaEventGenerator,
+    IMFMediaType, IMFSample, IMFTransform, MFCreateDXGIDeviceManager, MFCreateDXGISurfaceBuffer,
+    MFCreateMediaType, MFCreateSample, MFStartup, MFTEnumEx, METransformHaveOutput,
+    METransformNeedInput, MFMediaType_Video, MFT_CATEGORY_VIDEO_ENCODER,
+    MFT_ENUM_FLAG_HARDWARE, MFT_ENUM_FLAG_SORTANDFILTER, MFT_FRIENDLY_NAME_Attribute,
+    MFT_MESSAGE_COMMAND_FLUSH,
+    MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, MFT_MESSAGE_NOTIFY_END_OF_STREAM,
+    MFT_MESSAGE_NOTIFY_END_STREAMING, MFT_MESSAGE_NOTIFY_START_OF_STREAM,
+    MFT_MESSAGE_SET_D3D_MANAGER, MFT_OUTPUT_DATA_BUFFER, MFT_REGISTER_TYPE_INFO,
+    MFVideoFormat_H264, MFVideoFormat_NV12, MFSTARTUP_FULL, MF_E_TRANSFORM_NEED_MORE_INPUT,
+    MF_EVENT_TYPE, MF_MT_AVG_BITRATE, MF_MT_FRAME_RATE, MF_MT_FRAME_SIZE,
+    MF_MT_INTERLACE_MODE, MF_MT_MAJOR_TYPE, MF_MT_SUBTYPE, MF_TRANSFORM_ASYNC_UNLOCK,
+    MFVideoInterlace_Progressive,
+};
+
+use super::EncodedFrame;
+use crate::config::EncoderConfig;
+use crate::error::{EngineError, EngineResult};
+
+const CODECAPI_AVENCVIDEOFORCEKEYFRAME: GUID =
+    GUID::from_u128(0x2c0378e0_de45_4d7a_8b8d_60ae2e7be9b1);
+const CODECAPI_AVENCCOMMONMEANBITRATE: GUID =
+    GUID::from_u128(0xf7222374_2144_4815_b550_a37f8e12ee52);
+
+fn pack_u64(hi: u32, lo: u32) -> u64 {
+    ((hi as u64) << 32) | lo as u64
+}
+
+/// A hardware H.264 encoder MFT bound to our D3D11 device, fed NV12 textures
+/// and producing Annex-B access units.
+pub struct MftEncoder {
+    transform: IMFTransform,
+    event_gen: IMFMediaEventGenerator,
+    device_manager: IMFDXGIDeviceManager,
+    config: EncoderConfig,
+    frame_index: u64,
+    force_keyframe: bool,
+    /// MFT friendly name, e.g. "NVIDIA H.264 Encoder MFT"; reported in
+    /// session stats so performance data can be segmented by encoder.
+    friendly_name: String,
+}
+
+impl MftEncoder {
+    /// Activates the first hardware H.264 encoder MFT and configures it for
+    /// NV12 input at the given resolution/fps/bitrate.
+    pub fn new(device: &ID3D11Device, config: &EncoderConfig) -> EngineResult<Self> {
+        unsafe { MFStartup(windows::Win32::Media::MediaFoundation::MF_VERSION, MFSTARTUP_FULL) }
+            .map_err(|e| EngineError::Encode(format!("MFStartup failed: {e}")))?;
+
+        let output_info = MFT_REGISTER_TYPE_INFO {
+            guidMajorType: MFMediaType_Video,
+            guidSubtype: MFVideoFormat_H264,
+        };
+        let mut activates = std::ptr::null_mut();
+        let mut count = 0u32;
+        unsafe {
+            MFTEnumEx(
+                MFT_CATEGORY_VIDEO_ENCODER,
+                MFT_ENUM_FLAG_HARDWARE | MFT_ENUM_FLAG_SORTANDFILTER,
+                None,
+                Some(&output_info),
+                &mut activates,
+                &mut count,
+            )?;
+        }
+        if count == 0 {
+            return Err(EngineError::Encode(
+                "no hardware H.264 encoder available".into(),
+            ));
+        }
+        let activates = unsafe { std::slice::from_raw_parts(activates, count as usize) };
+        let activate = activates[0]
+            .as_ref()
+            .ok_or_else(|| EngineError::Encode("null activate".into()))?;
+        let friendly_name = unsafe {
+            let mut value = windows::core::PWSTR::null();
+            let mut length = 0u32;
+            match activate.GetAllocatedString(&MFT_FRIENDLY_NAME_Attribute, &mut value, &mut length)
+            {
+                Ok(()) => {
+                    let name = value.to_string().unwrap_or_else(|_| "unknown".into());
+                    windows::Win32::System::Com::CoTaskMemFree(Some(value.as_ptr() as *const _));
+                    name
+                }
+                Err(_) => "unknown".into(),
+            }
+        };
+        let transform: IMFTransform = unsafe { activate.ActivateObject()? };
+
+        // Hardware MFTs are async: unlock, then bind our D3D device.
+        let attrs: IMFAttributes = unsafe { transform.GetAttributes()? };
+        unsafe { attrs.SetUINT32(&MF_TRANSFORM_ASYNC_UNLOCK, 1)? };
+
+        let mut reset_token = 0u32;
+        let mut device_manager: Option<IMFDXGIDeviceManager> = None;
+        unsafe {
+            MFCreateDXGIDeviceManager(&mut reset_token, &mut device_manager)?;
+        }
+        let device_manager = device_manager.unwrap();
+        unsafe {
+            device_manager.ResetDevice(device, reset_token)?;
+            transform.ProcessMessage(
+                MFT_MESSAGE_SET_D3D_MANAGER,
+                std::mem::transmute_copy(&device_manager),
+            )?;
+        }
+
+        // Output type must be set before input for encoders.
+        unsafe {
+            let out_type: IMFMediaType = MFCreateMediaType()?;
+            out_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+            out_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_H264)?;
+            out_type.SetUINT32(&MF_MT_AVG_BITRATE, config.bitrate_kbps * 1000)?;
+            out_type.SetUINT64(&MF_MT_FRAME_SIZE, pack_u64(config.width, config.height))?;
+            out_type.SetUINT64(&MF_MT_FRAME_RATE, pack_u64(config.fps, 1))?;
+            out_type.SetUINT32(&MF_MT_INTERLACE_MODE, MFVideoInterlace_Progressive.0 as u32)?;
+            transform.SetOutputType(0, &out_type, 0).map_err(|e| {
+                EngineError::Encode(format!("SetOutputType failed: {e}"))
+            })?;
+
+            let in_type: IMFMediaType = MFCreateMediaType()?;
+            in_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+            in_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_NV12)?;
+            in_type.SetUINT64(&MF_MT_FRAME_SIZE, pack_u64(config.width, config.height))?;
+            in_type.SetUINT64(&MF_MT_FRAME_RATE, pack_u64(config.fps, 1))?;
+            transform.SetInputType(0, &in_type, 0).map_err(|e| {
+                EngineError::Encode(format!("SetInputType failed: {e}"))
+            })?;
+
+            transform.ProcessMessage(MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, 0)?;
+            transform.ProcessMessage(MFT_MESSAGE_NOTIFY_START_OF_STREAM, 0)?;
+        }
+
+        let event_gen: IMFMediaEventGenerator = transform
+            .cast()
+            .map_err(|_| EngineError::Encode("encoder MFT is not async".into()))?;
+
+        Ok(Self {
+            transform,
+            event_gen,
+            device_manager,
+            config: config.clone(),
+            frame_index: 0,
+            force_keyframe: false,
+            friendly_name,
+        })
+    }
+
+    /// The active MFT's friendly name.
+    pub fn friendly_name(&self) -> &str {
+        &self.friendly_name
+    }
+
+    /// Request the next output frame be an IDR.
+    pub fn force_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    /// Changes the target bitrate on the live encoder via ICodecAPI.
+    /// Hardware encoders apply this within a GOP or two.
+    pub fn set_bitrate(&mut self, bitrate_kbps: u32) -> EngineResult<()> {
+        self.config.bitrate_kbps = bitrate_kbps;
+        let codec_api = self
+            .transform
+            .cast::<windows::Win32::Media::MediaFoundation::ICodecAPI>()
+            .map_err(|_| EngineError::Encode("encoder has no ICodecAPI".into()))?;
+        let v = windows::core::VARIANT::from(bitrate_kbps * 1000);
+        unsafe { codec_api.SetValue(&CODECAPI_AVENCCOMMONMEANBITRATE, &v) }
+            .map_err(|e| EngineError::Encode(format!("set bitrate: {e}")))
+    }
+
+    /// Changes the frame rate used for sample timestamps. The actual rate
+    /// reduction happens by thinning in the encode loop.
+    pub fn set_fps(&mut self, fps: u32) {
+        self.config.fps = fps.max(1);
+    }
+
+    /// Feeds one NV12 texture into the encoder and drains any ready output.
+    /// Hardware encoders pipeline a few frames, so `None` is normal early on.
+    pub fn encode(&mut self, texture: &ID3D11Texture2D, capture_qpc: i64) -> EngineResult<Option<EncodedFrame>> {
+        unsafe {
+            if self.force_keyframe {
+                self.force_keyframe = false;
+                if let Ok(codec_api) = self
+                    .transform
+                    .cast::<windows::Win32::Media::MediaFoundation::ICodecAPI>()
+                {
+                    let v = windows::core::VARIANT::from(1u32);
+                    let _ = codec_api.SetValue(&CODECAPI_AVENCVIDEOFORCEKEYFRAME, &v);
+                }
+            }
+
+            // Async MFT protocol: wait for NeedInput, submit, then drain
+            // HaveOutput events.
+            loop {
+                let event: IMFMediaEvent = self.event_gen.GetEvent(
+                    windows::Win32::Media::MediaFoundation::MF_EVENT_FLAG_NONE,
+                )?;
+                let event_type = MF_EVENT_TYPE(event.GetType()? as i32);
+                if event_type == METransformNeedInput {
+                    let _buffer_live = crate::leak::BUFFERS.track();
+                    let buffer: IMFMediaBuffer =
+                        MFCreateDXGISurfaceBuffer(&ID3D11Texture2D::IID, texture, 0, false)?;
+                    let _sample_live = crate::leak::SAMPLES.track();
+                    let sample: IMFSample = MFCreateSample()?;
+                    sample.AddBuffer(&buffer)?;
+                    let hns = self.frame_index as i64 * 10_000_000 / self.config.fps as i64;
+                    sample.SetSampleTime(hns)?;
+                    sample.SetSampleDuration(10_000_000 / self.config.fps as i64)?;
+                    self.transform.ProcessInput(0, &sample, 0)?;
+                    self.frame_index += 1;
+                } else if event_type == METransformHaveOutput {
+                    return self.drain_output(capture_qpc);
+                } else {
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn drain_output(&mut self, capture_qpc: i64) -> EngineResult<Option<EncodedFrame>> {
+        unsafe {
+            let mut output = MFT_OUTPUT_DATA_BUFFER::default();
+            output.dwStreamID = 0;
+            let mut status = 0u32;
+            let hr = self
+                .transform
+                .ProcessOutput(0, std::slice::from_mut(&mut output), &mut status);
+            match hr {
+                Ok(()) => {}
+                Err(e) if e.code() == MF_E_TRANSFORM_NEED_MORE_INPUT => return Ok(None),
+                Err(e) => return Err(EngineError::Encode(format!("ProcessOutput: {e}"))),
+            }
+            // The async MFT allocates the output sample and hands ownership
+            // to us; take it out of the ManuallyDrop so it is released when
+            // this scope ends instead of leaking every frame.
+            let Some(sample) = std::mem::ManuallyDrop::into_inner(output.pSample) else {
+                return Ok(None);
+            };
+            let _sample_live = crate::leak::SAMPLES.track();
+            let _buffer_live = crate::leak::BUFFERS.track();
+            let buffer: IMFMediaBuffer = sample.ConvertToContiguousBuffer()?;
+            let mut ptr = std::ptr::null_mut();
+            let mut len = 0u32;
+            buffer.Lock(&mut ptr, None, Some(&mut len))?;
+            let data = std::slice::from_raw_parts(ptr, len as usize).to_vec();
+            buffer.Unlock()?;
+
+            let is_keyframe = sample
+                .GetUINT32(&windows::Win32::Media::MediaFoundation::MFSampleExtension_CleanPoint)
+                .map(|v| v != 0)
+                .unwrap_or(false);
+
+            Ok(Some(EncodedFrame {
+                data,
+                is_keyframe,
+                width: self.config.width,
+                height: self.config.height,
+                frame_index: self.frame_index.saturating_sub(1),
+                capture_qpc,
+            }))
+        }
+    }
+
+    /// Drains the encoder at end of stream.
+    pub fn flush(&mut self) -> EngineResult<()> {
+        unsafe {
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0)?;
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_COMMAND_FLUSH, 0)?;
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_END_STREAMING, 0)?;
+        }
+        let _ = &self.device_manager;
+        Ok(())
+    }
+}
+
+impl super::VideoEncoder for MftEncoder {
+    fn friendly_name(&self) -> &str {
+        MftEncoder::friendly_name(self)
+    }
+
+    fn encode(
+        &mut self,
+        input: super::EncoderInput<'_>,
+        capture_qpc: i64,
+    ) -> EngineResult<Option<EncodedFrame>> {
+        match input {
+            super::EncoderInput::Nv12Texture(texture) => {
+                MftEncoder::encode(self, texture, capture_qpc)
+            }
+            super::EncoderInput::Bgra { .. } => Err(EngineError::Encode(
+                "the MFT encoder takes NV12 textures, not CPU frames".into(),
+            )),
+        }
+    }
+
+    fn force_keyframe(&mut self) {
+        MftEncoder::force_keyframe(self)
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> EngineResult<()> {
+        MftEncoder::set_bitrate(self, bitrate_kbps)
+    }
+
+    fn set_fps(&mut self, fps: u32) {
+        MftEncoder::set_fps(self, fps)
+    }
+
+    fn flush(&mut self) -> EngineResult<()> {
+        MftEncoder::flush(self)
+    }
+}
@@ -0,0 +1,77 @@
+//! H.264 encoding. The `VideoEncoder` trait is the backend interface,
+//! implemented by the Media Foundation hardware path and the openh264
+//! software fallback.
+
+#[cfg(windows)]
+pub mod convert;
+#[cfg(windows)]
+pub mod d3d;
+#[cfg(windows)]
+pub mod mft;
+#[cfg(windows)]
+pub mod pipeline;
+#[cfg(windows)]
+pub mod processor;
+pub mod software;
+
+use crate::error::EngineResult;
+
+/// One frame handed to a video encoder. The hardware path keeps frames on
+/// the GPU as NV12 textures; software backends take plain BGRA bytes.
+pub enum EncoderInput<'a> {
+    #[cfg(windows)]
+    Nv12Texture(&'a windows::Win32::Graphics::Direct3D11::ID3D11Texture2D),
+    Bgra {
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+    },
+}
+
+/// A video encoder backend. Construction stays backend-specific (each
+/// takes `EncoderConfig` plus whatever device handles it needs); once
+/// built, the pipeline and engine only speak this interface, so
+/// VideoToolbox/VAAPI/software encoders slot in without touching them.
+pub trait VideoEncoder {
+    /// Human-readable encoder name for stats and logs.
+    fn friendly_name(&self) -> &str;
+
+    /// Encodes one frame, returning an access unit if the encoder
+    /// produced one (hardware encoders buffer a few frames). Backends
+    /// reject input variants they can't take — the MFT path is
+    /// texture-only.
+    fn encode(
+        &mut self,
+        input: EncoderInput<'_>,
+        capture_qpc: i64,
+    ) -> EngineResult<Option<EncodedFrame>>;
+
+    /// Requests an IDR on the next frame.
+    fn force_keyframe(&mut self);
+
+    /// Live bitrate change; no rebuild.
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> EngineResult<()>;
+
+    /// Live fps change; only adjusts sample timing.
+    fn set_fps(&mut self, fps: u32);
+
+    /// Drains any buffered frames at end of session.
+    fn flush(&mut self) -> EngineResult<()>;
+}
+
+/// An encoded H.264 access unit in Annex-B format.
+#[derive(Clone)]
+pub struct EncodedFrame {
+    pub data: Vec<u8>,
+    pub is_keyframe: bool,
+    /// Encoded output dimensions, used by the transport to notify the SFU
+    /// when they change mid-session.
+    pub width: u32,
+    pub height: u32,
+    /// Monotonic index of the frame within the session, used by the
+    /// transport to derive RTP timestamps.
+    pub frame_index: u64,
+    /// Capture QPC timestamp carried through from WGC.
+    pub capture_qpc: i64,
+}
+
@@ -0,0 +1,277 @@
+//! The per-frame encode path: BGRA upload → NV12 convert → H.264 encode.
+//! The uploaded texture can be teed into additional encoder branches with
+//! their own configs and sinks.
+
+use std::path::Path;
+
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext};
+
+use super::convert::Converter;
+use super::mft::MftEncoder;
+use super::processor::FrameProcessor;
+use super::software::SoftwareEncoder;
+use super::{EncodedFrame, EncoderInput, VideoEncoder};
+use crate::capture::CaptureFrame;
+use crate::config::EncoderConfig;
+use crate::error::{EngineError, EngineResult};
+use crate::record::Recorder;
+
+/// A secondary encoder fed from the same uploaded BGRA texture as the
+/// primary, converting/scaling and writing to its own file.
+struct TeeBranch {
+    converter: Converter,
+    encoder: MftEncoder,
+    recorder: Option<Recorder>,
+}
+
+/// Where the primary encode happens: on the GPU through the NV12
+/// converter and a hardware MFT, or on the CPU through openh264 when no
+/// MFT activates. The CPU path takes BGRA frames directly and skips the
+/// GPU entirely — no upload, no tees, no processors.
+enum PrimaryEncoder {
+    Gpu {
+        converter: Converter,
+        encoder: MftEncoder,
+    },
+    Cpu(SoftwareEncoder),
+}
+
+/// Owns the GPU resources for one encode session. Created lazily on the
+/// first frame because the capture size isn't known until then.
+pub struct EncodePipeline {
+    device: ID3D11Device,
+    context: ID3D11DeviceContext,
+    primary: PrimaryEncoder,
+    config: EncoderConfig,
+    tees: Vec<TeeBranch>,
+    processors: Vec<Box<dyn FrameProcessor>>,
+    in_width: u32,
+    in_height: u32,
+}
+
+impl EncodePipeline {
+    pub fn new(
+        device: ID3D11Device,
+        context: ID3D11DeviceContext,
+        in_width: u32,
+        in_height: u32,
+        config: &EncoderConfig,
+    ) -> EngineResult<Self> {
+        let primary = match MftEncoder::new(&device, config) {
+            Ok(encoder) => PrimaryEncoder::Gpu {
+                converter: Converter::new(
+                    &device,
+                    &context,
+                    in_width,
+                    in_height,
+                    config.width,
+                    config.height,
+                )?,
+                encoder,
+            },
+            // No hardware MFT (Windows N without the Media Feature Pack,
+            // VMs): fall back to the clamped openh264 path rather than
+            // refusing to start at all.
+            Err(e) => {
+                tracing::warn!("hardware encoder unavailable ({e}); trying openh264");
+                PrimaryEncoder::Cpu(SoftwareEncoder::new(config)?)
+            }
+        };
+        Ok(Self {
+            device,
+            context,
+            primary,
+            config: config.clone(),
+            tees: Vec::new(),
+            processors: Vec::new(),
+            in_width,
+            in_height,
+        })
+    }
+
+    /// Whether the primary encode runs on a hardware MFT (with frames
+    /// staying on the GPU), as opposed to the openh264 CPU fallback.
+    pub fn is_hardware(&self) -> bool {
+        matches!(self.primary, PrimaryEncoder::Gpu { .. })
+    }
+
+    /// Adds a secondary encoder branch writing Annex-B to `path`. Tees
+    /// share the uploaded GPU texture, so they need the hardware path.
+    pub fn add_tee(&mut self, config: &EncoderConfig, path: &Path) -> EngineResult<()> {
+        if !self.is_hardware() {
+            return Err(EngineError::Encode(
+                "tee outputs are unavailable on the software encoder fallback".into(),
+            ));
+        }
+        let converter = Converter::new(
+            &self.device,
+            &self.context,
+            self.in_width,
+            self.in_height,
+            config.width,
+            config.height,
+        )?;
+        let encoder = MftEncoder::new(&self.device, config)?;
+        let recorder = Recorder::create(path)?;
+        self.tees.push(TeeBranch {
+            converter,
+            encoder,
+            recorder: Some(recorder),
+        });
+        Ok(())
+    }
+
+    /// Registers a frame processor. Processors run in registration order
+    /// on every frame, ahead of both the primary encoder and the tees.
+    /// They operate on GPU textures, so the software fallback skips them
+    /// (the share still works, just without overlays/compositing).
+    pub fn add_processor(&mut self, processor: Box<dyn FrameProcessor>) {
+        if !self.is_hardware() {
+            tracing::warn!("frame processors are unavailable on the software encoder; skipping");
+            return;
+        }
+        self.processors.push(processor);
+    }
+
+    pub fn input_size(&self) -> (u32, u32) {
+        (self.in_width, self.in_height)
+    }
+
+    /// Friendly name of the primary encoder.
+    pub fn encoder_name(&self) -> &str {
+        match &self.primary {
+            PrimaryEncoder::Gpu { encoder, .. } => encoder.friendly_name(),
+            PrimaryEncoder::Cpu(encoder) => VideoEncoder::friendly_name(encoder),
+        }
+    }
+
+    /// Encodes one captured BGRA frame, returning the encoded access unit if
+    /// the encoder produced one.
+    pub fn encode(&mut self, frame: &CaptureFrame) -> EngineResult<Option<EncodedFrame>> {
+        // The CPU fallback never touches the device: straight from the
+        // capture buffer into openh264.
+        if let PrimaryEncoder::Cpu(encoder) = &mut self.primary {
+            let _span = crate::trace::span("encode");
+            return encoder.encode(
+                EncoderInput::Bgra {
+                    data: &frame.data,
+                    width: frame.width,
+                    height: frame.height,
+                },
+                frame.qpc,
+            );
+        }
+        let _texture_live = crate::leak::TEXTURES.track();
+        let mut texture = {
+            let _span = crate::trace::span("upload");
+            Converter::upload_bgra(
+                &self.device,
+                &self.context,
+                &frame.data,
+                frame.width,
+                frame.height,
+            )?
+        };
+        // Effects run first so they apply to the primary and every tee.
+        for processor in &mut self.processors {
+            if let Some(replaced) = processor.process(
+                &self.device,
+                &self.context,
+                &texture,
+                frame.width,
+                frame.height,
+            )? {
+                texture = replaced;
+            }
+        }
+        // Tee branches share the uploaded texture; each converts at its
+        // own output size and writes to its own sink.
+        for tee in &mut self.tees {
+            let nv12 = tee.converter.convert(&texture)?;
+            if let Some(encoded) = tee.encoder.encode(nv12, frame.qpc)? {
+                if let Some(recorder) = tee.recorder.as_mut() {
+                    recorder.write_frame(&encoded)?;
+                }
+            }
+        }
+        let PrimaryEncoder::Gpu { converter, encoder } = &mut self.primary else {
+            unreachable!("CPU path returned above");
+        };
+        let nv12 = {
+            let _span = crate::trace::span("convert");
+            converter.convert(&texture)?
+        };
+        let _span = crate::trace::span("encode");
+        encoder.encode(nv12, frame.qpc)
+    }
+
+    pub fn force_keyframe(&mut self) {
+        match &mut self.primary {
+            PrimaryEncoder::Gpu { encoder, .. } => encoder.force_keyframe(),
+            PrimaryEncoder::Cpu(encoder) => VideoEncoder::force_keyframe(encoder),
+        }
+    }
+
+    /// Live bitrate change; no pipeline rebuild needed.
+    pub fn set_bitrate(&mut self, bitrate_kbps: u32) -> EngineResult<()> {
+        self.config.bitrate_kbps = bitrate_kbps;
+        match &mut self.primary {
+            PrimaryEncoder::Gpu { encoder, .. } => encoder.set_bitrate(bitrate_kbps),
+            PrimaryEncoder::Cpu(encoder) => VideoEncoder::set_bitrate(encoder, bitrate_kbps),
+        }
+    }
+
+    /// Live fps change; only adjusts sample timing, the encode loop thins
+    /// frames to the new rate.
+    pub fn set_fps(&mut self, fps: u32) {
+        self.config.fps = fps.max(1);
+        match &mut self.primary {
+            PrimaryEncoder::Gpu { encoder, .. } => encoder.set_fps(fps),
+            PrimaryEncoder::Cpu(encoder) => VideoEncoder::set_fps(encoder, fps),
+        }
+    }
+
+    /// Changes the output resolution by rebuilding the converter and the
+    /// encoder. The new encoder starts with an IDR, so viewers recover
+    /// without a manual keyframe request.
+    pub fn set_resolution(&mut self, width: u32, height: u32) -> EngineResult<()> {
+        self.config.width = width;
+        self.config.height = height;
+        match &mut self.primary {
+            PrimaryEncoder::Gpu { converter, encoder } => {
+                encoder.flush()?;
+                *converter = Converter::new(
+                    &self.device,
+                    &self.context,
+                    self.in_width,
+                    self.in_height,
+                    width,
+                    height,
+                )?;
+                *encoder = MftEncoder::new(&self.device, &self.config)?;
+            }
+            PrimaryEncoder::Cpu(encoder) => {
+                // The software clamp re-applies, so a 4K request still
+                // lands at the CPU ceiling.
+                *encoder = SoftwareEncoder::new(&self.config)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> EngineResult<()> {
+        for tee in &mut self.tees {
+            tee.encoder.flush()?;
+            if let Some(recorder) = tee.recorder.take() {
+                match recorder.finish() {
+                    Ok(frames) => tracing::info!("tee recording finished: {frames} frames"),
+                    Err(e) => tracing::error!("tee recorder finish: {e}"),
+                }
+            }
+        }
+        match &mut self.primary {
+            PrimaryEncoder::Gpu { encoder, .. } => encoder.flush(),
+            PrimaryEncoder::Cpu(encoder) => VideoEncoder::flush(encoder),
+        }
+    }
+}
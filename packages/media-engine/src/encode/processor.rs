@@ -0,0 +1,31 @@
+//! Frame-processing hook for custom effects.
+//!
+//! Crate consumers can register processors on [`EncodePipeline`] to blur
+//! regions, apply color filters, watermark, etc. without forking the
+//! pipeline. Processors see the uploaded BGRA texture before NV12
+//! conversion, so effects apply to the primary encoder and every tee.
+//!
+//! [`EncodePipeline`]: super::pipeline::EncodePipeline
+
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D};
+
+use crate::error::EngineResult;
+
+/// A per-frame effect running between upload and conversion.
+///
+/// Implementations run on the encode thread with the pipeline's device and
+/// immediate context; they may sample or overwrite `texture` in place, or
+/// return a replacement texture (same size, `B8G8R8A8_UNORM`) to substitute
+/// for the rest of the frame. Returning `Ok(None)` passes the input
+/// through unchanged. An error fails the frame — and with it the session —
+/// so recoverable problems should be handled inside the processor.
+pub trait FrameProcessor: Send {
+    fn process(
+        &mut self,
+        device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+        width: u32,
+        height: u32,
+    ) -> EngineResult<Option<ID3D11Texture2D>>;
+}
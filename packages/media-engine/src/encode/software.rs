@@ -0,0 +1,194 @@
+//! openh264 CPU encoder: the last-resort backend for machines where no
+//! hardware MFT activates (Windows N without the Media Feature Pack,
+//! VMs). Takes BGRA frames straight from capture — no GPU resources at
+//! all — and clamps resolution and fps so the CPU cost stays sane.
+
+use openh264::encoder::{Encoder, EncoderConfig as OpenH264Config, FrameType};
+use openh264::formats::YUVSource;
+use openh264::OpenH264API;
+
+use super::{EncodedFrame, EncoderInput, VideoEncoder};
+use crate::config::EncoderConfig;
+use crate::error::{EngineError, EngineResult};
+
+/// Output ceilings for the software path. 720p30 is roughly one busy core
+/// on openh264; anything above makes the share unusable on the machines
+/// that need this fallback.
+const MAX_WIDTH: u32 = 1280;
+const MAX_HEIGHT: u32 = 720;
+const MAX_FPS: u32 = 30;
+
+/// Clamps an encoder config to the software ceilings, preserving aspect
+/// ratio and rounding to even dimensions (4:2:0 needs them).
+pub fn clamp_config(config: &EncoderConfig) -> EncoderConfig {
+    let scale = (MAX_WIDTH as f64 / config.width as f64)
+        .min(MAX_HEIGHT as f64 / config.height as f64)
+        .min(1.0);
+    let even = |v: f64| ((v as u32).max(2)) & !1;
+    EncoderConfig {
+        width: even(config.width as f64 * scale),
+        height: even(config.height as f64 * scale),
+        fps: config.fps.min(MAX_FPS),
+        ..*config
+    }
+}
+
+/// I420 planes in the layout openh264 consumes.
+struct I420Frame {
+    width: usize,
+    height: usize,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl YUVSource for I420Frame {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn strides(&self) -> (usize, usize, usize) {
+        (self.width, self.width / 2, self.width / 2)
+    }
+
+    fn y(&self) -> &[u8] {
+        &self.y
+    }
+
+    fn u(&self) -> &[u8] {
+        &self.u
+    }
+
+    fn v(&self) -> &[u8] {
+        &self.v
+    }
+}
+
+/// Nearest-neighbour scale plus BT.601 BGRA → I420, chroma from the
+/// top-left pixel of each 2x2 block. All CPU, like everything else on
+/// this path.
+fn convert(data: &[u8], sw: u32, sh: u32, dw: u32, dh: u32) -> I420Frame {
+    let (dw_us, dh_us) = (dw as usize, dh as usize);
+    let mut y_plane = vec![0u8; dw_us * dh_us];
+    let mut u_plane = vec![0u8; dw_us * dh_us / 4];
+    let mut v_plane = vec![0u8; dw_us * dh_us / 4];
+    for row in 0..dh_us {
+        let from_y = row as u64 * sh as u64 / dh as u64;
+        for col in 0..dw_us {
+            let from_x = col as u64 * sw as u64 / dw as u64;
+            let p = ((from_y * sw as u64 + from_x) * 4) as usize;
+            let (b, g, r) = (data[p] as i32, data[p + 1] as i32, data[p + 2] as i32);
+            y_plane[row * dw_us + col] =
+                (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            if row % 2 == 0 && col % 2 == 0 {
+                let c = (row / 2) * (dw_us / 2) + col / 2;
+                u_plane[c] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                v_plane[c] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+            }
+        }
+    }
+    I420Frame {
+        width: dw_us,
+        height: dh_us,
+        y: y_plane,
+        u: u_plane,
+        v: v_plane,
+    }
+}
+
+/// A CPU H.264 encoder producing the same Annex-B access units as the
+/// MFT path, so recording and transport don't know the difference.
+pub struct SoftwareEncoder {
+    encoder: Encoder,
+    config: EncoderConfig,
+    frame_index: u64,
+}
+
+impl SoftwareEncoder {
+    /// Builds an openh264 encoder at the clamped output size. `config` is
+    /// the session's requested encoder config; the clamp is applied here
+    /// so every caller gets the same ceilings.
+    pub fn new(config: &EncoderConfig) -> EngineResult<Self> {
+        let config = clamp_config(config);
+        let oh_config = OpenH264Config::new(config.width, config.height)
+            .max_frame_rate(config.fps as f32)
+            .set_bitrate_bps(config.bitrate_kbps * 1000);
+        let encoder = Encoder::with_api_config(OpenH264API::from_source(), oh_config)
+            .map_err(|e| EngineError::Encode(format!("openh264 init: {e}")))?;
+        tracing::warn!(
+            "no hardware encoder; using openh264 at {}x{}@{}",
+            config.width,
+            config.height,
+            config.fps
+        );
+        Ok(Self {
+            encoder,
+            config,
+            frame_index: 0,
+        })
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+}
+
+impl VideoEncoder for SoftwareEncoder {
+    fn friendly_name(&self) -> &str {
+        "openh264 (software)"
+    }
+
+    fn encode(
+        &mut self,
+        input: EncoderInput<'_>,
+        capture_qpc: i64,
+    ) -> EngineResult<Option<EncodedFrame>> {
+        let EncoderInput::Bgra {
+            data,
+            width,
+            height,
+        } = input
+        else {
+            return Err(EngineError::Encode(
+                "the software encoder takes CPU frames, not textures".into(),
+            ));
+        };
+        let frame = convert(data, width, height, self.config.width, self.config.height);
+        let bitstream = self
+            .encoder
+            .encode(&frame)
+            .map_err(|e| EngineError::Encode(format!("openh264 encode: {e}")))?;
+        let encoded = EncodedFrame {
+            data: bitstream.to_vec(),
+            is_keyframe: matches!(bitstream.frame_type(), FrameType::IDR),
+            width: self.config.width,
+            height: self.config.height,
+            frame_index: self.frame_index,
+            capture_qpc,
+        };
+        self.frame_index += 1;
+        Ok(Some(encoded))
+    }
+
+    fn force_keyframe(&mut self) {
+        self.encoder.force_intra_frame();
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> EngineResult<()> {
+        // The binding has no live rate-control setter; the new target
+        // applies on the next resolution change's rebuild.
+        self.config.bitrate_kbps = bitrate_kbps;
+        Ok(())
+    }
+
+    fn set_fps(&mut self, fps: u32) {
+        // Rate reduction happens by thinning in the encode loop; only the
+        // bookkeeping changes here, clamped like everything else.
+        self.config.fps = fps.clamp(1, MAX_FPS);
+    }
+
+    fn flush(&mut self) -> EngineResult<()> {
+        // openh264 emits each access unit synchronously; nothing buffered.
+        Ok(())
+    }
+}
@@ -0,0 +1,1541 @@
+//! Session orchestration: owns the worker threads and wires the channels
+//! between capture, encode, audio, and transport.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::audio;
+use crate::capture::{self, CaptureFrame, CaptureTarget, FrameReceiver};
+use crate::config::{AudioCaptureConfig, AudioMode, EncoderConfig, ScreenShareConfig};
+use crate::encode::pipeline::EncodePipeline;
+use crate::error::{EngineError, EngineResult};
+use crate::record::{Recorder, ReplayBuffer};
+use crate::stats::{self, EngineStats, SharedStats};
+use crate::transport;
+
+/// Live knobs the transport turns based on SFU feedback and the encode
+/// thread obeys. Keeping this out of the channels avoids re-plumbing every
+/// time a new control is added.
+pub struct PublishControl {
+    /// False while the SFU reports no subscribed qualities: encoding stops
+    /// (capture stays warm) so we don't stream bits into the void.
+    pub encoding_enabled: std::sync::atomic::AtomicBool,
+    /// Highest subscribed quality (proto::VideoQuality values 0..=2). The
+    /// encoder thins frames when only lower qualities are wanted.
+    pub max_quality: std::sync::atomic::AtomicU32,
+    /// User-requested pause: encoding and audio sending stop but capture,
+    /// encoder, and the LiveKit connection stay warm for an instant resume.
+    pub paused: std::sync::atomic::AtomicBool,
+    /// True while the server reports our delivery as poor or lost. The
+    /// startup bitrate ramp holds at its current step while this is set.
+    pub delivery_degraded: std::sync::atomic::AtomicBool,
+    /// User-requested video mute: RTP for the video track stops and the
+    /// server is told via MuteTrack so receivers render a muted tile.
+    pub video_muted: std::sync::atomic::AtomicBool,
+    /// Same for the screen-share audio track.
+    pub audio_muted: std::sync::atomic::AtomicBool,
+}
+
+impl PublishControl {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            encoding_enabled: std::sync::atomic::AtomicBool::new(true),
+            max_quality: std::sync::atomic::AtomicU32::new(
+                livekit_protocol::VideoQuality::High as u32,
+            ),
+            paused: std::sync::atomic::AtomicBool::new(false),
+            delivery_degraded: std::sync::atomic::AtomicBool::new(false),
+            video_muted: std::sync::atomic::AtomicBool::new(false),
+            audio_muted: std::sync::atomic::AtomicBool::new(false),
+        })
+    }
+}
+
+/// Why a session ended, delivered through `EngineCallbacks::on_stopped`
+/// so the client can decide whether to auto-retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// `stop()` was called (or the engine was dropped).
+    UserRequested,
+    /// The capture source went away — window closed or display unplugged.
+    CaptureClosed,
+    /// The encoder failed and could not continue.
+    EncoderFailed,
+    /// The server connection was lost and could not be re-established.
+    Disconnected,
+    /// An internal fault — a worker thread panicked.
+    Fault,
+}
+
+impl StopReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StopReason::UserRequested => "user",
+            StopReason::CaptureClosed => "capture_closed",
+            StopReason::EncoderFailed => "encoder_failed",
+            StopReason::Disconnected => "disconnected",
+            StopReason::Fault => "fault",
+        }
+    }
+}
+
+/// First-wins cell recording why the session is stopping. Whichever thread
+/// hits a fatal condition first sets it; the encode thread reads it when
+/// firing `on_stopped`.
+pub type StopReasonCell = Arc<std::sync::Mutex<Option<StopReason>>>;
+
+/// Records `reason` unless another thread already recorded one.
+pub fn record_stop_reason(cell: &StopReasonCell, reason: StopReason) {
+    let mut guard = cell.lock().unwrap();
+    if guard.is_none() {
+        *guard = Some(reason);
+    }
+}
+
+/// Extracts a printable message from a `catch_unwind` payload.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+/// Startup latency breakdown in milliseconds from `start()`. Fields stay
+/// `None` for milestones a session never reaches (record-only sessions
+/// have no signal join, ICE, or packets).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StartupTimings {
+    pub first_frame_ms: Option<u64>,
+    pub encoder_ready_ms: Option<u64>,
+    pub signal_join_ms: Option<u64>,
+    pub ice_connected_ms: Option<u64>,
+    pub first_packet_ms: Option<u64>,
+}
+
+/// Collects startup milestones across the worker threads and fires
+/// `on_started` exactly once when the session is fully live: first RTP
+/// packet sent, or encoder ready in record-only mode.
+pub struct StartupTracker {
+    origin: Instant,
+    record_only: bool,
+    timings: std::sync::Mutex<StartupTimings>,
+    fired: AtomicBool,
+}
+
+impl StartupTracker {
+    pub fn new(record_only: bool) -> Arc<Self> {
+        Arc::new(Self {
+            origin: Instant::now(),
+            record_only,
+            timings: std::sync::Mutex::new(StartupTimings::default()),
+            fired: AtomicBool::new(false),
+        })
+    }
+
+    fn mark(
+        &self,
+        pick: impl FnOnce(&mut StartupTimings) -> &mut Option<u64>,
+        callbacks: &EngineCallbacks,
+    ) {
+        let elapsed = self.origin.elapsed().as_millis() as u64;
+        let snapshot = {
+            let mut timings = self.timings.lock().unwrap();
+            let slot = pick(&mut timings);
+            if slot.is_none() {
+                *slot = Some(elapsed);
+            }
+            *timings
+        };
+        let complete = if self.record_only {
+            snapshot.encoder_ready_ms.is_some()
+        } else {
+            snapshot.first_packet_ms.is_some()
+        };
+        if complete && !self.fired.swap(true, Ordering::SeqCst) {
+            (callbacks.on_started)(snapshot);
+        }
+    }
+
+    pub fn mark_first_frame(&self, callbacks: &EngineCallbacks) {
+        self.mark(|t| &mut t.first_frame_ms, callbacks);
+    }
+
+    pub fn mark_encoder_ready(&self, callbacks: &EngineCallbacks) {
+        self.mark(|t| &mut t.encoder_ready_ms, callbacks);
+    }
+
+    pub fn mark_signal_join(&self, callbacks: &EngineCallbacks) {
+        self.mark(|t| &mut t.signal_join_ms, callbacks);
+    }
+
+    pub fn mark_ice_connected(&self, callbacks: &EngineCallbacks) {
+        self.mark(|t| &mut t.ice_connected_ms, callbacks);
+    }
+
+    pub fn mark_first_packet(&self, callbacks: &EngineCallbacks) {
+        self.mark(|t| &mut t.first_packet_ms, callbacks);
+    }
+}
+
+/// Transport connection lifecycle, surfaced through
+/// `on_connection_state` so the UI can show accurate
+/// "connecting… / live / reconnecting" badges instead of guessing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The signal WebSocket joined the room.
+    SignalConnected,
+    IceChecking,
+    IceConnected,
+    IceDisconnected,
+    /// The signal connection dropped and is being re-dialed.
+    Reconnecting,
+    /// The transport has shut down (cleanly or not).
+    Closed,
+}
+
+impl ConnectionState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConnectionState::SignalConnected => "signal_connected",
+            ConnectionState::IceChecking => "ice_checking",
+            ConnectionState::IceConnected => "ice_connected",
+            ConnectionState::IceDisconnected => "ice_disconnected",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Closed => "closed",
+        }
+    }
+}
+
+/// Connection details delivered once through `on_connected`, confirming
+/// that publishing actually succeeded: the join completed, ICE connected,
+/// and the server acknowledged the video track.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectedInfo {
+    pub room_name: String,
+    pub room_sid: String,
+    pub participant_identity: String,
+    pub participant_sid: String,
+    /// Server-assigned sid of the published screen-share track.
+    pub track_sid: String,
+}
+
+/// Room-level happenings surfaced from the signal connection so the app
+/// can show who is watching and how good their connection is.
+pub enum RoomEvent {
+    ParticipantUpdate(Vec<livekit_protocol::ParticipantInfo>),
+    ConnectionQuality(Vec<livekit_protocol::ConnectionQualityInfo>),
+    SpeakersChanged(Vec<livekit_protocol::SpeakerInfo>),
+    /// The signal connection dropped and the engine is re-dialing.
+    Reconnecting,
+    /// The session was resumed; publishing continues with a fresh keyframe.
+    Reconnected,
+}
+
+/// Callbacks delivered from worker threads. On the NAPI side these wrap
+/// ThreadsafeFunctions.
+pub struct EngineCallbacks {
+    pub on_stats: Box<dyn Fn(EngineStats) + Send + Sync>,
+    /// Fatal failures. The variant's `code()` gives JS a stable category
+    /// to branch on.
+    pub on_error: Box<dyn Fn(&EngineError) + Send + Sync>,
+    /// Non-fatal issues — dropped frames, device glitches, bandwidth
+    /// downgrades — with a stable machine-readable code. The session keeps
+    /// running after every warning.
+    pub on_warning: Box<dyn Fn(&'static str, String) + Send + Sync>,
+    pub on_stopped: Box<dyn Fn(StopReason) + Send + Sync>,
+    pub on_room_event: Box<dyn Fn(RoomEvent) + Send + Sync>,
+    /// Fired once with the startup latency breakdown when the session
+    /// becomes fully live.
+    pub on_started: Box<dyn Fn(StartupTimings) + Send + Sync>,
+    /// Fired once when the join completed, ICE connected, and the server
+    /// acknowledged the published track.
+    pub on_connected: Box<dyn Fn(ConnectedInfo) + Send + Sync>,
+    /// Fired on every transport lifecycle transition.
+    pub on_connection_state: Box<dyn Fn(ConnectionState) + Send + Sync>,
+}
+
+impl EngineCallbacks {
+    /// A view of these callbacks for an auxiliary pipeline whose failures
+    /// shouldn't end the session: `on_error` is demoted to
+    /// `on_warning(code, …)`, everything else passes through.
+    fn demote_errors(self: &Arc<Self>, code: &'static str) -> Arc<Self> {
+        let on_stats = self.clone();
+        let on_error = self.clone();
+        let on_warning = self.clone();
+        let on_stopped = self.clone();
+        let on_room_event = self.clone();
+        let on_started = self.clone();
+        let on_connected = self.clone();
+        let on_connection_state = self.clone();
+        Arc::new(Self {
+            on_stats: Box::new(move |s| (on_stats.on_stats)(s)),
+            on_error: Box::new(move |e| (on_error.on_warning)(code, e.to_string())),
+            on_warning: Box::new(move |c, m| (on_warning.on_warning)(c, m)),
+            on_stopped: Box::new(move |r| (on_stopped.on_stopped)(r)),
+            on_room_event: Box::new(move |e| (on_room_event.on_room_event)(e)),
+            on_started: Box::new(move |t| (on_started.on_started)(t)),
+            on_connected: Box::new(move |i| (on_connected.on_connected)(i)),
+            on_connection_state: Box::new(move |s| (on_connection_state.on_connection_state)(s)),
+        })
+    }
+}
+
+/// Commands routed into the encode thread.
+pub enum EngineCommand {
+    ForceKeyframe,
+    /// Live bitrate change in kbps.
+    SetBitrate(u32),
+    /// Live frame-rate change; frames are thinned to the new rate.
+    SetFps(u32),
+    /// Live output-resolution change; rebuilds converter and encoder.
+    SetResolution(u32, u32),
+}
+
+/// Commands routed into the audio supervisor.
+pub enum AudioControl {
+    /// Stops or restarts the capture thread without ending the session.
+    SetEnabled(bool),
+    /// Re-targets capture (system mix vs. a process tree); restarts the
+    /// capture thread with the new mode.
+    SetMode(AudioMode),
+}
+
+/// A running screen share session.
+pub struct MediaEngine {
+    stop: Arc<AtomicBool>,
+    cmd_tx: Sender<EngineCommand>,
+    stats: SharedStats,
+    publish_control: Arc<PublishControl>,
+    /// Rolling buffer of recent GOPs when `replay_seconds` is configured.
+    replay: Option<Arc<std::sync::Mutex<ReplayBuffer>>>,
+    /// Command channel and controls for the optional camera track.
+    camera_cmd_tx: Option<Sender<EngineCommand>>,
+    /// Command channel for the audio supervisor, present when the session
+    /// started with an audio mode.
+    audio_cmd_tx: Option<Sender<AudioControl>>,
+    camera_control: Option<Arc<PublishControl>>,
+    /// Current LiveKit token, shared with the signal client so server
+    /// refreshes and app-provided updates apply to future reconnects.
+    token: Arc<std::sync::Mutex<String>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl MediaEngine {
+    /// Validates the config, spins up all worker threads, and returns once
+    /// the session is starting (not once it is connected).
+    pub fn start(config: ScreenShareConfig, callbacks: EngineCallbacks) -> EngineResult<Self> {
+        config.validate()?;
+        let target = config.target;
+        let overlay_target = config.overlay.as_ref().map(|o| o.target);
+        // No server URL = record-only: capture and encode to disk without
+        // spinning up signaling or WebRTC.
+        let record_only = config.server_url.is_empty();
+        if record_only
+            && config.record_path.is_none()
+            && config.rtmp_url.is_none()
+            && config.rtp_out.is_none()
+            && config.hls_dir.is_none()
+        {
+            return Err(EngineError::Config(
+                "record-only mode (empty serverUrl) requires recordPath, rtmpUrl, rtpOut, or hlsDir"
+                    .into(),
+            ));
+        }
+        let callbacks = Arc::new(callbacks);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = stats::new_shared();
+        let keyframe_request = Arc::new(AtomicBool::new(false));
+        let token = Arc::new(std::sync::Mutex::new(config.token.clone()));
+        let publish_control = PublishControl::new();
+        let stop_reason: StopReasonCell = Arc::new(std::sync::Mutex::new(None));
+        let startup = StartupTracker::new(record_only);
+        let replay = config
+            .replay_seconds
+            .map(|s| Arc::new(std::sync::Mutex::new(ReplayBuffer::new(s as u64))));
+
+        // Capture → encode: a latest-frame slot. Capture replaces the
+        // pending frame when the encoder falls behind, so the encoder
+        // always picks up the newest frame and never works off a backlog.
+        let (frame_tx, frame_rx) = capture::frame_slot();
+        // Encode → transport: encoded access units. Absent in record-only
+        // mode, where encoded frames stop at the recorder.
+        let (encoded_tx, encoded_rx) = if record_only {
+            (None, None)
+        } else {
+            let (tx, rx) = mpsc::channel();
+            (Some(tx), Some(rx))
+        };
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+
+        let mut threads = Vec::new();
+
+        // RTMP sink thread (optional): connects to the ingest and muxes
+        // encoded frames into FLV, fed by its own bounded channel so a
+        // slow ingest never stalls the encode loop. When the LiveKit
+        // transport is running too, an ingest failure only warns; in
+        // stream-only mode it ends the session like a transport failure.
+        let mut rtmp_tx = None;
+        if let Some(url) = config.rtmp_url.clone() {
+            let (tx, rx) = mpsc::sync_channel::<crate::encode::EncodedFrame>(60);
+            rtmp_tx = Some(tx);
+            let fatal = record_only;
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            let stop_reason = stop_reason.clone();
+            let encoder = config.encoder.clone();
+            threads.push(std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    rtmp_thread(&url, &encoder, rx, &stop)
+                }));
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e),
+                    Err(payload) => Some(EngineError::Panic(format!(
+                        "rtmp: {}",
+                        panic_message(payload.as_ref())
+                    ))),
+                };
+                if let Some(e) = error {
+                    tracing::error!("rtmp thread: {e}");
+                    if fatal {
+                        record_stop_reason(&stop_reason, StopReason::Disconnected);
+                        (callbacks.on_error)(&e);
+                        stop.store(true, Ordering::SeqCst);
+                    } else {
+                        (callbacks.on_warning)("rtmp", e.to_string());
+                    }
+                }
+            }));
+        }
+
+        // Camera chain (optional, not in record-only mode): its own
+        // capture + encode threads, publishing on the shared transport.
+        let mut camera_publish = None;
+        let mut camera_cmd_tx = None;
+        let mut camera_control = None;
+        if let Some(cam) = config.camera.clone().filter(|_| !record_only) {
+            let (cam_frame_tx, cam_frame_rx) = capture::frame_slot();
+            let (cam_encoded_tx, cam_encoded_rx) = mpsc::channel();
+            let (cam_tx, cam_rx) = mpsc::channel();
+            let cam_keyframe = Arc::new(AtomicBool::new(false));
+            let cam_publish_control = PublishControl::new();
+
+            // Camera capture thread. Camera failure shouldn't kill the
+            // screen share, mirroring the audio policy.
+            {
+                let stop = stop.clone();
+                let callbacks = callbacks.clone();
+                let index = cam.index;
+                threads.push(std::thread::spawn(move || {
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        capture::run_capture(
+                            CaptureTarget::Camera(index),
+                            cam.encoder.fps,
+                            false,
+                            cam_frame_tx,
+                            stop.clone(),
+                        )
+                    }));
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => {
+                            tracing::error!("camera capture thread: {e}");
+                            (callbacks.on_warning)("camera", e.to_string());
+                        }
+                        Err(payload) => {
+                            let e = EngineError::Panic(format!(
+                                "camera capture: {}",
+                                panic_message(payload.as_ref())
+                            ));
+                            tracing::error!("{e}");
+                            (callbacks.on_warning)("camera", e.to_string());
+                        }
+                    }
+                }));
+            }
+
+            // Camera encode thread: same loop as the screen share, fed a
+            // synthesized config with the camera encoder and no sinks.
+            // The loop reports through `on_error` — first-frame timeout
+            // when the device is busy, a failed second encoder instance —
+            // but camera failure shouldn't kill the screen share any more
+            // here than in the capture thread, so demote its errors to
+            // camera warnings.
+            {
+                let stop = stop.clone();
+                let callbacks = callbacks.demote_errors("camera");
+                let stats = stats.clone();
+                let mut cam_config = config.clone();
+                cam_config.encoder = cam.encoder.clone();
+                cam_config.record_path = None;
+                cam_config.record_rotate = None;
+                cam_config.replay_seconds = None;
+                cam_config.rtmp_url = None;
+                cam_config.rtp_out = None;
+                cam_config.hls_dir = None;
+                cam_config.metrics_port = None;
+                cam_config.trace_path = None;
+                cam_config.tees = Vec::new();
+                cam_config.overlay = None;
+                cam_config.ramp_up = None;
+                let cam_keyframe = cam_keyframe.clone();
+                let cam_publish_control = cam_publish_control.clone();
+                threads.push(std::thread::spawn(move || {
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        encode_publish_thread(
+                            cam_config,
+                            cam_frame_rx,
+                            None,
+                            Some(cam_encoded_tx),
+                            cam_rx,
+                            cam_keyframe,
+                            cam_publish_control,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            stop.clone(),
+                            stats,
+                            callbacks.clone(),
+                        )
+                    }));
+                    if let Err(payload) = result {
+                        let e = EngineError::Panic(format!(
+                            "camera encode: {}",
+                            panic_message(payload.as_ref())
+                        ));
+                        tracing::error!("{e}");
+                        (callbacks.on_warning)("camera", e.to_string());
+                    }
+                }));
+            }
+
+            camera_publish = Some(transport::CameraPublish {
+                frame_rx: cam_encoded_rx,
+                keyframe_request: cam_keyframe,
+                width: cam.encoder.width,
+                height: cam.encoder.height,
+            });
+            camera_cmd_tx = Some(cam_tx);
+            camera_control = Some(cam_publish_control);
+        }
+
+        // Overlay capture thread (optional): feeds the compositor in the
+        // encode thread. Overlay failure shouldn't kill the share.
+        let overlay_rx = match overlay_target {
+            Some(overlay_target) => {
+                let (overlay_tx, overlay_rx) = capture::frame_slot();
+                let stop = stop.clone();
+                let callbacks = callbacks.clone();
+                let fps = config.encoder.fps;
+                threads.push(std::thread::spawn(move || {
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        capture::run_capture(overlay_target, fps, false, overlay_tx, stop.clone())
+                    }));
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => {
+                            tracing::error!("overlay capture thread: {e}");
+                            (callbacks.on_warning)("overlay", e.to_string());
+                        }
+                        Err(payload) => {
+                            let e = EngineError::Panic(format!(
+                                "overlay capture: {}",
+                                panic_message(payload.as_ref())
+                            ));
+                            tracing::error!("{e}");
+                            (callbacks.on_warning)("overlay", e.to_string());
+                        }
+                    }
+                }));
+                Some(overlay_rx)
+            }
+            None => None,
+        };
+
+        // Capture thread.
+        {
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            let fps = config.encoder.fps;
+            let show_cursor = config.show_cursor;
+            let stop_reason = stop_reason.clone();
+            let mmcss = config.mmcss.clone();
+            threads.push(std::thread::spawn(move || {
+                let _mmcss = crate::mmcss::register("Capture", &mmcss);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    capture::run_capture(target, fps, show_cursor, frame_tx, stop.clone())
+                }));
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        tracing::error!("capture thread: {e}");
+                        record_stop_reason(&stop_reason, StopReason::CaptureClosed);
+                        (callbacks.on_error)(&e);
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    Err(payload) => {
+                        let e = EngineError::Panic(format!(
+                            "capture: {}",
+                            panic_message(payload.as_ref())
+                        ));
+                        tracing::error!("{e}");
+                        record_stop_reason(&stop_reason, StopReason::Fault);
+                        (callbacks.on_error)(&e);
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                }
+            }));
+        }
+
+        // Audio supervisor (optional). Audio flows to the transport — or to
+        // the raw RTP output in stream-only mode — so sessions with neither
+        // skip it. The supervisor owns the actual
+        // capture thread and can stop/restart it for runtime toggles and
+        // mode switches without touching the video pipeline.
+        let rtp_wants_audio = config
+            .rtp_out
+            .as_ref()
+            .is_some_and(|r| r.audio_address.is_some());
+        let mut audio_cmd_tx = None;
+        let mut audio_rx = match config
+            .audio_mode
+            .filter(|_| !record_only || rtp_wants_audio)
+        {
+            Some(mode) => {
+                let (audio_tx, audio_rx) = mpsc::channel();
+                let (cmd_tx, cmd_rx) = mpsc::channel();
+                audio_cmd_tx = Some(cmd_tx);
+                let stop = stop.clone();
+                let callbacks = callbacks.clone();
+                threads.push(std::thread::spawn(move || {
+                    audio_supervisor_thread(mode, audio_tx, cmd_rx, stop, callbacks)
+                }));
+                Some(audio_rx)
+            }
+            None => None,
+        };
+
+        // Raw RTP output thread (optional): packetizes encoded frames
+        // itself and fires them at the configured address, fed by its own
+        // bounded channel like the RTMP sink. In stream-only mode it also
+        // takes ownership of the audio packets.
+        let mut rtp_tx = None;
+        if let Some(rtp) = config.rtp_out.clone() {
+            let (tx, rx) = mpsc::sync_channel::<crate::encode::EncodedFrame>(60);
+            rtp_tx = Some(tx);
+            let rtp_audio_rx = if record_only { audio_rx.take() } else { None };
+            let fatal = record_only;
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            let stop_reason = stop_reason.clone();
+            threads.push(std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    transport::rtp_out::rtp_out_thread(&rtp, rx, rtp_audio_rx, &stop)
+                }));
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e),
+                    Err(payload) => Some(EngineError::Panic(format!(
+                        "rtp out: {}",
+                        panic_message(payload.as_ref())
+                    ))),
+                };
+                if let Some(e) = error {
+                    tracing::error!("rtp out thread: {e}");
+                    if fatal {
+                        record_stop_reason(&stop_reason, StopReason::Disconnected);
+                        (callbacks.on_error)(&e);
+                        stop.store(true, Ordering::SeqCst);
+                    } else {
+                        (callbacks.on_warning)("rtp_out", e.to_string());
+                    }
+                }
+            }));
+        }
+
+        // Pipeline profiling (optional): arm the process-global trace
+        // collector; the encode thread writes the file on exit.
+        if config.trace_path.is_some() {
+            crate::trace::start();
+        }
+
+        // Metrics exporter (optional): a loopback HTTP endpoint for
+        // Prometheus scrapes. Never fatal — losing metrics shouldn't end
+        // a share.
+        if let Some(port) = config.metrics_port {
+            let stats = stats.clone();
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            threads.push(std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    crate::metrics::exporter_thread(port, stats, &stop)
+                }));
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e),
+                    Err(payload) => Some(EngineError::Panic(format!(
+                        "metrics: {}",
+                        panic_message(payload.as_ref())
+                    ))),
+                };
+                if let Some(e) = error {
+                    tracing::error!("metrics thread: {e}");
+                    (callbacks.on_warning)("metrics", e.to_string());
+                }
+            }));
+        }
+
+        // HLS segmenter thread (optional): cuts the encode into fMP4
+        // segments on disk for link sharing. Disk is local, so failures
+        // here (out of space, permissions) only warn unless the segmenter
+        // is the sole output.
+        let mut hls_tx = None;
+        if let Some(dir) = config.hls_dir.clone() {
+            let (tx, rx) = mpsc::sync_channel::<crate::encode::EncodedFrame>(60);
+            hls_tx = Some(tx);
+            let fatal = record_only
+                && config.record_path.is_none()
+                && config.rtmp_url.is_none()
+                && config.rtp_out.is_none();
+            let segment_seconds = config.encoder.gop_seconds;
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            let stop_reason = stop_reason.clone();
+            threads.push(std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    hls_thread(std::path::Path::new(&dir), segment_seconds, rx, &stop)
+                }));
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e),
+                    Err(payload) => Some(EngineError::Panic(format!(
+                        "hls: {}",
+                        panic_message(payload.as_ref())
+                    ))),
+                };
+                if let Some(e) = error {
+                    tracing::error!("hls thread: {e}");
+                    if fatal {
+                        record_stop_reason(&stop_reason, StopReason::Fault);
+                        (callbacks.on_error)(&e);
+                        stop.store(true, Ordering::SeqCst);
+                    } else {
+                        (callbacks.on_warning)("hls", e.to_string());
+                    }
+                }
+            }));
+        }
+
+        // Encode thread.
+        {
+            let stop = stop.clone();
+            let callbacks = callbacks.clone();
+            let stats = stats.clone();
+            let config = config.clone();
+            let keyframe_request = keyframe_request.clone();
+            let publish_control = publish_control.clone();
+            let stop_reason = stop_reason.clone();
+            let replay = replay.clone();
+            let startup = startup.clone();
+            let trace_path = config.trace_path.clone();
+            threads.push(std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    encode_publish_thread(
+                        config,
+                        frame_rx,
+                        overlay_rx,
+                        encoded_tx,
+                        cmd_rx,
+                        keyframe_request,
+                        publish_control,
+                        replay,
+                        rtmp_tx,
+                        rtp_tx,
+                        hls_tx,
+                        Some(startup),
+                        stop.clone(),
+                        stats,
+                        callbacks.clone(),
+                    )
+                }));
+                stop.store(true, Ordering::SeqCst);
+                let thread_reason = match result {
+                    Ok(reason) => reason,
+                    Err(payload) => {
+                        let e = EngineError::Panic(format!(
+                            "encode: {}",
+                            panic_message(payload.as_ref())
+                        ));
+                        tracing::error!("{e}");
+                        (callbacks.on_error)(&e);
+                        StopReason::Fault
+                    }
+                };
+                if let Some(path) = trace_path {
+                    match crate::trace::stop_and_write(std::path::Path::new(&path)) {
+                        Ok(events) => tracing::info!("trace: wrote {events} events to {path}"),
+                        Err(e) => (callbacks.on_warning)("trace", e.to_string()),
+                    }
+                }
+                // Another thread's fatal condition takes precedence over
+                // whatever ended the encode loop.
+                let reason = stop_reason
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .unwrap_or(thread_reason);
+                (callbacks.on_stopped)(reason);
+            }));
+        }
+
+        // Transport thread (skipped in record-only mode).
+        if let Some(encoded_rx) = encoded_rx {
+            let stop = stop.clone();
+            let stats = stats.clone();
+            let config = config.clone();
+            let callbacks = callbacks.clone();
+            let token = token.clone();
+            let publish_control = publish_control.clone();
+            let stop_reason = stop_reason.clone();
+            threads.push(std::thread::spawn(move || {
+                let _mmcss = crate::mmcss::register("Playback", &config.mmcss);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    transport::transport_thread(
+                        config,
+                        encoded_rx,
+                        audio_rx,
+                        camera_publish,
+                        keyframe_request,
+                        publish_control,
+                        stop.clone(),
+                        stats,
+                        callbacks.clone(),
+                        token,
+                        stop_reason.clone(),
+                        startup,
+                    )
+                }));
+                if let Err(payload) = result {
+                    let e = EngineError::Panic(format!(
+                        "transport: {}",
+                        panic_message(payload.as_ref())
+                    ));
+                    tracing::error!("{e}");
+                    record_stop_reason(&stop_reason, StopReason::Fault);
+                    (callbacks.on_error)(&e);
+                    stop.store(true, Ordering::SeqCst);
+                }
+            }));
+        }
+
+        // Stats emitter.
+        {
+            let stop = stop.clone();
+            let stats = stats.clone();
+            let callbacks = callbacks.clone();
+            let interval = Duration::from_millis(config.stats_interval_ms.max(100));
+            threads.push(std::thread::spawn(move || {
+                let mut last = EngineStats::default();
+                let mut last_instant = Instant::now();
+                let mut monitor = crate::sysmon::SystemMonitor::new();
+                while !stop.load(Ordering::SeqCst) {
+                    std::thread::sleep(interval);
+                    // Store utilization in the shared stats so synchronous
+                    // snapshots (exporter, getScreenShareStats) see it too.
+                    let util = monitor.sample();
+                    let mut snapshot = {
+                        let mut s = stats.lock().unwrap();
+                        s.cpu_percent = util.cpu_percent;
+                        s.gpu_percent = util.gpu_percent;
+                        s.gpu_memory_mb = util.gpu_memory_mb;
+                        s.clone()
+                    };
+                    snapshot.compute_percentiles();
+                    let dt = last_instant.elapsed().as_secs_f64();
+                    last_instant = Instant::now();
+                    snapshot.fps = (snapshot.frames_encoded - last.frames_encoded) as f64 / dt;
+                    snapshot.bitrate_kbps =
+                        (snapshot.bytes_sent - last.bytes_sent) as f64 * 8.0 / 1000.0 / dt;
+                    let new_keyframes = snapshot.keyframes_encoded - last.keyframes_encoded;
+                    snapshot.avg_keyframe_bytes = if new_keyframes > 0 {
+                        (snapshot.keyframe_bytes - last.keyframe_bytes) as f64
+                            / new_keyframes as f64
+                    } else {
+                        0.0
+                    };
+                    let new_dropped = snapshot.frames_dropped - last.frames_dropped;
+                    if new_dropped > 0 {
+                        (callbacks.on_warning)(
+                            "frames_dropped",
+                            format!("{new_dropped} frames dropped in the last interval"),
+                        );
+                    }
+                    last = snapshot.clone();
+                    (callbacks.on_stats)(snapshot);
+                }
+            }));
+        }
+
+        Ok(Self {
+            stop,
+            cmd_tx,
+            stats,
+            publish_control,
+            replay,
+            camera_cmd_tx,
+            audio_cmd_tx,
+            camera_control,
+            token,
+            threads,
+        })
+    }
+
+    /// Replaces the session token. The next signal reconnect uses the new
+    /// token; LiveKit-issued refreshes land here automatically.
+    pub fn update_token(&self, token: String) {
+        *self.token.lock().unwrap() = token;
+    }
+
+    /// Request an IDR from the encoder (e.g. when a new viewer subscribes).
+    pub fn force_keyframe(&self) {
+        let _ = self.cmd_tx.send(EngineCommand::ForceKeyframe);
+    }
+
+    /// Stops encoding and audio sending while keeping capture, encoder, and
+    /// the LiveKit connection warm. Resuming is instant, unlike a restart.
+    pub fn pause(&self) {
+        self.publish_control.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes a paused session. Viewers need an IDR to pick the stream
+    /// back up, so one is requested immediately.
+    pub fn resume(&self) {
+        if self.publish_control.paused.swap(false, Ordering::SeqCst) {
+            self.force_keyframe();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.publish_control.paused.load(Ordering::SeqCst)
+    }
+
+    /// Mutes or unmutes the published video track: RTP stops, the server
+    /// gets a MuteTrack signal, and everything else stays warm — unlike
+    /// `pause`, audio keeps flowing. Unmuting requests an IDR so viewers
+    /// recover immediately.
+    pub fn set_video_muted(&self, muted: bool) {
+        let was_muted = self.publish_control.video_muted.swap(muted, Ordering::SeqCst);
+        if was_muted && !muted {
+            self.force_keyframe();
+        }
+    }
+
+    /// Mutes or unmutes the screen-share audio track without touching the
+    /// video. Capture keeps running; packets are dropped before RTP.
+    pub fn set_audio_muted(&self, muted: bool) {
+        self.publish_control.audio_muted.store(muted, Ordering::SeqCst);
+    }
+
+    /// Mutes or unmutes the camera track without touching the screen
+    /// share. Unmuting requests an IDR so viewers recover immediately.
+    pub fn set_camera_muted(&self, muted: bool) {
+        let Some(control) = self.camera_control.as_ref() else {
+            return;
+        };
+        let was_muted = control.paused.swap(muted, Ordering::SeqCst);
+        if was_muted && !muted {
+            if let Some(cmd_tx) = self.camera_cmd_tx.as_ref() {
+                let _ = cmd_tx.send(EngineCommand::ForceKeyframe);
+            }
+        }
+    }
+
+    /// Changes the camera encoder bitrate on the live session.
+    pub fn set_camera_bitrate(&self, bitrate_kbps: u32) {
+        if let Some(cmd_tx) = self.camera_cmd_tx.as_ref() {
+            let _ = cmd_tx.send(EngineCommand::SetBitrate(bitrate_kbps));
+        }
+    }
+
+    /// Starts or stops audio capture on the live session. A no-op when the
+    /// session was started without an audio mode: the transport has no
+    /// audio track to feed.
+    pub fn set_audio_enabled(&self, enabled: bool) {
+        if let Some(cmd_tx) = self.audio_cmd_tx.as_ref() {
+            let _ = cmd_tx.send(AudioControl::SetEnabled(enabled));
+        }
+    }
+
+    /// Re-targets audio capture (system mix vs. a process tree) on the
+    /// live session without touching the video pipeline.
+    pub fn set_audio_mode(&self, mode: AudioMode) {
+        if let Some(cmd_tx) = self.audio_cmd_tx.as_ref() {
+            let _ = cmd_tx.send(AudioControl::SetMode(mode));
+        }
+    }
+
+    /// Changes the encoder bitrate on the live session.
+    pub fn set_bitrate(&self, bitrate_kbps: u32) {
+        let _ = self.cmd_tx.send(EngineCommand::SetBitrate(bitrate_kbps));
+    }
+
+    /// Changes the encode frame rate on the live session.
+    pub fn set_fps(&self, fps: u32) {
+        let _ = self.cmd_tx.send(EngineCommand::SetFps(fps));
+    }
+
+    /// Changes the output resolution on the live session.
+    pub fn set_resolution(&self, width: u32, height: u32) {
+        let _ = self.cmd_tx.send(EngineCommand::SetResolution(width, height));
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.stop.load(Ordering::SeqCst)
+    }
+
+    /// Writes the last `seconds` of buffered video to `path`. Requires
+    /// `replay_seconds` to have been set at start.
+    pub fn save_replay(&self, path: &std::path::Path, seconds: u64) -> EngineResult<u64> {
+        let replay = self
+            .replay
+            .as_ref()
+            .ok_or_else(|| EngineError::Config("replay buffer not enabled".into()))?;
+        replay.lock().unwrap().save(path, seconds)
+    }
+
+    /// Exports the last `seconds` of buffered video as an animated GIF.
+    /// Requires `replay_seconds` to have been set at start.
+    pub fn export_clip(
+        &self,
+        path: &std::path::Path,
+        seconds: u64,
+        options: &crate::clip::ClipOptions,
+    ) -> EngineResult<u64> {
+        let replay = self
+            .replay
+            .as_ref()
+            .ok_or_else(|| EngineError::Config("replay buffer not enabled".into()))?;
+        replay.lock().unwrap().export_clip(path, seconds, options)
+    }
+
+    pub fn current_stats(&self) -> EngineStats {
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.compute_percentiles();
+        stats
+    }
+
+    /// The current stats snapshot in Prometheus text exposition format,
+    /// for callers that scrape through the app instead of the exporter.
+    pub fn prometheus_metrics(&self) -> String {
+        crate::metrics::render(&self.current_stats())
+    }
+
+    /// Signals all threads to stop. Does not wait for them; `MediaEngine`
+    /// drops its handles when the struct is dropped.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops the session and joins every worker thread, returning the final
+    /// stats. When this returns, the encoder flush and the transport leave
+    /// have completed, so it is safe to start a new session immediately.
+    pub fn stop_and_wait(mut self) -> EngineStats {
+        self.stop();
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.compute_percentiles();
+        stats
+    }
+}
+
+impl Drop for MediaEngine {
+    fn drop(&mut self) {
+        self.stop();
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+        // All workers have joined, so nothing is legitimately holding
+        // pipeline resources anymore.
+        crate::leak::assert_drained();
+    }
+}
+
+/// Connects to the RTMP ingest and feeds it encoded frames until the
+/// session stops or the encode thread hangs up. Runs on its own thread so
+/// ingest backpressure never reaches the encode loop.
+fn rtmp_thread(
+    url: &str,
+    encoder: &EncoderConfig,
+    frame_rx: Receiver<crate::encode::EncodedFrame>,
+    stop: &AtomicBool,
+) -> EngineResult<()> {
+    let mut sink =
+        crate::rtmp::RtmpSink::connect(url, encoder.width, encoder.height, encoder.fps)?;
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        match frame_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(frame) => sink.write_frame(&frame)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    match sink.finish() {
+        Ok(frames) => tracing::info!("rtmp stream finished: {frames} frames"),
+        Err(e) => tracing::warn!("rtmp teardown: {e}"),
+    }
+    Ok(())
+}
+
+/// Segments encoded frames into fMP4 + an HLS playlist until the session
+/// stops or the encode thread hangs up. Segments cut at keyframes, so the
+/// target follows the encoder's GOP length.
+fn hls_thread(
+    dir: &std::path::Path,
+    segment_seconds: u32,
+    frame_rx: Receiver<crate::encode::EncodedFrame>,
+    stop: &AtomicBool,
+) -> EngineResult<()> {
+    let mut segmenter = crate::mux::fmp4::HlsSegmenter::create(dir, segment_seconds)?;
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        match frame_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(frame) => segmenter.write_video(&frame)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    let segments = segmenter.finish()?;
+    tracing::info!("hls stream finished: {segments} segments");
+    Ok(())
+}
+
+/// Owns the audio capture thread, restarting it on enable toggles and mode
+/// switches. Capture failure — error or panic — shouldn't kill the video
+/// share, but it must not die silently either.
+fn audio_supervisor_thread(
+    initial_mode: AudioMode,
+    packet_tx: Sender<crate::audio::AudioPacket>,
+    cmd_rx: Receiver<AudioControl>,
+    stop: Arc<AtomicBool>,
+    callbacks: Arc<EngineCallbacks>,
+) {
+    let mut mode = initial_mode;
+    let mut enabled = true;
+    let mut run: Option<(Arc<AtomicBool>, JoinHandle<()>)> = None;
+
+    let stop_run = |run: &mut Option<(Arc<AtomicBool>, JoinHandle<()>)>| {
+        if let Some((run_stop, handle)) = run.take() {
+            run_stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+    };
+
+    while !stop.load(Ordering::SeqCst) {
+        if enabled && run.is_none() {
+            let run_stop = Arc::new(AtomicBool::new(false));
+            let audio_config = AudioCaptureConfig {
+                mode,
+                sample_rate: audio::OPUS_SAMPLE_RATE,
+                channels: audio::OPUS_CHANNELS,
+            };
+            let packet_tx = packet_tx.clone();
+            let thread_stop = run_stop.clone();
+            let callbacks = callbacks.clone();
+            let handle = std::thread::spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    audio::run_audio_capture(audio_config, packet_tx, thread_stop)
+                }));
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        tracing::error!("audio thread: {e}");
+                        (callbacks.on_warning)("audio", e.to_string());
+                    }
+                    Err(payload) => {
+                        let e = EngineError::Panic(format!(
+                            "audio: {}",
+                            panic_message(payload.as_ref())
+                        ));
+                        tracing::error!("{e}");
+                        (callbacks.on_warning)("audio", e.to_string());
+                    }
+                }
+            });
+            run = Some((run_stop, handle));
+        }
+
+        match cmd_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(AudioControl::SetEnabled(value)) => {
+                if value != enabled {
+                    enabled = value;
+                    stop_run(&mut run);
+                }
+            }
+            Ok(AudioControl::SetMode(new_mode)) => {
+                if new_mode != mode {
+                    mode = new_mode;
+                    // Restart with the new target; the loop respawns it.
+                    stop_run(&mut run);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    stop_run(&mut run);
+}
+
+/// The encode loop: waits for captured frames, always encoding the newest
+/// one available, and forwards encoded output to the transport (and the
+/// recorder, when configured).
+#[allow(clippy::too_many_arguments)]
+fn encode_publish_thread(
+    config: ScreenShareConfig,
+    frame_rx: FrameReceiver,
+    overlay_rx: Option<FrameReceiver>,
+    encoded_tx: Option<Sender<crate::encode::EncodedFrame>>,
+    cmd_rx: Receiver<EngineCommand>,
+    keyframe_request: Arc<AtomicBool>,
+    publish_control: Arc<PublishControl>,
+    replay: Option<Arc<std::sync::Mutex<ReplayBuffer>>>,
+    rtmp_tx: Option<SyncSender<crate::encode::EncodedFrame>>,
+    rtp_tx: Option<SyncSender<crate::encode::EncodedFrame>>,
+    hls_tx: Option<SyncSender<crate::encode::EncodedFrame>>,
+    startup: Option<Arc<StartupTracker>>,
+    stop: Arc<AtomicBool>,
+    stats: SharedStats,
+    callbacks: Arc<EngineCallbacks>,
+) -> StopReason {
+    let _mmcss = crate::mmcss::register("Playback", &config.mmcss);
+    // Wait for the first frame so we know the capture size before creating
+    // GPU resources.
+    let first = match frame_rx.recv_timeout(Duration::from_millis(config.first_frame_timeout_ms)) {
+        Ok(frame) => frame,
+        Err(_) => {
+            (callbacks.on_error)(&EngineError::FirstFrameTimeout);
+            return StopReason::CaptureClosed;
+        }
+    };
+    if let Some(startup) = startup.as_ref() {
+        startup.mark_first_frame(&callbacks);
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (
+            first,
+            overlay_rx,
+            encoded_tx,
+            startup,
+            cmd_rx,
+            keyframe_request,
+            publish_control,
+            replay,
+            rtmp_tx,
+            rtp_tx,
+            hls_tx,
+            stats,
+            config,
+        );
+        (callbacks.on_error)(&EngineError::Encode(
+        "encoding is only implemented on Windows".into(),
+    ));
+        return StopReason::EncoderFailed;
+    }
+
+    #[cfg(windows)]
+    {
+        let (device, context) = match crate::encode::d3d::take_or_create_device() {
+            Ok(pair) => pair,
+            Err(e) => {
+                (callbacks.on_error)(&e);
+                return StopReason::EncoderFailed;
+            }
+        };
+        let mut pipeline = match EncodePipeline::new(
+            device,
+            context,
+            first.width,
+            first.height,
+            &config.encoder,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                (callbacks.on_error)(&e);
+                return StopReason::EncoderFailed;
+            }
+        };
+        for tee in &config.tees {
+            if let Err(e) = pipeline.add_tee(&tee.encoder, std::path::Path::new(&tee.record_path)) {
+                (callbacks.on_error)(&e);
+                return StopReason::EncoderFailed;
+            }
+        }
+
+        // Record which backend is doing the work, so stats consumers can
+        // segment performance reports by encoder.
+        {
+            let mut s = stats.lock().unwrap();
+            s.encoder_name = pipeline.encoder_name().to_string();
+            s.encoder_vendor =
+                crate::capabilities::vendor_from_name(pipeline.encoder_name()).to_string();
+            s.encoder_hardware = pipeline.is_hardware();
+            // Frames only stay on the GPU when a hardware MFT is driving.
+            s.encoder_d3d = pipeline.is_hardware();
+        }
+
+        if let Some(startup) = startup.as_ref() {
+            startup.mark_encoder_ready(&callbacks);
+        }
+
+        let mut recorder = match config.record_path.as_ref() {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let result = match config.record_rotate.as_ref() {
+                    Some(rotate) => Recorder::create_rotating(&path, rotate),
+                    None => Recorder::create(&path),
+                };
+                match result {
+                    Ok(r) => Some(r),
+                    Err(e) => {
+                        (callbacks.on_error)(&e);
+                        return StopReason::EncoderFailed;
+                    }
+                }
+            }
+            None => None,
+        };
+
+        // Startup bitrate ramp: begin below the target and double every
+        // step while delivery stays healthy. Only meaningful when there is
+        // a transport to give feedback, so record-only sessions skip it.
+        let mut ramp = config
+            .ramp_up
+            .as_ref()
+            .filter(|_| encoded_tx.is_some())
+            .filter(|r| r.start_kbps < config.encoder.bitrate_kbps)
+            .map(|r| (r.start_kbps, Instant::now() + Duration::from_millis(r.step_ms)));
+        if let Some((start_kbps, _)) = ramp {
+            if let Err(e) = pipeline.set_bitrate(start_kbps) {
+                tracing::warn!("ramp start bitrate: {e}");
+                ramp = None;
+            }
+        }
+
+        let mut next = Some(first);
+        let mut last_overlay: Option<CaptureFrame> = None;
+        let mut exit_reason = StopReason::UserRequested;
+        // Some hardware MFTs emit SPS/PPS only on the first IDR; cache and
+        // re-insert them so every keyframe leaving here decodes standalone
+        // for late subscribers and mid-stream sinks.
+        let mut parameter_sets = crate::bitstream::h264::ParameterSets::default();
+        let mut encode_ms_acc = 0.0f64;
+        let mut encode_count = 0u64;
+        let mut frame_counter = 0u64;
+        // Capture keeps running at the configured rate; fps changes thin
+        // frames here instead of re-pacing the capture thread.
+        let mut target_fps = config.encoder.fps.max(1);
+
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            match cmd_rx.try_recv() {
+                Ok(EngineCommand::ForceKeyframe) => pipeline.force_keyframe(),
+                Ok(EngineCommand::SetBitrate(kbps)) => {
+                    // An explicit bitrate takes over from the startup ramp.
+                    ramp = None;
+                    if let Err(e) = pipeline.set_bitrate(kbps) {
+                        tracing::warn!("set bitrate: {e}");
+                    }
+                }
+                Ok(EngineCommand::SetFps(fps)) => {
+                    target_fps = fps.max(1);
+                    pipeline.set_fps(fps);
+                }
+                Ok(EngineCommand::SetResolution(width, height)) => {
+                    if let Err(e) = pipeline.set_resolution(width, height) {
+                        (callbacks.on_error)(&e);
+                        exit_reason = StopReason::EncoderFailed;
+                        break;
+                    }
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => break,
+            }
+            if keyframe_request.swap(false, Ordering::SeqCst) {
+                pipeline.force_keyframe();
+            }
+
+            // Advance the startup ramp: double toward the target while the
+            // server reports healthy delivery, hold while it doesn't.
+            if let Some((current, next_step)) = ramp {
+                if Instant::now() >= next_step {
+                    let policy = config.ramp_up.as_ref().unwrap();
+                    let step = Duration::from_millis(policy.step_ms);
+                    if publish_control.delivery_degraded.load(Ordering::SeqCst) {
+                        ramp = Some((current, Instant::now() + step));
+                    } else {
+                        let target = config.encoder.bitrate_kbps;
+                        let stepped = current.saturating_mul(2).min(target);
+                        if let Err(e) = pipeline.set_bitrate(stepped) {
+                            tracing::warn!("ramp bitrate: {e}");
+                        }
+                        ramp = if stepped >= target {
+                            None
+                        } else {
+                            Some((stepped, Instant::now() + step))
+                        };
+                    }
+                }
+            }
+
+            let frame = match next.take() {
+                Some(frame) => frame,
+                None => match frame_rx.recv_timeout(Duration::from_millis(500)) {
+                    Ok(frame) => frame,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+            };
+
+            // The slot already discarded older frames; anything capture
+            // replaced while this loop was busy counts as dropped.
+            let mut frame = frame;
+            let dropped = frame_rx.take_dropped();
+
+            // Composite the newest overlay frame into the main one. If the
+            // overlay source stalls, its last frame keeps showing.
+            if let (Some(overlay_rx), Some(overlay)) =
+                (overlay_rx.as_ref(), config.overlay.as_ref())
+            {
+                if let Ok(newer) = overlay_rx.try_recv() {
+                    last_overlay = Some(newer);
+                }
+                if let Some(last) = last_overlay.as_ref() {
+                    crate::compose::blit_overlay(&mut frame, last, overlay.anchor, overlay.fraction);
+                }
+            }
+
+            {
+                let mut s = stats.lock().unwrap();
+                s.frames_captured += 1 + dropped;
+                s.frames_dropped += dropped;
+            }
+
+            // Skip encoding entirely while paused by the user or while the
+            // SFU reports no subscribers; thin frames when only lower
+            // qualities are wanted.
+            if publish_control.paused.load(Ordering::SeqCst)
+                || !publish_control
+                    .encoding_enabled
+                    .load(Ordering::SeqCst)
+            {
+                continue;
+            }
+            frame_counter += 1;
+            let quality_divisor = match publish_control.max_quality.load(Ordering::SeqCst) {
+                q if q == livekit_protocol::VideoQuality::Low as u32 => 3,
+                q if q == livekit_protocol::VideoQuality::Medium as u32 => 2,
+                _ => 1,
+            };
+            let fps_divisor = (config.encoder.fps.max(1) / target_fps).max(1) as u64;
+            let divisor = quality_divisor.max(fps_divisor);
+            if divisor > 1 && frame_counter % divisor != 0 {
+                continue;
+            }
+
+            let encode_start = Instant::now();
+            match pipeline.encode(&frame) {
+                Ok(Some(mut encoded)) => {
+                    parameter_sets.observe(&encoded.data);
+                    if encoded.is_keyframe {
+                        parameter_sets.ensure_keyframe_parameter_sets(&mut encoded.data);
+                    }
+                    let encode_ms = encode_start.elapsed().as_secs_f64() * 1000.0;
+                    encode_ms_acc += encode_ms;
+                    encode_count += 1;
+                    {
+                        let mut s = stats.lock().unwrap();
+                        s.frames_encoded += 1;
+                        s.avg_encode_ms = encode_ms_acc / encode_count as f64;
+                        s.push_encode_ms(encode_ms);
+                        if encoded.is_keyframe {
+                            s.keyframes_encoded += 1;
+                            s.keyframe_bytes += encoded.data.len() as u64;
+                        }
+                    }
+                    if let Some(recorder) = recorder.as_mut() {
+                        if let Err(e) = recorder.write_frame(&encoded) {
+                            tracing::error!("recorder: {e}");
+                        }
+                    }
+                    if let Some(replay) = replay.as_ref() {
+                        replay.lock().unwrap().push(&encoded);
+                    }
+                    if let Some(rtmp_tx) = rtmp_tx.as_ref() {
+                        // The ingest thread owns its own pacing; when it
+                        // backs up, frames are dropped here rather than
+                        // stalling the encode loop.
+                        if let Err(mpsc::TrySendError::Full(_)) =
+                            rtmp_tx.try_send(encoded.clone())
+                        {
+                            tracing::debug!("rtmp ingest behind, dropping frame");
+                        }
+                    }
+                    if let Some(rtp_tx) = rtp_tx.as_ref() {
+                        if let Err(mpsc::TrySendError::Full(_)) =
+                            rtp_tx.try_send(encoded.clone())
+                        {
+                            tracing::debug!("rtp out behind, dropping frame");
+                        }
+                    }
+                    if let Some(hls_tx) = hls_tx.as_ref() {
+                        if let Err(mpsc::TrySendError::Full(_)) =
+                            hls_tx.try_send(encoded.clone())
+                        {
+                            tracing::debug!("hls segmenter behind, dropping frame");
+                        }
+                    }
+                    if let Some(encoded_tx) = encoded_tx.as_ref() {
+                        if encoded_tx.send(encoded).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    (callbacks.on_error)(&e);
+                    exit_reason = StopReason::EncoderFailed;
+                    break;
+                }
+            }
+        }
+
+        if let Err(e) = pipeline.flush() {
+            tracing::warn!("encoder flush: {e}");
+        }
+        if let Some(recorder) = recorder {
+            match recorder.finish() {
+                Ok(frames) => tracing::info!("recording finished: {frames} frames"),
+                Err(e) => tracing::error!("recorder finish: {e}"),
+            }
+        }
+        exit_reason
+    }
+}
@@ -0,0 +1,71 @@
+use thiserror::Error;
+
+/// Errors produced anywhere in the capture → encode → transport pipeline.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("capture error: {0}")]
+    Capture(String),
+
+    #[error("encoder error: {0}")]
+    Encode(String),
+
+    #[error("audio error: {0}")]
+    Audio(String),
+
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("signal error: {0}")]
+    Signal(String),
+
+    #[error("engine is already running")]
+    AlreadyRunning,
+
+    #[error("engine is not running")]
+    NotRunning,
+
+    #[error("timed out waiting for first frame")]
+    FirstFrameTimeout,
+
+    #[error("signal connect timed out: {0}")]
+    SignalTimeout(String),
+
+    #[error("ICE connect timed out: {0}")]
+    IceTimeout(String),
+
+    #[error("worker thread panicked: {0}")]
+    Panic(String),
+}
+
+impl EngineError {
+    /// Stable machine-readable code for the JS side, e.g. `"encode"` or
+    /// `"signal_timeout"`. Codes are append-only; message text may change.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EngineError::Config(_) => "config",
+            EngineError::Capture(_) => "capture",
+            EngineError::Encode(_) => "encode",
+            EngineError::Audio(_) => "audio",
+            EngineError::Transport(_) => "transport",
+            EngineError::Signal(_) => "signal",
+            EngineError::AlreadyRunning => "already_running",
+            EngineError::NotRunning => "not_running",
+            EngineError::FirstFrameTimeout => "first_frame_timeout",
+            EngineError::SignalTimeout(_) => "signal_timeout",
+            EngineError::IceTimeout(_) => "ice_timeout",
+            EngineError::Panic(_) => "panic",
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for EngineError {
+    fn from(e: windows::core::Error) -> Self {
+        EngineError::Capture(format!("{e}"))
+    }
+}
+
+pub type EngineResult<T> = Result<T, EngineError>;
@@ -0,0 +1,86 @@
+//! Live-object counters for the D3D/Media Foundation resources the
+//! pipeline creates per frame. A leaked video processor input view once
+//! ran the GPU out of memory and froze whole systems, so the hot-path
+//! object lifetimes are now tracked: creation sites take a [`Guard`]
+//! scoped to the object, and shutdown asserts every counter drained.
+//!
+//! Counting is two relaxed atomics per object and stays on in release
+//! builds; the shutdown assertion only fires in debug builds, where a
+//! leak aborts the process instead of hiding in a log.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Tracks how many instances of one resource kind are currently alive.
+pub struct Counter {
+    name: &'static str,
+    created: AtomicU64,
+    released: AtomicU64,
+}
+
+/// BGRA upload and readback staging textures.
+pub static TEXTURES: Counter = Counter::new("d3d textures");
+/// Video processor input views — the historical offender.
+pub static INPUT_VIEWS: Counter = Counter::new("video processor input views");
+/// Media Foundation samples, input and output.
+pub static SAMPLES: Counter = Counter::new("mf samples");
+/// Media Foundation media buffers.
+pub static BUFFERS: Counter = Counter::new("mf buffers");
+
+const ALL: [&Counter; 4] = [&TEXTURES, &INPUT_VIEWS, &SAMPLES, &BUFFERS];
+
+impl Counter {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            created: AtomicU64::new(0),
+            released: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts one object as created; the returned guard counts it released
+    /// on drop. Declare the guard right next to the resource so their
+    /// scopes match.
+    pub fn track(&'static self) -> Guard {
+        self.created.fetch_add(1, Ordering::Relaxed);
+        Guard { counter: self }
+    }
+
+    /// Objects created but not yet released.
+    pub fn live(&self) -> u64 {
+        // Loads race with in-flight frames; saturate rather than underflow.
+        self.created
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.released.load(Ordering::Relaxed))
+    }
+}
+
+/// Scope marker for one tracked resource.
+pub struct Guard {
+    counter: &'static Counter,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.counter.released.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Reports any counter with live objects and, in debug builds, panics so
+/// tests catch the leak. Call after every worker thread has joined —
+/// in-flight frames legitimately hold resources while threads run, as
+/// does a second concurrent session (counters are process-global).
+pub fn assert_drained() {
+    let mut leaked = false;
+    for counter in ALL {
+        let live = counter.live();
+        if live > 0 {
+            leaked = true;
+            tracing::error!(
+                "resource leak: {live} {} still live at shutdown ({} created)",
+                counter.name,
+                counter.created.load(Ordering::Relaxed),
+            );
+        }
+    }
+    debug_assert!(!leaked, "pipeline leaked resources; see log for counts");
+}
@@ -0,0 +1,35 @@
+//! The native screen share engine: capture → encode → LiveKit transport.
+//!
+//! Built with the default `node` feature this is the NAPI addon the
+//! Electron main process loads; without it, `media_engine` is a plain
+//! Rust library exposing the engine/capture/encode/transport APIs.
+
+#![cfg_attr(not(windows), allow(dead_code))]
+
+pub mod audio;
+pub mod bitstream;
+pub mod capabilities;
+pub mod capture;
+pub mod clip;
+pub mod compose;
+pub mod config;
+pub mod diagnostics;
+pub mod encode;
+pub mod engine;
+pub mod error;
+pub mod leak;
+pub mod logging;
+pub mod metrics;
+pub mod mmcss;
+pub mod mux;
+pub mod record;
+pub mod rtmp;
+pub mod stats;
+pub mod sysmon;
+pub mod trace;
+pub mod transport;
+
+#[cfg(feature = "node")]
+mod node;
+#[cfg(feature = "node")]
+pub use node::*;
@@ -0,0 +1,97 @@
+//! Bridges `tracing` records to an app-provided sink.
+//!
+//! The NAPI layer installs a ThreadsafeFunction here so the Electron main
+//! process can collect engine logs instead of scraping stderr. The level
+//! filter can be adjusted on the live subscriber.
+
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Registry};
+
+use crate::error::{EngineError, EngineResult};
+
+/// One structured record forwarded to the sink.
+pub struct LogRecord {
+    /// "TRACE" through "ERROR".
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+type Sink = Box<dyn Fn(LogRecord) + Send + Sync>;
+
+static RELOAD_HANDLE: Mutex<Option<reload::Handle<LevelFilter, Registry>>> = Mutex::new(None);
+
+fn parse_level(level: &str) -> EngineResult<LevelFilter> {
+    level
+        .parse()
+        .map_err(|_| EngineError::Config(format!("unknown log level: {level}")))
+}
+
+/// Installs the global subscriber forwarding records at or above `level`
+/// ("off", "error", "warn", "info", "debug", "trace") to `sink`. Fails if a
+/// subscriber is already installed.
+pub fn init(sink: Sink, level: &str) -> EngineResult<()> {
+    let (filter, handle) = reload::Layer::new(parse_level(level)?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(SinkLayer { sink })
+        .try_init()
+        .map_err(|e| EngineError::Config(format!("install log subscriber: {e}")))?;
+    *RELOAD_HANDLE.lock().unwrap() = Some(handle);
+    Ok(())
+}
+
+/// Adjusts the level filter on the live subscriber.
+pub fn set_level(level: &str) -> EngineResult<()> {
+    let filter = parse_level(level)?;
+    let guard = RELOAD_HANDLE.lock().unwrap();
+    let handle = guard
+        .as_ref()
+        .ok_or_else(|| EngineError::Config("logging not initialized".into()))?;
+    handle
+        .reload(filter)
+        .map_err(|e| EngineError::Config(format!("reload log filter: {e}")))
+}
+
+struct SinkLayer {
+    sink: Sink,
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        (self.sink)(LogRecord {
+            level: event.metadata().level().to_string(),
+            target: event.metadata().target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Collects the `message` field verbatim and appends the remaining fields
+/// as `key=value` pairs.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            if !self.message.is_empty() {
+                self.message.push(' ');
+            }
+            let _ = write!(self.message, "{}={:?}", field.name(), value);
+        }
+    }
+}
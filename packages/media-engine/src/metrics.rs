@@ -0,0 +1,162 @@
+//! Prometheus-compatible metrics export. Renders the engine's rolling
+//! [`EngineStats`] into text exposition format, either pulled through the
+//! NAPI surface or scraped from an optional local HTTP exporter — so a
+//! self-hosted deployment can graph engine health next to its server
+//! metrics.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::{EngineError, EngineResult};
+use crate::stats::{EngineStats, SharedStats};
+
+/// Renders a stats snapshot as Prometheus text exposition format. Counter
+/// totals come straight from the session counters; latency and encode
+/// time surface as summary-style quantile gauges over the engine's
+/// rolling five-second window.
+pub fn render(stats: &EngineStats) -> String {
+    let mut out = String::new();
+    let mut counter = |name: &str, help: &str, value: u64| {
+        out.push_str(&format!(
+            "# HELP migo_engine_{name} {help}\n# TYPE migo_engine_{name} counter\nmigo_engine_{name} {value}\n"
+        ));
+    };
+    counter(
+        "frames_captured_total",
+        "Frames received from the capture source.",
+        stats.frames_captured,
+    );
+    counter(
+        "frames_encoded_total",
+        "Frames the encoder produced output for.",
+        stats.frames_encoded,
+    );
+    counter(
+        "frames_dropped_total",
+        "Captured frames dropped because the encoder was behind.",
+        stats.frames_dropped,
+    );
+    counter(
+        "keyframes_encoded_total",
+        "Keyframes produced by the primary encoder.",
+        stats.keyframes_encoded,
+    );
+    counter(
+        "bytes_sent_total",
+        "Encoded payload bytes handed to the transport.",
+        stats.bytes_sent,
+    );
+    counter(
+        "packets_sent_total",
+        "RTP packets written to the media socket.",
+        stats.packets_sent,
+    );
+
+    let mut gauge = |name: &str, help: &str, value: f64| {
+        out.push_str(&format!(
+            "# HELP migo_engine_{name} {help}\n# TYPE migo_engine_{name} gauge\nmigo_engine_{name} {value}\n"
+        ));
+    };
+    gauge(
+        "fps",
+        "Encoded frames per second over the last stats interval.",
+        stats.fps,
+    );
+    gauge(
+        "bitrate_kbps",
+        "Outgoing bitrate over the last stats interval.",
+        stats.bitrate_kbps,
+    );
+    gauge(
+        "cpu_percent",
+        "Process CPU usage as a percentage of all logical cores.",
+        stats.cpu_percent,
+    );
+    gauge(
+        "gpu_percent",
+        "GPU engine utilization attributed to this process, in percent.",
+        stats.gpu_percent,
+    );
+    gauge(
+        "gpu_memory_mb",
+        "Dedicated video memory in use by this process, in megabytes.",
+        stats.gpu_memory_mb,
+    );
+    gauge(
+        "encoder_hardware",
+        "1 when a hardware MFT is encoding, 0 on the CPU fallback.",
+        if stats.encoder_hardware { 1.0 } else { 0.0 },
+    );
+
+    let mut summary = |name: &str, help: &str, quantiles: &[(f64, f64)]| {
+        out.push_str(&format!(
+            "# HELP migo_engine_{name} {help}\n# TYPE migo_engine_{name} summary\n"
+        ));
+        for &(q, value) in quantiles {
+            out.push_str(&format!(
+                "migo_engine_{name}{{quantile=\"{q}\"}} {value}\n"
+            ));
+        }
+    };
+    summary(
+        "encode_ms",
+        "Encode time per frame over the rolling window, in milliseconds.",
+        &[
+            (0.5, stats.p50_encode_ms),
+            (0.95, stats.p95_encode_ms),
+            (0.99, stats.p99_encode_ms),
+        ],
+    );
+    summary(
+        "latency_ms",
+        "Capture-to-send latency over the rolling window, in milliseconds.",
+        &[(0.95, stats.p95_latency_ms)],
+    );
+    out
+}
+
+/// Serves `GET /metrics` on `127.0.0.1:port` until `stop` is set.
+/// Loopback only: the exporter carries no authentication, and a scraper
+/// on the same box (or an SSH tunnel) is the operator story here.
+pub fn exporter_thread(port: u16, stats: SharedStats, stop: &AtomicBool) -> EngineResult<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| EngineError::Config(format!("metrics exporter bind 127.0.0.1:{port}: {e}")))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| EngineError::Config(format!("metrics exporter: {e}")))?;
+    tracing::info!("metrics exporter listening on 127.0.0.1:{port}");
+
+    while !stop.load(Ordering::SeqCst) {
+        let (mut socket, _) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("metrics exporter accept: {e}");
+                continue;
+            }
+        };
+        let _ = socket.set_read_timeout(Some(Duration::from_secs(2)));
+        // Drain the request line + headers; the path doesn't matter, every
+        // response is the metrics page.
+        let mut request = [0u8; 1024];
+        let _ = socket.read(&mut request);
+        let body = {
+            let mut snapshot = stats.lock().unwrap().clone();
+            snapshot.compute_percentiles();
+            render(&snapshot)
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        if let Err(e) = socket.write_all(response.as_bytes()) {
+            tracing::debug!("metrics exporter write: {e}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Multimedia Class Scheduler (MMCSS) registration for the realtime
+//! worker threads. Registering with the "Capture" or "Playback" class
+//! tells the Windows scheduler these threads have a frame deadline, so a
+//! busy Electron renderer or a background compile doesn't starve the
+//! 16 ms encode budget. Registration is best-effort: failure logs a
+//! warning and the thread runs at normal priority.
+
+use crate::config::MmcssConfig;
+
+/// Registers the current thread with MMCSS under `task` ("Capture",
+/// "Playback", …) at the configured priority. Keep the returned guard
+/// alive for the thread's lifetime; dropping it reverts the registration.
+/// Returns a no-op guard when disabled, on failure, or off Windows.
+pub fn register(task: &str, config: &MmcssConfig) -> Registration {
+    if !config.enabled {
+        return Registration::none();
+    }
+    imp::register(task, config)
+}
+
+/// Scope guard for one thread's MMCSS registration.
+pub struct Registration {
+    #[cfg(windows)]
+    handle: Option<windows::Win32::Foundation::HANDLE>,
+}
+
+impl Registration {
+    fn none() -> Self {
+        Self {
+            #[cfg(windows)]
+            handle: None,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::Registration;
+    use crate::config::{MmcssConfig, MmcssPriority};
+
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Threading::{
+        AvRevertMmThreadCharacteristics, AvSetMmThreadCharacteristicsW, AvSetMmThreadPriority,
+        AVRT_PRIORITY_CRITICAL, AVRT_PRIORITY_HIGH, AVRT_PRIORITY_LOW, AVRT_PRIORITY_NORMAL,
+    };
+
+    pub(super) fn register(task: &str, config: &MmcssConfig) -> Registration {
+        let wide: Vec<u16> = task.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut task_index = 0u32;
+        let handle = match unsafe {
+            AvSetMmThreadCharacteristicsW(PCWSTR::from_raw(wide.as_ptr()), &mut task_index)
+        } {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::warn!("mmcss: register {task}: {e}");
+                return Registration::none();
+            }
+        };
+        let priority = match config.priority {
+            MmcssPriority::Low => AVRT_PRIORITY_LOW,
+            MmcssPriority::Normal => AVRT_PRIORITY_NORMAL,
+            MmcssPriority::High => AVRT_PRIORITY_HIGH,
+            MmcssPriority::Critical => AVRT_PRIORITY_CRITICAL,
+        };
+        if let Err(e) = unsafe { AvSetMmThreadPriority(handle, priority) } {
+            tracing::warn!("mmcss: set priority for {task}: {e}");
+        }
+        tracing::debug!("mmcss: thread registered in class {task}");
+        Registration {
+            handle: Some(handle),
+        }
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            if let Some(handle) = self.handle.take() {
+                let _ = unsafe { AvRevertMmThreadCharacteristics(handle) };
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use super::Registration;
+    use crate::config::MmcssConfig;
+
+    /// MMCSS doesn't exist off Windows; threads run unregistered.
+    pub(super) fn register(_task: &str, _config: &MmcssConfig) -> Registration {
+        Registration::none()
+    }
+}
@@ -0,0 +1,348 @@
+//! Fragmented MP4 segmenting for HLS: an init segment, `.m4s` media
+//! segments cut at keyframes, and an EVENT playlist rewritten as segments
+//! land. Serving the output directory over plain HTTP gives "share to a
+//! link" viewing in any browser (hls.js, Safari natively) with no WebRTC
+//! involved.
+//!
+//! Video only: Opus-in-fMP4 is not part of the HLS spec and Safari
+//! rejects it, and the engine ships no AAC encoder.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bitstream::h264::annex_b_units;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+use crate::mux::mp4::{build_box, video_sample_entry, IDENTITY_MATRIX, VIDEO_TIMESCALE};
+
+/// trun sample flags: sync sample (depends on nothing).
+const FLAGS_KEYFRAME: u32 = 0x0200_0000;
+/// trun sample flags: non-sync, depends on others.
+const FLAGS_DELTA: u32 = 0x0101_0000;
+
+/// One buffered sample: presentation time (90 kHz), keyframe, AVCC bytes.
+struct Pending {
+    time: u64,
+    keyframe: bool,
+    data: Vec<u8>,
+}
+
+/// Cuts the live encode into fMP4 segments under a directory. Segments
+/// close at the first keyframe past the target duration, so the encoder's
+/// keyframe interval bounds segment length.
+pub struct HlsSegmenter {
+    dir: PathBuf,
+    /// Target segment length in 90 kHz units.
+    target: u64,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    init_written: bool,
+    anchor_qpc: Option<i64>,
+    pending: Vec<Pending>,
+    /// Next segment's sequence number; also counts published segments.
+    sequence: u32,
+    /// Published segment durations in seconds, for the playlist.
+    durations: Vec<f64>,
+}
+
+impl HlsSegmenter {
+    pub fn create(dir: &Path, target_seconds: u32) -> EngineResult<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|e| EngineError::Config(format!("cannot create {}: {e}", dir.display())))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            target: target_seconds.max(1) as u64 * VIDEO_TIMESCALE as u64,
+            sps: None,
+            pps: None,
+            init_written: false,
+            anchor_qpc: None,
+            pending: Vec::new(),
+            sequence: 0,
+            durations: Vec::new(),
+        })
+    }
+
+    /// Appends one encoded access unit. The init segment goes out once
+    /// SPS/PPS have been seen; media segments follow at keyframes.
+    pub fn write_video(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        let anchor = *self.anchor_qpc.get_or_insert(frame.capture_qpc);
+        let elapsed = (frame.capture_qpc - anchor).max(0) as u64;
+        let time = elapsed * (VIDEO_TIMESCALE as u64) / 10_000_000;
+
+        let mut data = Vec::with_capacity(frame.data.len());
+        for nal in annex_b_units(&frame.data) {
+            match nal[0] & 0x1f {
+                7 => self.sps = Some(nal.to_vec()),
+                8 => self.pps = Some(nal.to_vec()),
+                9 => {}
+                _ => {
+                    data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    data.extend_from_slice(nal);
+                }
+            }
+        }
+        if !self.init_written {
+            let (Some(sps), Some(pps)) = (self.sps.clone(), self.pps.clone()) else {
+                return Ok(());
+            };
+            self.write_init(&sps, &pps, frame.width, frame.height)?;
+            self.init_written = true;
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        // Cut at keyframes only, so every segment decodes standalone.
+        if frame.is_keyframe
+            && !self.pending.is_empty()
+            && time.saturating_sub(self.pending[0].time) >= self.target
+        {
+            self.flush_segment()?;
+        }
+        self.pending.push(Pending {
+            time,
+            keyframe: frame.is_keyframe,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Publishes the buffered samples and marks the playlist ended.
+    /// Returns the number of segments written.
+    pub fn finish(mut self) -> EngineResult<u64> {
+        if !self.pending.is_empty() {
+            self.flush_segment()?;
+        }
+        if self.sequence > 0 {
+            self.write_playlist(true)?;
+        }
+        Ok(self.sequence as u64)
+    }
+
+    fn write_init(&self, sps: &[u8], pps: &[u8], width: u32, height: u32) -> EngineResult<()> {
+        let ftyp = build_box(
+            b"ftyp",
+            &[&b"iso6"[..], &0x200u32.to_be_bytes(), b"iso6", b"mp41"],
+        );
+
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0; 4]);
+        mvhd.extend_from_slice(&[0; 8]);
+        mvhd.extend_from_slice(&VIDEO_TIMESCALE.to_be_bytes());
+        mvhd.extend_from_slice(&0u32.to_be_bytes()); // duration: live
+        mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        mvhd.extend_from_slice(&0x0100u16.to_be_bytes());
+        mvhd.extend_from_slice(&[0; 10]);
+        mvhd.extend_from_slice(&IDENTITY_MATRIX);
+        mvhd.extend_from_slice(&[0; 24]);
+        mvhd.extend_from_slice(&2u32.to_be_bytes()); // next track id
+
+        let mut tkhd = Vec::new();
+        tkhd.extend_from_slice(&[0, 0, 0, 3]);
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&1u32.to_be_bytes()); // track id
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&0u32.to_be_bytes()); // duration: live
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&[0; 4]); // volume + reserved
+        tkhd.extend_from_slice(&IDENTITY_MATRIX);
+        tkhd.extend_from_slice(&(width << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(height << 16).to_be_bytes());
+
+        let mut mdhd = Vec::new();
+        mdhd.extend_from_slice(&[0; 4]);
+        mdhd.extend_from_slice(&[0; 8]);
+        mdhd.extend_from_slice(&VIDEO_TIMESCALE.to_be_bytes());
+        mdhd.extend_from_slice(&0u32.to_be_bytes());
+        mdhd.extend_from_slice(&0x55c4u16.to_be_bytes());
+        mdhd.extend_from_slice(&[0; 2]);
+
+        let mut hdlr = Vec::new();
+        hdlr.extend_from_slice(&[0; 8]);
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(b"VideoHandler\0");
+
+        let mut vmhd = Vec::new();
+        vmhd.extend_from_slice(&[0, 0, 0, 1]);
+        vmhd.extend_from_slice(&[0; 8]);
+        let dref_url = build_box(b"url ", &[&[0u8, 0, 0, 1]]);
+        let mut dref = Vec::new();
+        dref.extend_from_slice(&[0; 4]);
+        dref.extend_from_slice(&1u32.to_be_bytes());
+        dref.extend_from_slice(&dref_url);
+        let dinf = build_box(b"dinf", &[&build_box(b"dref", &[&dref])]);
+
+        // Empty sample tables: all samples arrive in fragments.
+        let mut stsd = Vec::new();
+        stsd.extend_from_slice(&[0; 4]);
+        stsd.extend_from_slice(&1u32.to_be_bytes());
+        stsd.extend_from_slice(&video_sample_entry(width, height, sps, pps));
+        let empty = [0u8; 8]; // version/flags + zero entry count
+        let stbl = build_box(
+            b"stbl",
+            &[
+                &build_box(b"stsd", &[&stsd]),
+                &build_box(b"stts", &[&empty]),
+                &build_box(b"stsc", &[&empty]),
+                &build_box(b"stsz", &[&[0u8; 12]]),
+                &build_box(b"stco", &[&empty]),
+            ],
+        );
+        let minf = build_box(b"minf", &[&build_box(b"vmhd", &[&vmhd]), &dinf, &stbl]);
+        let mdia = build_box(
+            b"mdia",
+            &[
+                &build_box(b"mdhd", &[&mdhd]),
+                &build_box(b"hdlr", &[&hdlr]),
+                &minf,
+            ],
+        );
+        let trak = build_box(b"trak", &[&build_box(b"tkhd", &[&tkhd]), &mdia]);
+
+        // trex: defaults for track 1 (overridden per sample in each trun).
+        let mut trex = Vec::new();
+        trex.extend_from_slice(&[0; 4]);
+        trex.extend_from_slice(&1u32.to_be_bytes()); // track id
+        trex.extend_from_slice(&1u32.to_be_bytes()); // sample description
+        trex.extend_from_slice(&[0; 12]); // duration/size/flags
+        let mvex = build_box(b"mvex", &[&build_box(b"trex", &[&trex])]);
+
+        let moov = build_box(b"moov", &[&build_box(b"mvhd", &[&mvhd]), &trak, &mvex]);
+        let mut init = ftyp;
+        init.extend_from_slice(&moov);
+        self.write_file("init.mp4", &init)
+    }
+
+    fn flush_segment(&mut self) -> EngineResult<()> {
+        let samples = std::mem::take(&mut self.pending);
+        let base_time = samples[0].time;
+
+        // Per-sample durations from the time deltas; the trailing sample
+        // repeats the previous delta, matching the plain MP4 muxer.
+        let mut durations: Vec<u32> = Vec::with_capacity(samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            let delta = match samples.get(i + 1) {
+                Some(next) => (next.time - sample.time).max(1) as u32,
+                None => durations.last().copied().unwrap_or(VIDEO_TIMESCALE / 30),
+            };
+            durations.push(delta);
+        }
+        let segment_ticks: u64 = durations.iter().map(|&d| d as u64).sum();
+
+        // The trun's data offset points past moof into mdat; trun size is
+        // fixed by the sample count, so build once with zero and rebuild
+        // with the real offset (same two-pass trick as the moov builder).
+        let probe = build_moof(self.sequence, base_time, &samples, &durations, 0);
+        let moof = build_moof(
+            self.sequence,
+            base_time,
+            &samples,
+            &durations,
+            probe.len() as u32 + 8,
+        );
+        debug_assert_eq!(probe.len(), moof.len());
+
+        let styp = build_box(
+            b"styp",
+            &[&b"msdh"[..], &0u32.to_be_bytes(), b"msdh", b"msix"],
+        );
+        let mdat_len: usize = samples.iter().map(|s| s.data.len()).sum();
+        let mut segment = Vec::with_capacity(styp.len() + moof.len() + mdat_len + 8);
+        segment.extend_from_slice(&styp);
+        segment.extend_from_slice(&moof);
+        segment.extend_from_slice(&(mdat_len as u32 + 8).to_be_bytes());
+        segment.extend_from_slice(b"mdat");
+        for sample in &samples {
+            segment.extend_from_slice(&sample.data);
+        }
+
+        let name = format!("seg{}.m4s", self.sequence);
+        self.write_file(&name, &segment)?;
+        self.durations
+            .push(segment_ticks as f64 / VIDEO_TIMESCALE as f64);
+        self.sequence += 1;
+        self.write_playlist(false)
+    }
+
+    /// Rewrites the playlist via a rename so HTTP servers never see a
+    /// half-written file.
+    fn write_playlist(&self, ended: bool) -> EngineResult<()> {
+        let target = self
+            .durations
+            .iter()
+            .fold(1f64, |max, &d| max.max(d))
+            .ceil() as u64;
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        playlist.push_str("#EXT-X-VERSION:7\n");
+        playlist.push_str(&format!("#EXT-X-TARGETDURATION:{target}\n"));
+        playlist.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+        // EVENT: segments are never removed, so late joiners can seek back.
+        playlist.push_str("#EXT-X-PLAYLIST-TYPE:EVENT\n");
+        playlist.push_str("#EXT-X-MAP:URI=\"init.mp4\"\n");
+        for (i, duration) in self.durations.iter().enumerate() {
+            playlist.push_str(&format!("#EXTINF:{duration:.3},\nseg{i}.m4s\n"));
+        }
+        if ended {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+        let tmp = self.dir.join("stream.m3u8.tmp");
+        fs::write(&tmp, playlist)
+            .map_err(|e| EngineError::Encode(format!("hls playlist: {e}")))?;
+        fs::rename(&tmp, self.dir.join("stream.m3u8"))
+            .map_err(|e| EngineError::Encode(format!("hls playlist: {e}")))
+    }
+
+    fn write_file(&self, name: &str, data: &[u8]) -> EngineResult<()> {
+        fs::write(self.dir.join(name), data)
+            .map_err(|e| EngineError::Encode(format!("hls segment {name}: {e}")))
+    }
+}
+
+fn build_moof(
+    sequence: u32,
+    base_time: u64,
+    samples: &[Pending],
+    durations: &[u32],
+    data_offset: u32,
+) -> Vec<u8> {
+    let mut mfhd = Vec::new();
+    mfhd.extend_from_slice(&[0; 4]);
+    mfhd.extend_from_slice(&(sequence + 1).to_be_bytes());
+
+    // default-base-is-moof: offsets count from the moof's first byte.
+    let mut tfhd = Vec::new();
+    tfhd.extend_from_slice(&0x0002_0000u32.to_be_bytes());
+    tfhd.extend_from_slice(&1u32.to_be_bytes()); // track id
+
+    let mut tfdt = Vec::new();
+    tfdt.extend_from_slice(&[1, 0, 0, 0]); // version 1: 64-bit time
+    tfdt.extend_from_slice(&base_time.to_be_bytes());
+
+    // data-offset + per-sample duration, size, and flags.
+    let mut trun = Vec::new();
+    trun.extend_from_slice(&0x0000_0701u32.to_be_bytes());
+    trun.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    trun.extend_from_slice(&data_offset.to_be_bytes());
+    for (sample, &duration) in samples.iter().zip(durations) {
+        trun.extend_from_slice(&duration.to_be_bytes());
+        trun.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+        let flags = if sample.keyframe {
+            FLAGS_KEYFRAME
+        } else {
+            FLAGS_DELTA
+        };
+        trun.extend_from_slice(&flags.to_be_bytes());
+    }
+
+    let traf = build_box(
+        b"traf",
+        &[
+            &build_box(b"tfhd", &[&tfhd]),
+            &build_box(b"tfdt", &[&tfdt]),
+            &build_box(b"trun", &[&trun]),
+        ],
+    );
+    build_box(b"moof", &[&build_box(b"mfhd", &[&mfhd]), &traf])
+}
@@ -0,0 +1,336 @@
+//! Matroska muxing: H.264 SimpleBlocks plus optional Opus audio, chosen
+//! for long recordings. The segment is written with an unknown size and
+//! clusters land on disk as they close, so a crash mid-recording loses at
+//! most the current cluster instead of the whole file (an MP4 without its
+//! moov is unreadable).
+//!
+//! WebM is deliberately not offered: the WebM subset forbids H.264, and a
+//! `.webm` file carrying it would be rejected by exactly the players that
+//! care about the extension.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::audio::AudioPacket;
+use crate::bitstream::h264::annex_b_units;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+
+/// Cluster span ceiling. SimpleBlock timestamps are 16-bit milliseconds
+/// relative to the cluster, so clusters must stay well under 32 s; five
+/// seconds also bounds what a crash can lose.
+const MAX_CLUSTER_MS: u64 = 5_000;
+
+// Element ids, from the Matroska spec.
+const EBML: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+/// Writes one `.mkv` file. Headers go out once the first keyframe has
+/// delivered SPS/PPS; blocks then stream cluster by cluster.
+pub struct MkvWriter {
+    out: BufWriter<File>,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    headers_written: bool,
+    /// File offset of the Duration float's payload, patched on finish.
+    duration_offset: u64,
+    /// Current cluster, buffered until it closes: (start ms, elements).
+    cluster: Option<(u64, Vec<u8>)>,
+    anchor_qpc: Option<i64>,
+    last_ms: u64,
+    video_blocks: u64,
+    has_audio: bool,
+    /// Whether the headers actually declared an audio TrackEntry — false
+    /// when the first packet lost the race against the first keyframe.
+    audio_declared: bool,
+}
+
+impl MkvWriter {
+    pub fn create(path: &Path) -> EngineResult<Self> {
+        let file = File::create(path)
+            .map_err(|e| EngineError::Config(format!("cannot create {}: {e}", path.display())))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+            sps: None,
+            pps: None,
+            headers_written: false,
+            duration_offset: 0,
+            cluster: None,
+            anchor_qpc: None,
+            last_ms: 0,
+            video_blocks: 0,
+            has_audio: false,
+            audio_declared: false,
+        })
+    }
+
+    /// Appends one encoded access unit as a SimpleBlock (AVCC layout, as
+    /// in MP4 — the `avcC` lives in CodecPrivate).
+    pub fn write_video(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        let anchor = *self.anchor_qpc.get_or_insert(frame.capture_qpc);
+        let ms = ((frame.capture_qpc - anchor).max(0) / 10_000) as u64;
+
+        let mut data = Vec::with_capacity(frame.data.len());
+        for nal in annex_b_units(&frame.data) {
+            match nal[0] & 0x1f {
+                7 => self.sps = Some(nal.to_vec()),
+                8 => self.pps = Some(nal.to_vec()),
+                9 => {}
+                _ => {
+                    data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    data.extend_from_slice(nal);
+                }
+            }
+        }
+        if !self.headers_written {
+            let (Some(sps), Some(pps)) = (self.sps.clone(), self.pps.clone()) else {
+                // Nothing decodable can precede the parameter sets.
+                return Ok(());
+            };
+            self.write_headers(&sps, &pps, frame.width, frame.height)?;
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.write_block(VIDEO_TRACK, ms, frame.is_keyframe, &data)?;
+        self.video_blocks += 1;
+        Ok(())
+    }
+
+    /// Appends one Opus packet. Timestamps derive from `sample_offset`
+    /// (48 kHz samples); the first packet also switches the track headers
+    /// to include audio, so it must arrive before the first video frame —
+    /// callers that record audio should call this from the start. Audio
+    /// that only shows up after the headers went out is dropped: blocks
+    /// for a track the headers never declared make the file spec-invalid.
+    pub fn write_audio(&mut self, packet: &AudioPacket) -> EngineResult<()> {
+        self.has_audio = true;
+        if !self.headers_written {
+            // Headers wait for the video parameter sets; buffering audio
+            // until then isn't worth it for a leading few milliseconds.
+            return Ok(());
+        }
+        if !self.audio_declared {
+            return Ok(());
+        }
+        let ms = packet.sample_offset / 48;
+        self.write_block(AUDIO_TRACK, ms, true, &packet.data)
+    }
+
+    fn write_headers(
+        &mut self,
+        sps: &[u8],
+        pps: &[u8],
+        width: u32,
+        height: u32,
+    ) -> EngineResult<()> {
+        let mut ebml = Vec::new();
+        // Version/read-version 1, max id length 4, max size length 8.
+        element(&mut ebml, 0x4286, &[1]);
+        element(&mut ebml, 0x42F7, &[1]);
+        element(&mut ebml, 0x42F2, &[4]);
+        element(&mut ebml, 0x42F3, &[8]);
+        element(&mut ebml, 0x4282, b"matroska");
+        element(&mut ebml, 0x4287, &[4]); // doctype version
+        element(&mut ebml, 0x4285, &[2]); // doctype read version
+        let mut header = Vec::new();
+        element(&mut header, EBML, &ebml);
+
+        // Segment with unknown size: the all-ones length means "until end
+        // of file", which is what makes truncated files playable.
+        write_id(&mut header, SEGMENT);
+        header.extend_from_slice(&[0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        self.write(&header)?;
+
+        let mut info = Vec::new();
+        element(&mut info, TIMESTAMP_SCALE, &1_000_000u32.to_be_bytes()); // 1 ms
+        element(&mut info, MUXING_APP, b"migo media-engine");
+        element(&mut info, WRITING_APP, b"migo media-engine");
+        // Duration placeholder, patched in finish(). Record where its
+        // 8-byte float payload lands: current position + the Info id/size
+        // header + the payload so far + the Duration id (2) and size (1).
+        let before_duration = info.len();
+        element(&mut info, DURATION, &0f64.to_be_bytes());
+        let mut wrapped = Vec::new();
+        element(&mut wrapped, INFO, &info);
+        let info_header_len = wrapped.len() - info.len();
+        self.duration_offset = self.position()? + (info_header_len + before_duration + 3) as u64;
+        self.write(&wrapped)?;
+
+        let mut avcc = Vec::new();
+        avcc.push(1);
+        avcc.extend_from_slice(&sps[1..4]);
+        avcc.push(0xff);
+        avcc.push(0xe1);
+        avcc.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(sps);
+        avcc.push(1);
+        avcc.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(pps);
+
+        let mut video = Vec::new();
+        element(&mut video, PIXEL_WIDTH, &width.to_be_bytes()[2..]);
+        element(&mut video, PIXEL_HEIGHT, &height.to_be_bytes()[2..]);
+        let mut video_entry = Vec::new();
+        element(&mut video_entry, TRACK_NUMBER, &[VIDEO_TRACK]);
+        element(&mut video_entry, TRACK_UID, &[VIDEO_TRACK]);
+        element(&mut video_entry, TRACK_TYPE, &[1]);
+        element(&mut video_entry, CODEC_ID, b"V_MPEG4/ISO/AVC");
+        element(&mut video_entry, CODEC_PRIVATE, &avcc);
+        element(&mut video_entry, VIDEO, &video);
+
+        let mut tracks = Vec::new();
+        element(&mut tracks, TRACK_ENTRY, &video_entry);
+        if self.has_audio {
+            // OpusHead, as in Ogg encapsulation (little-endian fields).
+            let mut opus_head = Vec::new();
+            opus_head.extend_from_slice(b"OpusHead");
+            opus_head.push(1); // version
+            opus_head.push(2); // channels
+            opus_head.extend_from_slice(&3840u16.to_le_bytes()); // pre-skip
+            opus_head.extend_from_slice(&48_000u32.to_le_bytes());
+            opus_head.extend_from_slice(&0i16.to_le_bytes()); // gain
+            opus_head.push(0); // mapping family
+
+            let mut audio = Vec::new();
+            element(&mut audio, SAMPLING_FREQUENCY, &48_000f64.to_be_bytes());
+            element(&mut audio, CHANNELS, &[2]);
+            let mut audio_entry = Vec::new();
+            element(&mut audio_entry, TRACK_NUMBER, &[AUDIO_TRACK]);
+            element(&mut audio_entry, TRACK_UID, &[AUDIO_TRACK]);
+            element(&mut audio_entry, TRACK_TYPE, &[2]);
+            element(&mut audio_entry, CODEC_ID, b"A_OPUS");
+            element(&mut audio_entry, CODEC_PRIVATE, &opus_head);
+            element(&mut audio_entry, AUDIO, &audio);
+            element(&mut tracks, TRACK_ENTRY, &audio_entry);
+            self.audio_declared = true;
+        }
+        let mut wrapped = Vec::new();
+        element(&mut wrapped, TRACKS, &tracks);
+        self.write(&wrapped)?;
+        self.headers_written = true;
+        Ok(())
+    }
+
+    fn write_block(&mut self, track: u8, ms: u64, keyframe: bool, data: &[u8]) -> EngineResult<()> {
+        self.last_ms = self.last_ms.max(ms);
+        // Close the cluster at a video keyframe past the span ceiling, so
+        // every cluster starts decodable.
+        let rollover = match &self.cluster {
+            Some((start, _)) => {
+                track == VIDEO_TRACK && keyframe && ms.saturating_sub(*start) >= MAX_CLUSTER_MS
+            }
+            None => false,
+        };
+        if rollover {
+            self.close_cluster()?;
+        }
+        let (start, elements) = self.cluster.get_or_insert_with(|| {
+            let mut elements = Vec::new();
+            element(&mut elements, TIMESTAMP, &ms.to_be_bytes());
+            (ms, elements)
+        });
+        let relative = ms.saturating_sub(*start).min(i16::MAX as u64) as i16;
+        let mut block = Vec::with_capacity(data.len() + 4);
+        block.push(0x80 | track); // track number as a 1-byte vint
+        block.extend_from_slice(&relative.to_be_bytes());
+        block.push(if keyframe { 0x80 } else { 0x00 });
+        block.extend_from_slice(data);
+        element(elements, SIMPLE_BLOCK, &block);
+        Ok(())
+    }
+
+    fn close_cluster(&mut self) -> EngineResult<()> {
+        if let Some((_, elements)) = self.cluster.take() {
+            let mut cluster = Vec::new();
+            element(&mut cluster, CLUSTER, &elements);
+            self.write(&cluster)?;
+            self.out
+                .flush()
+                .map_err(|e| EngineError::Encode(format!("mkv flush: {e}")))?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the last cluster and patches the duration. Returns the
+    /// number of video blocks written.
+    pub fn finish(mut self) -> EngineResult<u64> {
+        self.close_cluster()?;
+        self.out
+            .flush()
+            .map_err(|e| EngineError::Encode(format!("mkv flush: {e}")))?;
+        if self.duration_offset != 0 {
+            let mut file = self
+                .out
+                .into_inner()
+                .map_err(|e| EngineError::Encode(format!("mkv flush: {e}")))?;
+            file.seek(SeekFrom::Start(self.duration_offset))
+                .map_err(|e| EngineError::Encode(format!("mkv seek: {e}")))?;
+            file.write_all(&(self.last_ms as f64).to_be_bytes())
+                .map_err(|e| EngineError::Encode(format!("mkv duration: {e}")))?;
+        }
+        Ok(self.video_blocks)
+    }
+
+    fn write(&mut self, data: &[u8]) -> EngineResult<()> {
+        self.out
+            .write_all(data)
+            .map_err(|e| EngineError::Encode(format!("mkv write: {e}")))
+    }
+
+    fn position(&mut self) -> EngineResult<u64> {
+        self.out
+            .stream_position()
+            .map_err(|e| EngineError::Encode(format!("mkv position: {e}")))
+    }
+}
+
+/// Writes `id` (big-endian, marker bits included) followed by the payload
+/// size as a vint and the payload itself.
+fn element(out: &mut Vec<u8>, id: u32, payload: &[u8]) {
+    write_id(out, id);
+    write_size(out, payload.len() as u64);
+    out.extend_from_slice(payload);
+}
+
+fn write_id(out: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+/// EBML vint: the marker bit in the first byte encodes the width.
+fn write_size(out: &mut Vec<u8>, size: u64) {
+    let mut width = 1;
+    while width < 8 && size >= (1u64 << (7 * width)) - 1 {
+        width += 1;
+    }
+    let bytes = size.to_be_bytes();
+    out.push((0x80 >> (width - 1)) | bytes[8 - width]);
+    out.extend_from_slice(&bytes[8 - width + 1..]);
+}
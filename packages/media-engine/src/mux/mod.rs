@@ -0,0 +1,7 @@
+//! Output containers for recorded streams. The recorder picks a muxer
+//! from the output file extension; bare Annex-B remains the fallback for
+//! `.h264`/unknown extensions.
+
+pub mod fmp4;
+pub mod mkv;
+pub mod mp4;
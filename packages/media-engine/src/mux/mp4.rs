@@ -0,0 +1,494 @@
+//! MP4 (ISO BMFF) muxing: H.264 in `avc1`/`avcC`, optional Opus audio in
+//! `Opus`/`dOps`, real capture-clock timestamps, and a moov atom placed
+//! before the media data so progressive playback starts immediately
+//! ("faststart" — no second remux pass needed).
+//!
+//! Samples stream to a `.tmp` sidecar next to the output while recording;
+//! `finish` writes `ftyp` + `moov` and then copies the media data behind
+//! them. AAC is not offered — the engine ships no AAC encoder, and every
+//! current player handles Opus-in-MP4.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::audio::AudioPacket;
+use crate::bitstream::h264::annex_b_units;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+
+/// Video track timescale: 90 kHz maps losslessly from the capture QPC
+/// (100 ns ticks) and is the conventional H.264 clock.
+pub(crate) const VIDEO_TIMESCALE: u32 = 90_000;
+/// Opus always runs at 48 kHz in this engine.
+const AUDIO_TIMESCALE: u32 = 48_000;
+/// Samples per 10 ms Opus packet, the frame size the audio loops encode.
+const OPUS_SAMPLES_PER_PACKET: u32 = 480;
+/// Movie-level timescale (mvhd/elst durations).
+const MOVIE_TIMESCALE: u32 = 1_000;
+
+/// One recorded sample's metadata; the payload lives in the sidecar file.
+struct Sample {
+    /// Byte offset within the media payload.
+    offset: u64,
+    size: u32,
+    /// Presentation time in the track timescale, from zero.
+    time: u64,
+    keyframe: bool,
+}
+
+/// Writes one MP4 file. Created by the recorder for `.mp4` output paths;
+/// also usable directly for custom capture tools.
+pub struct Mp4Writer {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    payload: BufWriter<File>,
+    payload_len: u64,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    width: u32,
+    height: u32,
+    video: Vec<Sample>,
+    audio: Vec<Sample>,
+    /// QPC of the first video frame; all video times are relative to it.
+    anchor_qpc: Option<i64>,
+}
+
+impl Mp4Writer {
+    pub fn create(path: &Path) -> EngineResult<Self> {
+        let tmp_path = path.with_extension("mp4.tmp");
+        let file = File::create(&tmp_path).map_err(|e| {
+            EngineError::Config(format!("cannot create {}: {e}", tmp_path.display()))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp_path,
+            payload: BufWriter::new(file),
+            payload_len: 0,
+            sps: None,
+            pps: None,
+            width: 0,
+            height: 0,
+            video: Vec::new(),
+            audio: Vec::new(),
+            anchor_qpc: None,
+        })
+    }
+
+    /// Appends one encoded access unit, converting Annex-B framing to the
+    /// length-prefixed AVCC layout MP4 requires. SPS/PPS move into the
+    /// `avcC` sample entry instead of the stream.
+    pub fn write_video(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        let anchor = *self.anchor_qpc.get_or_insert(frame.capture_qpc);
+        let elapsed = (frame.capture_qpc - anchor).max(0) as u64;
+        // 100 ns ticks → 90 kHz.
+        let time = elapsed * (VIDEO_TIMESCALE as u64) / 10_000_000;
+        self.width = frame.width;
+        self.height = frame.height;
+
+        let mut data = Vec::with_capacity(frame.data.len());
+        for nal in annex_b_units(&frame.data) {
+            match nal[0] & 0x1f {
+                7 => self.sps = Some(nal.to_vec()),
+                8 => self.pps = Some(nal.to_vec()),
+                9 => {}
+                _ => {
+                    data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    data.extend_from_slice(nal);
+                }
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let offset = self.append(&data)?;
+        self.video.push(Sample {
+            offset,
+            size: data.len() as u32,
+            time,
+            keyframe: frame.is_keyframe,
+        });
+        Ok(())
+    }
+
+    /// Appends one Opus packet. `sample_offset` (48 kHz samples from the
+    /// start of audio capture) is the presentation time directly.
+    pub fn write_audio(&mut self, packet: &AudioPacket) -> EngineResult<()> {
+        let offset = self.append(&packet.data)?;
+        self.audio.push(Sample {
+            offset,
+            size: packet.data.len() as u32,
+            time: packet.sample_offset,
+            keyframe: true,
+        });
+        Ok(())
+    }
+
+    fn append(&mut self, data: &[u8]) -> EngineResult<u64> {
+        let offset = self.payload_len;
+        self.payload
+            .write_all(data)
+            .map_err(|e| EngineError::Encode(format!("mp4 write: {e}")))?;
+        self.payload_len += data.len() as u64;
+        Ok(offset)
+    }
+
+    /// Finalizes the file: `ftyp`, `moov`, then the media data copied in
+    /// behind them. Returns the number of video samples written.
+    pub fn finish(mut self) -> EngineResult<u64> {
+        let (Some(sps), Some(pps)) = (self.sps.clone(), self.pps.clone()) else {
+            return Err(EngineError::Encode(
+                "mp4: no SPS/PPS seen — nothing decodable was recorded".into(),
+            ));
+        };
+        self.payload
+            .flush()
+            .map_err(|e| EngineError::Encode(format!("mp4 flush: {e}")))?;
+
+        let ftyp = build_box(
+            b"ftyp",
+            &[
+                &b"isom"[..],
+                &0x200u32.to_be_bytes(),
+                b"isom",
+                b"iso2",
+                b"avc1",
+                b"mp41",
+            ],
+        );
+        // The chunk offsets inside moov depend on moov's own size; co64
+        // entries are fixed-width, so building once with a zero base
+        // yields the final size and a second pass fills in real offsets.
+        let probe = self.build_moov(&sps, &pps, 0);
+        let base = (ftyp.len() + probe.len() + 8) as u64;
+        let moov = self.build_moov(&sps, &pps, base);
+        debug_assert_eq!(probe.len(), moov.len());
+
+        let file = File::create(&self.path)
+            .map_err(|e| EngineError::Config(format!("cannot create {}: {e}", self.path.display())))?;
+        let mut out = BufWriter::new(file);
+        let write = |out: &mut BufWriter<File>, data: &[u8]| {
+            out.write_all(data)
+                .map_err(|e| EngineError::Encode(format!("mp4 write: {e}")))
+        };
+        write(&mut out, &ftyp)?;
+        write(&mut out, &moov)?;
+        write(&mut out, &(self.payload_len + 8).to_be_bytes()[4..])?;
+        write(&mut out, b"mdat")?;
+        let mut tmp = File::open(&self.tmp_path)
+            .map_err(|e| EngineError::Encode(format!("mp4 sidecar: {e}")))?;
+        let mut buf = vec![0u8; 1 << 16];
+        loop {
+            let n = tmp
+                .read(&mut buf)
+                .map_err(|e| EngineError::Encode(format!("mp4 sidecar read: {e}")))?;
+            if n == 0 {
+                break;
+            }
+            write(&mut out, &buf[..n])?;
+        }
+        out.flush()
+            .map_err(|e| EngineError::Encode(format!("mp4 flush: {e}")))?;
+        drop(tmp);
+        let _ = std::fs::remove_file(&self.tmp_path);
+        Ok(self.video.len() as u64)
+    }
+
+    /// Track duration in its own timescale: last sample time plus one
+    /// trailing sample duration.
+    fn track_duration(samples: &[Sample], fallback_delta: u64) -> u64 {
+        match samples.len() {
+            0 => 0,
+            1 => fallback_delta,
+            n => {
+                let last = samples[n - 1].time;
+                let prev = samples[n - 2].time;
+                last + (last - prev).max(1)
+            }
+        }
+    }
+
+    fn build_moov(&self, sps: &[u8], pps: &[u8], base_offset: u64) -> Vec<u8> {
+        let video_duration = Self::track_duration(&self.video, VIDEO_TIMESCALE as u64 / 30);
+        let audio_duration =
+            Self::track_duration(&self.audio, OPUS_SAMPLES_PER_PACKET as u64);
+        let movie_duration = (video_duration * MOVIE_TIMESCALE as u64
+            / VIDEO_TIMESCALE as u64)
+            .max(audio_duration * MOVIE_TIMESCALE as u64 / AUDIO_TIMESCALE as u64);
+
+        let mut tracks = vec![self.build_trak(
+            1,
+            video_duration,
+            movie_duration,
+            base_offset,
+            &self.video,
+            &video_sample_entry(self.width, self.height, sps, pps),
+            true,
+        )];
+        if !self.audio.is_empty() {
+            tracks.push(self.build_trak(
+                2,
+                audio_duration,
+                movie_duration,
+                base_offset,
+                &self.audio,
+                &audio_sample_entry(),
+                false,
+            ));
+        }
+
+        let track_count = tracks.len() as u32;
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0; 4]); // version/flags
+        mvhd.extend_from_slice(&[0; 8]); // creation/modification time
+        mvhd.extend_from_slice(&MOVIE_TIMESCALE.to_be_bytes());
+        mvhd.extend_from_slice(&(movie_duration as u32).to_be_bytes());
+        mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+        mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+        mvhd.extend_from_slice(&[0; 10]); // reserved
+        mvhd.extend_from_slice(&IDENTITY_MATRIX);
+        mvhd.extend_from_slice(&[0; 24]); // predefined
+        mvhd.extend_from_slice(&(track_count + 1).to_be_bytes()); // next track id
+
+        let mut children = vec![build_box(b"mvhd", &[&mvhd])];
+        children.extend(tracks);
+        let refs: Vec<&[u8]> = children.iter().map(Vec::as_slice).collect();
+        build_box(b"moov", &refs)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_trak(
+        &self,
+        track_id: u32,
+        duration: u64,
+        movie_duration: u64,
+        base_offset: u64,
+        samples: &[Sample],
+        sample_entry: &[u8],
+        video: bool,
+    ) -> Vec<u8> {
+        let timescale = if video { VIDEO_TIMESCALE } else { AUDIO_TIMESCALE };
+
+        let mut tkhd = Vec::new();
+        tkhd.extend_from_slice(&[0, 0, 0, 3]); // version 0, enabled+in movie
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&track_id.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&(movie_duration as u32).to_be_bytes());
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&[0; 4]); // layer + alternate group
+        tkhd.extend_from_slice(&(if video { 0u16 } else { 0x0100 }).to_be_bytes()); // volume
+        tkhd.extend_from_slice(&[0; 2]);
+        tkhd.extend_from_slice(&IDENTITY_MATRIX);
+        let (w, h) = if video { (self.width, self.height) } else { (0, 0) };
+        tkhd.extend_from_slice(&(w << 16).to_be_bytes()); // 16.16 fixed
+        tkhd.extend_from_slice(&(h << 16).to_be_bytes());
+
+        // Edit list: the whole track plays from media time zero at rate
+        // 1.0. Explicit rather than implied, so players that honour edits
+        // and players that ignore them agree on the timeline.
+        let mut elst = Vec::new();
+        elst.extend_from_slice(&[0; 4]);
+        elst.extend_from_slice(&1u32.to_be_bytes());
+        elst.extend_from_slice(&(movie_duration as u32).to_be_bytes());
+        elst.extend_from_slice(&0u32.to_be_bytes()); // media_time
+        elst.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate
+        let edts = build_box(b"edts", &[&build_box(b"elst", &[&elst])]);
+
+        let mut mdhd = Vec::new();
+        mdhd.extend_from_slice(&[0; 4]);
+        mdhd.extend_from_slice(&[0; 8]);
+        mdhd.extend_from_slice(&timescale.to_be_bytes());
+        mdhd.extend_from_slice(&(duration as u32).to_be_bytes());
+        mdhd.extend_from_slice(&0x55c4u16.to_be_bytes()); // language "und"
+        mdhd.extend_from_slice(&[0; 2]);
+
+        let mut hdlr = Vec::new();
+        hdlr.extend_from_slice(&[0; 8]);
+        hdlr.extend_from_slice(if video { b"vide" } else { b"soun" });
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(if video { b"VideoHandler\0" } else { b"SoundHandler\0" });
+
+        let media_header = if video {
+            let mut vmhd = Vec::new();
+            vmhd.extend_from_slice(&[0, 0, 0, 1]); // flags=1 per spec
+            vmhd.extend_from_slice(&[0; 8]);
+            build_box(b"vmhd", &[&vmhd])
+        } else {
+            build_box(b"smhd", &[&[0u8; 8]])
+        };
+        let dref_url = build_box(b"url ", &[&[0u8, 0, 0, 1]]); // self-contained
+        let mut dref = Vec::new();
+        dref.extend_from_slice(&[0; 4]);
+        dref.extend_from_slice(&1u32.to_be_bytes());
+        dref.extend_from_slice(&dref_url);
+        let dinf = build_box(b"dinf", &[&build_box(b"dref", &[&dref])]);
+
+        let stbl = self.build_stbl(samples, sample_entry, base_offset, video, timescale);
+        let minf = build_box(b"minf", &[&media_header, &dinf, &stbl]);
+        let mdia = build_box(
+            b"mdia",
+            &[
+                &build_box(b"mdhd", &[&mdhd]),
+                &build_box(b"hdlr", &[&hdlr]),
+                &minf,
+            ],
+        );
+        build_box(b"trak", &[&build_box(b"tkhd", &[&tkhd]), &edts, &mdia])
+    }
+
+    fn build_stbl(
+        &self,
+        samples: &[Sample],
+        sample_entry: &[u8],
+        base_offset: u64,
+        video: bool,
+        timescale: u32,
+    ) -> Vec<u8> {
+        let mut stsd = Vec::new();
+        stsd.extend_from_slice(&[0; 4]);
+        stsd.extend_from_slice(&1u32.to_be_bytes());
+        stsd.extend_from_slice(sample_entry);
+
+        // stts: run-length encoded sample durations, derived from the
+        // stored presentation times.
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (i, sample) in samples.iter().enumerate() {
+            let delta = match samples.get(i + 1) {
+                Some(next) => (next.time - sample.time).max(1) as u32,
+                // Trailing sample: repeat the previous delta.
+                None => runs.last().map(|&(_, d)| d).unwrap_or(timescale / 30),
+            };
+            match runs.last_mut() {
+                Some(run) if run.1 == delta => run.0 += 1,
+                _ => runs.push((1, delta)),
+            }
+        }
+        let mut stts = Vec::new();
+        stts.extend_from_slice(&[0; 4]);
+        stts.extend_from_slice(&(runs.len() as u32).to_be_bytes());
+        for (count, delta) in &runs {
+            stts.extend_from_slice(&count.to_be_bytes());
+            stts.extend_from_slice(&delta.to_be_bytes());
+        }
+
+        // One chunk per sample keeps stsc trivial and the offsets exact.
+        let mut stsc = Vec::new();
+        stsc.extend_from_slice(&[0; 4]);
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+        stsc.extend_from_slice(&1u32.to_be_bytes()); // first chunk
+        stsc.extend_from_slice(&1u32.to_be_bytes()); // samples per chunk
+        stsc.extend_from_slice(&1u32.to_be_bytes()); // sample description
+
+        let mut stsz = Vec::new();
+        stsz.extend_from_slice(&[0; 4]);
+        stsz.extend_from_slice(&0u32.to_be_bytes()); // per-sample sizes follow
+        stsz.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        for sample in samples {
+            stsz.extend_from_slice(&sample.size.to_be_bytes());
+        }
+
+        // co64 rather than stco: long recordings clear 4 GiB easily.
+        let mut co64 = Vec::new();
+        co64.extend_from_slice(&[0; 4]);
+        co64.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        for sample in samples {
+            co64.extend_from_slice(&(base_offset + sample.offset).to_be_bytes());
+        }
+
+        let mut children = vec![
+            build_box(b"stsd", &[&stsd]),
+            build_box(b"stts", &[&stts]),
+            build_box(b"stsc", &[&stsc]),
+            build_box(b"stsz", &[&stsz]),
+            build_box(b"co64", &[&co64]),
+        ];
+        if video {
+            let keyframes: Vec<u32> = samples
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.keyframe)
+                .map(|(i, _)| i as u32 + 1)
+                .collect();
+            let mut stss = Vec::new();
+            stss.extend_from_slice(&[0; 4]);
+            stss.extend_from_slice(&(keyframes.len() as u32).to_be_bytes());
+            for index in &keyframes {
+                stss.extend_from_slice(&index.to_be_bytes());
+            }
+            children.push(build_box(b"stss", &[&stss]));
+        }
+        let refs: Vec<&[u8]> = children.iter().map(Vec::as_slice).collect();
+        build_box(b"stbl", &refs)
+    }
+}
+
+pub(crate) const IDENTITY_MATRIX: [u8; 36] = [
+    0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0,
+];
+
+/// Wraps `payloads` in a size-prefixed box.
+pub(crate) fn build_box(name: &[u8; 4], payloads: &[&[u8]]) -> Vec<u8> {
+    let size = 8 + payloads.iter().map(|p| p.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&(size as u32).to_be_bytes());
+    out.extend_from_slice(name);
+    for payload in payloads {
+        out.extend_from_slice(payload);
+    }
+    out
+}
+
+/// `avc1` sample entry with the `avcC` decoder configuration.
+pub(crate) fn video_sample_entry(width: u32, height: u32, sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut avcc = Vec::new();
+    avcc.push(1); // configuration version
+    avcc.extend_from_slice(&sps[1..4]); // profile, compat, level
+    avcc.push(0xff); // 4-byte NALU lengths
+    avcc.push(0xe1); // one SPS
+    avcc.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+    avcc.extend_from_slice(sps);
+    avcc.push(1); // one PPS
+    avcc.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+    avcc.extend_from_slice(pps);
+
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&[0; 6]); // reserved
+    entry.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+    entry.extend_from_slice(&[0; 16]); // predefined/reserved
+    entry.extend_from_slice(&(width as u16).to_be_bytes());
+    entry.extend_from_slice(&(height as u16).to_be_bytes());
+    entry.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+    entry.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+    entry.extend_from_slice(&[0; 4]);
+    entry.extend_from_slice(&1u16.to_be_bytes()); // frame count
+    entry.extend_from_slice(&[0; 32]); // compressor name
+    entry.extend_from_slice(&0x0018u16.to_be_bytes()); // depth
+    entry.extend_from_slice(&(-1i16).to_be_bytes()); // predefined
+    entry.extend_from_slice(&build_box(b"avcC", &[&avcc]));
+    build_box(b"avc1", &[&entry])
+}
+
+/// `Opus` sample entry with the `dOps` configuration (RFC 7845 layout).
+fn audio_sample_entry() -> Vec<u8> {
+    let mut dops = Vec::new();
+    dops.push(0); // version
+    dops.push(2); // channels
+    dops.extend_from_slice(&3840u16.to_be_bytes()); // pre-skip (80 ms)
+    dops.extend_from_slice(&AUDIO_TIMESCALE.to_be_bytes()); // input rate
+    dops.extend_from_slice(&0i16.to_be_bytes()); // output gain
+    dops.push(0); // mapping family
+
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&[0; 6]);
+    entry.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+    entry.extend_from_slice(&[0; 8]);
+    entry.extend_from_slice(&2u16.to_be_bytes()); // channels
+    entry.extend_from_slice(&16u16.to_be_bytes()); // sample size
+    entry.extend_from_slice(&[0; 4]);
+    entry.extend_from_slice(&(AUDIO_TIMESCALE << 16).to_be_bytes()); // 16.16
+    entry.extend_from_slice(&build_box(b"dOps", &[&dops]));
+    build_box(b"Opus", &[&entry])
+}
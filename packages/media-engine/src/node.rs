@@ -0,0 +1,1319 @@
+//! The NAPI surface the Electron main process loads: enumerate targets,
+//! start/stop a share, force keyframes, and receive stats/error/stopped
+//! callbacks. Everything here is a thin translation layer over `engine`;
+//! Rust consumers skip it by building without the `node` feature.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+
+use crate::config::{EncoderConfig, ScreenShareConfig};
+use crate::engine::{ConnectedInfo, EngineCallbacks, MediaEngine, RoomEvent, StartupTimings};
+use crate::stats::EngineStats;
+
+/// Active sessions keyed by the handle returned from `start_screen_share`.
+/// Multiple shares can run concurrently (two monitors, or sharing while
+/// recording).
+static SESSIONS: Mutex<BTreeMap<u32, MediaEngine>> = Mutex::new(BTreeMap::new());
+static NEXT_SESSION_ID: AtomicU32 = AtomicU32::new(1);
+
+#[napi(object)]
+pub struct JsScreenShareConfig {
+    /// Omit (or pass empty) together with `token` for record-only mode:
+    /// capture + encode to `recordPath` with no LiveKit connection.
+    pub server_url: Option<String>,
+    /// Tried in order when `serverUrl` is unreachable (multi-region
+    /// deployments).
+    pub fallback_urls: Option<Vec<String>>,
+    pub token: Option<String>,
+    /// Extra STUN/TURN servers tried alongside the ones LiveKit hands out
+    /// (self-hosted deployments running their own coturn).
+    pub ice_servers: Option<Vec<JsIceServer>>,
+    /// What to capture, e.g. `{ kind: "display", index: 0 }` or
+    /// `{ kind: "window", hwnd }`.
+    pub target: JsCaptureTarget,
+    /// "smooth" | "balanced" | "sharp" — coordinated fps/resolution/
+    /// bitrate/GOP defaults for callers who don't want to pick numbers.
+    /// Explicit fields below still override individual values.
+    pub preset: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+    pub bitrate_kbps: Option<u32>,
+    /// How the published track is announced: display name, source kind,
+    /// stream grouping. Omitted fields derive from the capture target.
+    pub track: Option<JsTrackPublishOptions>,
+    pub show_cursor: Option<bool>,
+    /// Audio source: `{ kind: "system" }` or `{ kind: "process", pid }`.
+    /// Absent for no audio.
+    pub audio: Option<JsAudioMode>,
+    /// Also write the raw H.264 stream to this path.
+    pub record_path: Option<String>,
+    /// Split the recording into numbered segments with automatic
+    /// rollover; requires `recordPath`.
+    pub record_rotate: Option<JsRecordRotateConfig>,
+    /// Keep a rolling buffer of the last N seconds for `saveReplay`.
+    pub replay_seconds: Option<u32>,
+    /// Also push the stream to this RTMP(S) ingest URL
+    /// (`rtmp://host/app/streamKey`) — Twitch/YouTube live streaming.
+    /// Works alone (empty `serverUrl`) or next to the LiveKit publish.
+    pub rtmp_url: Option<String>,
+    /// Raw RTP/UDP output to a fixed address, for verifying the pipeline
+    /// with ffplay/GStreamer or feeding a custom SFU.
+    pub rtp_out: Option<JsRtpOutConfig>,
+    /// Also segment the stream into this directory as fMP4 + an HLS
+    /// playlist (`stream.m3u8`) — serve it over HTTP for browser viewing.
+    pub hls_dir: Option<String>,
+    /// Secondary encoders fed from the same capture, each with its own
+    /// output file (e.g. a lower-bitrate local recording).
+    pub tees: Option<Vec<JsEncoderTee>>,
+    /// Webcam published alongside the screen share as a second video
+    /// track on the same connection.
+    pub camera: Option<JsCameraShareConfig>,
+    /// Second source composited into a corner of the main capture before
+    /// encoding (picture-in-picture), producing one combined track.
+    pub overlay: Option<JsOverlayConfig>,
+    /// Per-room E2EE shared secret; enables frame encryption when set.
+    pub e2ee_key: Option<String>,
+    /// First-captured-frame timeout in milliseconds (default 5000).
+    pub first_frame_timeout_ms: Option<u32>,
+    /// Signal dial + join handshake timeout in milliseconds (default 10000).
+    pub signal_connect_timeout_ms: Option<u32>,
+    /// Negotiation + ICE timeout in milliseconds (default 15000).
+    pub ice_connect_timeout_ms: Option<u32>,
+    /// How often `onStats` fires, in milliseconds (default 1000,
+    /// minimum 100). `getScreenShareStats` returns snapshots at any cadence.
+    pub stats_interval_ms: Option<u32>,
+    /// Serve Prometheus metrics at `http://127.0.0.1:port/metrics` for
+    /// this session. Loopback only.
+    pub metrics_port: Option<u32>,
+    /// Profile the pipeline and write a Chrome trace (for `chrome://tracing`
+    /// or Perfetto) to this path when the session ends.
+    pub trace_path: Option<String>,
+    /// PEM bundle of extra root certificates to trust for `wss://`
+    /// (self-hosted deployments with an internal CA).
+    pub ca_certificate: Option<String>,
+    /// Trust only `caCertificate` roots, not the OS trust store.
+    pub disable_system_roots: Option<bool>,
+    /// Accept any server certificate. Dangerous; explicit opt-in only.
+    pub accept_invalid_certs: Option<bool>,
+    /// Reconnect attempts before the session is considered lost (default 5).
+    pub reconnect_max_retries: Option<u32>,
+    /// First reconnect backoff in milliseconds, doubling per attempt
+    /// (default 500).
+    pub reconnect_backoff_ms: Option<u32>,
+    /// Random extra delay added to each backoff (default 250).
+    pub reconnect_jitter_ms: Option<u32>,
+    /// Register the realtime worker threads with the Multimedia Class
+    /// Scheduler (Windows, default true).
+    pub mmcss_enabled: Option<bool>,
+    /// MMCSS thread priority: "low" | "normal" | "high" | "critical"
+    /// (default "normal").
+    pub mmcss_priority: Option<String>,
+    /// Ramp the bitrate up gradually at session start instead of opening
+    /// at the full configured rate.
+    pub ramp_up: Option<bool>,
+    /// Starting bitrate for the ramp (default 1000).
+    pub ramp_up_start_kbps: Option<u32>,
+    /// Doubling interval for the ramp in milliseconds (default 3000).
+    pub ramp_up_step_ms: Option<u32>,
+}
+
+/// How the published video track is announced to the room.
+#[napi(object)]
+pub struct JsTrackPublishOptions {
+    /// Display name receiving clients see, e.g. `"Monitor 2"`.
+    pub name: Option<String>,
+    /// `"screenshare"`, `"camera"`, or `"unknown"`.
+    pub source: Option<String>,
+    /// Stream grouping key; tracks sharing it are bundled by receivers.
+    pub stream: Option<String>,
+    /// Free-form app label, stored with saved profiles. Not transmitted —
+    /// LiveKit's AddTrack request has no per-track metadata slot.
+    pub metadata: Option<String>,
+}
+
+/// One STUN/TURN server, shaped like the W3C `RTCIceServer` dictionary.
+#[napi(object)]
+pub struct JsIceServer {
+    /// `stun:host[:port]` / `turn:host[:port]?transport=udp` URLs.
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+#[napi(object)]
+pub struct JsCameraShareConfig {
+    /// Index from `listCameras`.
+    pub index: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+    pub bitrate_kbps: Option<u32>,
+}
+
+/// Discriminated capture target. Exactly the fields its `kind` needs must
+/// be present; anything else is rejected before the session starts.
+#[napi(object)]
+pub struct JsCaptureTarget {
+    /// `"display"`, `"window"`, or `"camera"`.
+    pub kind: String,
+    /// Enumeration index, for `kind: "display"` and `kind: "camera"`.
+    pub index: Option<u32>,
+    /// Window handle from `listWindows`, for `kind: "window"`.
+    pub hwnd: Option<BigInt>,
+}
+
+fn parse_target(target: JsCaptureTarget) -> Result<capture::CaptureTarget> {
+    match target.kind.as_str() {
+        "display" | "camera" => {
+            let index = target.index.ok_or_else(|| {
+                Error::from_reason(format!("target kind \"{}\" requires `index`", target.kind))
+            })? as usize;
+            Ok(if target.kind == "display" {
+                capture::CaptureTarget::Display(index)
+            } else {
+                capture::CaptureTarget::Camera(index)
+            })
+        }
+        "window" => {
+            let hwnd = target
+                .hwnd
+                .ok_or_else(|| Error::from_reason("target kind \"window\" requires `hwnd`"))?;
+            let (_, hwnd, _) = hwnd.get_u64();
+            Ok(capture::CaptureTarget::Window(hwnd))
+        }
+        other => Err(Error::from_reason(format!("unknown target kind: {other}"))),
+    }
+}
+
+/// Discriminated audio source.
+#[napi(object)]
+pub struct JsAudioMode {
+    /// `"system"` (everything minus our own process) or `"process"`.
+    pub kind: String,
+    /// Process id whose tree to capture, for `kind: "process"`.
+    pub pid: Option<u32>,
+}
+
+fn parse_audio_mode(audio: JsAudioMode) -> Result<config::AudioMode> {
+    match audio.kind.as_str() {
+        "system" => Ok(config::AudioMode::System),
+        "process" => audio
+            .pid
+            .map(config::AudioMode::Process)
+            .ok_or_else(|| Error::from_reason("audio kind \"process\" requires `pid`")),
+        other => Err(Error::from_reason(format!("unknown audio kind: {other}"))),
+    }
+}
+
+#[napi(object)]
+pub struct JsOverlayConfig {
+    /// What to blend in, same shape as the main `target`.
+    pub target: JsCaptureTarget,
+    /// "top_left" | "top_right" | "bottom_left" | "bottom_right"
+    /// (default bottom right).
+    pub anchor: Option<String>,
+    /// Overlay width as a fraction of the main frame width, clamped to
+    /// 0.05..=0.5 (default 0.2).
+    pub fraction: Option<f64>,
+}
+
+#[napi(object)]
+pub struct JsEncoderTee {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+    pub bitrate_kbps: Option<u32>,
+    pub record_path: String,
+}
+
+/// Segment rollover policy for long recordings; at least one of the
+/// limits must be set.
+#[napi(object)]
+pub struct JsRecordRotateConfig {
+    /// Close the segment after this many seconds of video.
+    pub max_seconds: Option<u32>,
+    /// Close the segment once it reaches this many MiB.
+    pub max_mb: Option<u32>,
+    /// Keep at most this many segments, deleting the oldest.
+    pub keep_segments: Option<u32>,
+}
+
+/// Raw RTP/UDP output for debugging and custom SFUs.
+#[napi(object)]
+pub struct JsRtpOutConfig {
+    /// `host:port` the H.264 RTP packets are sent to.
+    pub address: String,
+    /// `host:port` for the Opus RTP packets; only used in stream-only
+    /// mode (empty `serverUrl`).
+    pub audio_address: Option<String>,
+    /// Write an SDP file describing the streams here, playable with
+    /// `ffplay -protocol_whitelist file,rtp,udp`.
+    pub sdp_path: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsEngineStats {
+    /// Handle of the session these stats belong to, so callbacks can be
+    /// correlated when several sessions run concurrently.
+    pub session_id: u32,
+    pub frames_captured: f64,
+    pub frames_encoded: f64,
+    pub frames_dropped: f64,
+    pub keyframes: f64,
+    pub bytes_sent: f64,
+    pub packets_sent: f64,
+    pub avg_encode_ms: f64,
+    pub fps: f64,
+    pub bitrate_kbps: f64,
+    /// Average capture-to-send latency over the rolling window, in ms.
+    pub avg_latency_ms: f64,
+    /// 95th percentile capture-to-send latency, in ms.
+    pub p95_latency_ms: f64,
+    /// Encode-time percentiles over the rolling window, in ms.
+    pub p50_encode_ms: f64,
+    pub p95_encode_ms: f64,
+    pub p99_encode_ms: f64,
+    /// Average keyframe size over the last stats interval, in bytes.
+    pub avg_keyframe_bytes: f64,
+    /// Friendly name of the active encoder MFT; empty until the encode
+    /// pipeline comes up.
+    pub encoder_name: String,
+    /// "nvidia", "amd", "intel", or "unknown".
+    pub encoder_vendor: String,
+    /// The encoder is a hardware MFT (always true once the pipeline is up).
+    pub encoder_hardware: bool,
+    /// Frames stay on the GPU end to end; there is no CPU readback path.
+    pub encoder_d3d: bool,
+    /// Process CPU usage as a percentage of all logical cores
+    /// (Windows only; zero elsewhere).
+    pub cpu_percent: f64,
+    /// GPU engine utilization attributed to this process, in percent
+    /// (Windows only; zero elsewhere).
+    pub gpu_percent: f64,
+    /// Dedicated video memory in use by this process, in megabytes
+    /// (Windows only; zero elsewhere).
+    pub gpu_memory_mb: f64,
+}
+
+impl From<EngineStats> for JsEngineStats {
+    fn from(s: EngineStats) -> Self {
+        Self {
+            // Filled in by the caller, which knows the session handle.
+            session_id: 0,
+            frames_captured: s.frames_captured as f64,
+            frames_encoded: s.frames_encoded as f64,
+            frames_dropped: s.frames_dropped as f64,
+            keyframes: s.keyframes as f64,
+            bytes_sent: s.bytes_sent as f64,
+            packets_sent: s.packets_sent as f64,
+            avg_encode_ms: s.avg_encode_ms,
+            fps: s.fps,
+            bitrate_kbps: s.bitrate_kbps,
+            avg_latency_ms: s.avg_latency_ms,
+            p95_latency_ms: s.p95_latency_ms,
+            p50_encode_ms: s.p50_encode_ms,
+            p95_encode_ms: s.p95_encode_ms,
+            p99_encode_ms: s.p99_encode_ms,
+            avg_keyframe_bytes: s.avg_keyframe_bytes,
+            encoder_name: s.encoder_name,
+            encoder_vendor: s.encoder_vendor,
+            encoder_hardware: s.encoder_hardware,
+            encoder_d3d: s.encoder_d3d,
+            cpu_percent: s.cpu_percent,
+            gpu_percent: s.gpu_percent,
+            gpu_memory_mb: s.gpu_memory_mb,
+        }
+    }
+}
+
+/// Startup latency breakdown, delivered once through `onStarted` when the
+/// session becomes fully live. Milliseconds from `startScreenShare`;
+/// fields the session never reached (e.g. ICE in record-only mode) are
+/// null.
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsStartupTimings {
+    pub first_frame_ms: Option<f64>,
+    pub encoder_ready_ms: Option<f64>,
+    pub signal_join_ms: Option<f64>,
+    pub ice_connected_ms: Option<f64>,
+    pub first_packet_ms: Option<f64>,
+}
+
+impl From<StartupTimings> for JsStartupTimings {
+    fn from(t: StartupTimings) -> Self {
+        Self {
+            first_frame_ms: t.first_frame_ms.map(|v| v as f64),
+            encoder_ready_ms: t.encoder_ready_ms.map(|v| v as f64),
+            signal_join_ms: t.signal_join_ms.map(|v| v as f64),
+            ice_connected_ms: t.ice_connected_ms.map(|v| v as f64),
+            first_packet_ms: t.first_packet_ms.map(|v| v as f64),
+        }
+    }
+}
+
+/// Delivered once through `onConnected` when the join completed, ICE
+/// connected, and the server acknowledged the published track — positive
+/// confirmation that publishing succeeded, not just an absence of errors.
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsConnectedInfo {
+    pub room_name: String,
+    pub room_sid: String,
+    pub participant_identity: String,
+    pub participant_sid: String,
+    pub track_sid: String,
+}
+
+/// A non-fatal issue. `code` is stable ("camera", "overlay", "audio",
+/// "frames_dropped", "bandwidth", "signal"); `message` is human-readable.
+#[napi(object)]
+pub struct JsWarning {
+    pub code: String,
+    pub message: String,
+}
+
+/// A fatal engine failure. `code` is a stable category (`"config"`,
+/// `"encode"`, `"signal_timeout"`, ...); `message` is human-readable.
+#[napi(object)]
+pub struct JsEngineError {
+    pub code: String,
+    pub message: String,
+}
+
+/// Converts an engine error into a thrown napi error with its stable code
+/// prefixed, e.g. `[encode] encoder error: ...`.
+fn engine_error(e: error::EngineError) -> Error {
+    Error::from_reason(format!("[{}] {e}", e.code()))
+}
+
+impl From<ConnectedInfo> for JsConnectedInfo {
+    fn from(info: ConnectedInfo) -> Self {
+        Self {
+            room_name: info.room_name,
+            room_sid: info.room_sid,
+            participant_identity: info.participant_identity,
+            participant_sid: info.participant_sid,
+            track_sid: info.track_sid,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsRoomParticipant {
+    pub sid: String,
+    pub identity: String,
+    pub name: String,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsConnectionQuality {
+    pub participant_sid: String,
+    /// "excellent" | "good" | "poor" | "lost"
+    pub quality: String,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsSpeaker {
+    pub sid: String,
+    pub level: f64,
+    pub active: bool,
+}
+
+/// A discriminated room event; exactly one of the payload fields is set
+/// depending on `kind`.
+#[napi(object)]
+#[derive(Clone)]
+pub struct JsRoomEvent {
+    /// "participant_update" | "connection_quality" | "speakers_changed"
+    pub kind: String,
+    pub participants: Option<Vec<JsRoomParticipant>>,
+    pub qualities: Option<Vec<JsConnectionQuality>>,
+    pub speakers: Option<Vec<JsSpeaker>>,
+}
+
+impl From<RoomEvent> for JsRoomEvent {
+    fn from(event: RoomEvent) -> Self {
+        match event {
+            RoomEvent::ParticipantUpdate(participants) => JsRoomEvent {
+                kind: "participant_update".into(),
+                participants: Some(
+                    participants
+                        .into_iter()
+                        .map(|p| JsRoomParticipant {
+                            sid: p.sid,
+                            identity: p.identity,
+                            name: p.name,
+                        })
+                        .collect(),
+                ),
+                qualities: None,
+                speakers: None,
+            },
+            RoomEvent::ConnectionQuality(updates) => JsRoomEvent {
+                kind: "connection_quality".into(),
+                participants: None,
+                qualities: Some(
+                    updates
+                        .into_iter()
+                        .map(|q| JsConnectionQuality {
+                            participant_sid: q.participant_sid,
+                            quality: match q.quality {
+                                q if q == livekit_protocol::ConnectionQuality::Excellent as i32 => {
+                                    "excellent".into()
+                                }
+                                q if q == livekit_protocol::ConnectionQuality::Good as i32 => {
+                                    "good".into()
+                                }
+                                q if q == livekit_protocol::ConnectionQuality::Lost as i32 => {
+                                    "lost".into()
+                                }
+                                _ => "poor".into(),
+                            },
+                        })
+                        .collect(),
+                ),
+                speakers: None,
+            },
+            RoomEvent::Reconnecting => JsRoomEvent {
+                kind: "reconnecting".into(),
+                participants: None,
+                qualities: None,
+                speakers: None,
+            },
+            RoomEvent::Reconnected => JsRoomEvent {
+                kind: "reconnected".into(),
+                participants: None,
+                qualities: None,
+                speakers: None,
+            },
+            RoomEvent::SpeakersChanged(speakers) => JsRoomEvent {
+                kind: "speakers_changed".into(),
+                participants: None,
+                qualities: None,
+                speakers: Some(
+                    speakers
+                        .into_iter()
+                        .map(|s| JsSpeaker {
+                            sid: s.sid,
+                            level: s.level as f64,
+                            active: s.active,
+                        })
+                        .collect(),
+                ),
+            },
+        }
+    }
+}
+
+#[napi(object)]
+pub struct JsWindowInfo {
+    pub hwnd: BigInt,
+    pub title: String,
+    pub process_id: u32,
+}
+
+#[napi(object)]
+pub struct JsDisplayInfo {
+    pub index: u32,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub is_primary: bool,
+}
+
+/// Enumerates capturable top-level windows.
+#[napi]
+pub fn list_windows() -> Vec<JsWindowInfo> {
+    capture::enumerate::list_windows()
+        .into_iter()
+        .map(|w| JsWindowInfo {
+            hwnd: BigInt::from(w.hwnd),
+            title: w.title,
+            process_id: w.process_id,
+        })
+        .collect()
+}
+
+/// Async variant of `listWindows`. Enumeration walks every top-level
+/// window and can take hundreds of milliseconds on busy desktops, so this
+/// runs it on a worker thread instead of blocking the Node main thread.
+#[napi]
+pub async fn list_windows_async() -> Result<Vec<JsWindowInfo>> {
+    tokio::task::spawn_blocking(list_windows)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+#[napi(object)]
+pub struct JsCameraInfo {
+    pub index: u32,
+    pub name: String,
+    /// Stable device id, useful for persisting a selection.
+    pub symbolic_link: String,
+}
+
+/// Enumerates connected cameras. Use `targetType: "camera"` with the
+/// returned index to publish a webcam through the engine.
+#[napi]
+pub fn list_cameras() -> Vec<JsCameraInfo> {
+    #[cfg(windows)]
+    {
+        capture::camera::list_cameras()
+            .into_iter()
+            .map(|c| JsCameraInfo {
+                index: c.index as u32,
+                name: c.name,
+                symbolic_link: c.symbolic_link,
+            })
+            .collect()
+    }
+    #[cfg(not(windows))]
+    {
+        Vec::new()
+    }
+}
+
+/// Enumerates connected displays.
+#[napi]
+pub fn list_displays() -> Vec<JsDisplayInfo> {
+    capture::enumerate::list_displays()
+        .into_iter()
+        .map(|d| JsDisplayInfo {
+            index: d.index as u32,
+            name: d.name,
+            width: d.width,
+            height: d.height,
+            is_primary: d.is_primary,
+        })
+        .collect()
+}
+
+/// Async variant of `listDisplays`; enumerates on a worker thread.
+#[napi]
+pub async fn list_displays_async() -> Result<Vec<JsDisplayInfo>> {
+    tokio::task::spawn_blocking(list_displays)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+#[napi(object)]
+pub struct JsEncoderInfo {
+    /// "h264", "hevc", or "av1".
+    pub codec: String,
+    pub name: String,
+    /// "nvidia", "amd", "intel", or "unknown".
+    pub vendor: String,
+}
+
+#[napi(object)]
+pub struct JsCapabilities {
+    pub encoders: Vec<JsEncoderInfo>,
+    pub wgc: bool,
+    pub process_loopback_audio: bool,
+    pub max_encode_width: u32,
+    pub max_encode_height: u32,
+    pub hdr: bool,
+}
+
+/// Probes hardware encoders and OS capture support so the UI can offer
+/// only options that will actually work.
+#[napi]
+pub fn get_capabilities() -> JsCapabilities {
+    let caps = capabilities::probe();
+    JsCapabilities {
+        encoders: caps
+            .encoders
+            .into_iter()
+            .map(|e| JsEncoderInfo {
+                codec: e.codec.to_string(),
+                name: e.name,
+                vendor: e.vendor.to_string(),
+            })
+            .collect(),
+        wgc: caps.wgc,
+        process_loopback_audio: caps.process_loopback_audio,
+        max_encode_width: caps.max_encode_width,
+        max_encode_height: caps.max_encode_height,
+        hdr: caps.hdr,
+    }
+}
+
+fn build_config(js: JsScreenShareConfig) -> Result<ScreenShareConfig> {
+    let preset_given = js.preset.is_some();
+    let defaults = match js.preset.as_deref() {
+        Some(raw) => config::QualityPreset::parse(raw)
+            .map_err(engine_error)?
+            .encoder(),
+        None => EncoderConfig::default(),
+    };
+    Ok(ScreenShareConfig {
+        server_url: js.server_url.unwrap_or_default(),
+        fallback_urls: js.fallback_urls.unwrap_or_default(),
+        token: js.token.unwrap_or_default(),
+        ice_servers: js
+            .ice_servers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|server| config::IceServerConfig {
+                urls: server.urls,
+                username: server.username,
+                credential: server.credential,
+            })
+            .collect(),
+        target: parse_target(js.target)?,
+        encoder: {
+            let width = js.width.unwrap_or(defaults.width);
+            let height = js.height.unwrap_or(defaults.height);
+            let fps = js.fps.unwrap_or(defaults.fps);
+            EncoderConfig {
+                width,
+                height,
+                fps,
+                // No explicit bitrate: a preset supplies its own, otherwise
+                // derive one from the output size and frame rate.
+                bitrate_kbps: js.bitrate_kbps.unwrap_or_else(|| {
+                    if preset_given {
+                        defaults.bitrate_kbps
+                    } else {
+                        config::default_bitrate_kbps(width, height, fps)
+                    }
+                }),
+                gop_seconds: defaults.gop_seconds,
+            }
+        },
+        track: js
+            .track
+            .map(|track| {
+                Ok::<_, Error>(config::TrackPublishConfig {
+                    name: track.name,
+                    source: track
+                        .source
+                        .as_deref()
+                        .map(config::TrackSource::parse)
+                        .transpose()
+                        .map_err(engine_error)?,
+                    stream: track.stream,
+                    metadata: track.metadata,
+                })
+            })
+            .transpose()?
+            .unwrap_or_default(),
+        audio_mode: js.audio.map(parse_audio_mode).transpose()?,
+        show_cursor: js.show_cursor.unwrap_or(true),
+        record_path: js.record_path,
+        record_rotate: js.record_rotate.map(|r| config::RecordRotateConfig {
+            max_seconds: r.max_seconds,
+            max_mb: r.max_mb,
+            keep_segments: r.keep_segments,
+        }),
+        replay_seconds: js.replay_seconds,
+        rtmp_url: js.rtmp_url,
+        rtp_out: js.rtp_out.map(|rtp| config::RtpOutConfig {
+            address: rtp.address,
+            audio_address: rtp.audio_address,
+            sdp_path: rtp.sdp_path,
+        }),
+        hls_dir: js.hls_dir,
+        camera: js.camera.map(|cam| {
+            let width = cam.width.unwrap_or(1280);
+            let height = cam.height.unwrap_or(720);
+            let fps = cam.fps.unwrap_or(30);
+            config::CameraShareConfig {
+                index: cam.index as usize,
+                encoder: EncoderConfig {
+                    width,
+                    height,
+                    fps,
+                    bitrate_kbps: cam
+                        .bitrate_kbps
+                        .unwrap_or_else(|| config::default_bitrate_kbps(width, height, fps)),
+                    gop_seconds: defaults.gop_seconds,
+                },
+            }
+        }),
+        overlay: js
+            .overlay
+            .map(|overlay| {
+                Ok::<_, Error>(config::OverlayConfig {
+                    target: parse_target(overlay.target)?,
+                    anchor: overlay
+                        .anchor
+                        .as_deref()
+                        .map(config::OverlayAnchor::parse)
+                        .transpose()
+                        .map_err(engine_error)?
+                        .unwrap_or(config::OverlayAnchor::BottomRight),
+                    fraction: overlay.fraction.unwrap_or(0.2) as f32,
+                })
+            })
+            .transpose()?,
+        tees: js
+            .tees
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tee| {
+                let width = tee.width.unwrap_or(defaults.width);
+                let height = tee.height.unwrap_or(defaults.height);
+                let fps = tee.fps.unwrap_or(defaults.fps);
+                config::EncoderTee {
+                    encoder: EncoderConfig {
+                        width,
+                        height,
+                        fps,
+                        bitrate_kbps: tee
+                            .bitrate_kbps
+                            .unwrap_or_else(|| config::default_bitrate_kbps(width, height, fps)),
+                        gop_seconds: defaults.gop_seconds,
+                    },
+                    record_path: tee.record_path,
+                }
+            })
+            .collect(),
+        e2ee_key: js.e2ee_key,
+        first_frame_timeout_ms: js
+            .first_frame_timeout_ms
+            .map(u64::from)
+            .unwrap_or(config::DEFAULT_FIRST_FRAME_TIMEOUT_MS),
+        signal_connect_timeout_ms: js
+            .signal_connect_timeout_ms
+            .map(u64::from)
+            .unwrap_or(config::DEFAULT_SIGNAL_CONNECT_TIMEOUT_MS),
+        ice_connect_timeout_ms: js
+            .ice_connect_timeout_ms
+            .map(u64::from)
+            .unwrap_or(config::DEFAULT_ICE_CONNECT_TIMEOUT_MS),
+        stats_interval_ms: js
+            .stats_interval_ms
+            .map(u64::from)
+            .unwrap_or(config::DEFAULT_STATS_INTERVAL_MS),
+        metrics_port: js
+            .metrics_port
+            .map(|port| {
+                // Port 0 would bind an ephemeral port the app can't
+                // discover, so it's rejected along with out-of-range.
+                u16::try_from(port)
+                    .ok()
+                    .filter(|&port| port != 0)
+                    .ok_or_else(|| Error::from_reason("metricsPort must be 1..=65535"))
+            })
+            .transpose()?,
+        trace_path: js.trace_path,
+        tls: config::TlsConfig {
+            ca_pem: js.ca_certificate,
+            no_system_roots: js.disable_system_roots.unwrap_or(false),
+            accept_invalid_certs: js.accept_invalid_certs.unwrap_or(false),
+        },
+        reconnect: {
+            let defaults = config::ReconnectPolicy::default();
+            config::ReconnectPolicy {
+                max_retries: js.reconnect_max_retries.unwrap_or(defaults.max_retries),
+                backoff_ms: js
+                    .reconnect_backoff_ms
+                    .map(u64::from)
+                    .unwrap_or(defaults.backoff_ms),
+                jitter_ms: js
+                    .reconnect_jitter_ms
+                    .map(u64::from)
+                    .unwrap_or(defaults.jitter_ms),
+            }
+        },
+        mmcss: {
+            let defaults = config::MmcssConfig::default();
+            config::MmcssConfig {
+                enabled: js.mmcss_enabled.unwrap_or(defaults.enabled),
+                priority: match js.mmcss_priority.as_deref() {
+                    None => defaults.priority,
+                    Some("low") => config::MmcssPriority::Low,
+                    Some("normal") => config::MmcssPriority::Normal,
+                    Some("high") => config::MmcssPriority::High,
+                    Some("critical") => config::MmcssPriority::Critical,
+                    Some(other) => {
+                        return Err(Error::from_reason(format!(
+                            "mmcssPriority must be low|normal|high|critical, got '{other}'"
+                        )))
+                    }
+                },
+            }
+        },
+        ramp_up: js.ramp_up.unwrap_or(false).then(|| {
+            let defaults = config::RampUpPolicy::default();
+            config::RampUpPolicy {
+                start_kbps: js.ramp_up_start_kbps.unwrap_or(defaults.start_kbps),
+                step_ms: js
+                    .ramp_up_step_ms
+                    .map(u64::from)
+                    .unwrap_or(defaults.step_ms),
+            }
+        }),
+    })
+}
+
+/// Creates the expensive encode-side GPU resources (D3D11 device, video
+/// processor, hardware encoder) ahead of time so a following
+/// `startScreenShare` is near-instant. Call it when the share picker
+/// opens. Safe to call repeatedly; a no-op off Windows.
+#[napi]
+pub async fn prewarm() -> Result<()> {
+    #[cfg(windows)]
+    {
+        tokio::task::spawn_blocking(encode::d3d::prewarm)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?
+            .map_err(engine_error)
+    }
+    #[cfg(not(windows))]
+    {
+        Ok(())
+    }
+}
+
+/// Outcome of one diagnostic stage.
+#[napi(object)]
+pub struct JsStageReport {
+    pub ok: bool,
+    /// A short summary when the stage passed, the error when it failed.
+    pub detail: String,
+    pub elapsed_ms: f64,
+}
+
+#[napi(object)]
+pub struct JsDiagnosticsReport {
+    pub capture: JsStageReport,
+    pub encode: JsStageReport,
+    pub signal: JsStageReport,
+    pub ice: JsStageReport,
+}
+
+impl From<diagnostics::StageReport> for JsStageReport {
+    fn from(stage: diagnostics::StageReport) -> Self {
+        Self {
+            ok: stage.ok,
+            detail: stage.detail,
+            elapsed_ms: stage.elapsed_ms as f64,
+        }
+    }
+}
+
+/// Pre-flight check: captures one frame, encodes it, joins the room over
+/// the signal WebSocket, and runs a STUN connectivity probe, reporting
+/// per-stage pass/fail. Takes several seconds; run it off the UI path.
+#[napi]
+pub async fn run_diagnostics(server_url: String, token: String) -> Result<JsDiagnosticsReport> {
+    let report = tokio::task::spawn_blocking(move || diagnostics::run(&server_url, &token))
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(JsDiagnosticsReport {
+        capture: report.capture.into(),
+        encode: report.encode.into(),
+        signal: report.signal.into(),
+        ice: report.ice.into(),
+    })
+}
+
+/// One engine log record forwarded by `initLogging`.
+#[napi(object)]
+pub struct JsLogRecord {
+    /// "TRACE" through "ERROR".
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Installs a global `tracing` subscriber forwarding engine logs at or
+/// above `level` ("off", "error", "warn", "info", "debug", "trace") to the
+/// callback. Call once, early; fails if a subscriber is already installed.
+#[napi]
+pub fn init_logging(
+    #[napi(ts_arg_type = "(record: JsLogRecord) => void")] callback: ThreadsafeFunction<
+        JsLogRecord,
+        ErrorStrategy::Fatal,
+    >,
+    level: String,
+) -> Result<()> {
+    logging::init(
+        Box::new(move |record| {
+            callback.call(
+                JsLogRecord {
+                    level: record.level,
+                    target: record.target,
+                    message: record.message,
+                },
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        }),
+        &level,
+    )
+    .map_err(engine_error)
+}
+
+/// Adjusts the log level filter installed by `initLogging`.
+#[napi]
+pub fn set_log_level(level: String) -> Result<()> {
+    logging::set_level(&level).map_err(engine_error)
+}
+
+/// Starts a screen share session and returns its handle. Pass the handle
+/// to the per-session calls (`stopScreenShare`, `forceKeyframe`, ...).
+#[napi]
+pub fn start_screen_share(
+    config: JsScreenShareConfig,
+    #[napi(ts_arg_type = "(stats: JsEngineStats) => void")] on_stats: ThreadsafeFunction<
+        JsEngineStats,
+        ErrorStrategy::Fatal,
+    >,
+    #[napi(ts_arg_type = "(error: JsEngineError) => void")] on_error: ThreadsafeFunction<
+        JsEngineError,
+        ErrorStrategy::Fatal,
+    >,
+    #[napi(ts_arg_type = "(reason: string) => void")] on_stopped: ThreadsafeFunction<
+        String,
+        ErrorStrategy::Fatal,
+    >,
+    #[napi(ts_arg_type = "(event: JsRoomEvent) => void")] on_room_event: Option<
+        ThreadsafeFunction<JsRoomEvent, ErrorStrategy::Fatal>,
+    >,
+    #[napi(ts_arg_type = "(timings: JsStartupTimings) => void")] on_started: Option<
+        ThreadsafeFunction<JsStartupTimings, ErrorStrategy::Fatal>,
+    >,
+    #[napi(ts_arg_type = "(info: JsConnectedInfo) => void")] on_connected: Option<
+        ThreadsafeFunction<JsConnectedInfo, ErrorStrategy::Fatal>,
+    >,
+    #[napi(
+        ts_arg_type = "(state: string) => void"
+    )] on_connection_state: Option<ThreadsafeFunction<String, ErrorStrategy::Fatal>>,
+    #[napi(ts_arg_type = "(warning: JsWarning) => void")] on_warning: Option<
+        ThreadsafeFunction<JsWarning, ErrorStrategy::Fatal>,
+    >,
+) -> Result<u32> {
+    let config = build_config(config)?;
+    // Allocate the handle up front so callbacks can carry it: with several
+    // concurrent sessions the JS side needs to know which one is talking.
+    let session_id = NEXT_SESSION_ID.fetch_add(1, Ordering::SeqCst);
+    let callbacks = EngineCallbacks {
+        on_stats: Box::new(move |stats| {
+            let mut stats: JsEngineStats = stats.into();
+            stats.session_id = session_id;
+            on_stats.call(stats, ThreadsafeFunctionCallMode::NonBlocking);
+        }),
+        on_error: Box::new(move |error| {
+            on_error.call(
+                JsEngineError {
+                    code: error.code().to_string(),
+                    message: error.to_string(),
+                },
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        }),
+        on_warning: Box::new(move |code, message| {
+            if let Some(on_warning) = on_warning.as_ref() {
+                on_warning.call(
+                    JsWarning {
+                        code: code.to_string(),
+                        message,
+                    },
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            }
+        }),
+        on_stopped: Box::new(move |reason| {
+            on_stopped.call(
+                reason.as_str().to_string(),
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        }),
+        on_room_event: Box::new(move |event| {
+            if let Some(on_room_event) = on_room_event.as_ref() {
+                on_room_event.call(event.into(), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }),
+        on_started: Box::new(move |timings| {
+            if let Some(on_started) = on_started.as_ref() {
+                on_started.call(timings.into(), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }),
+        on_connected: Box::new(move |info| {
+            if let Some(on_connected) = on_connected.as_ref() {
+                on_connected.call(info.into(), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }),
+        on_connection_state: Box::new(move |state| {
+            if let Some(on_connection_state) = on_connection_state.as_ref() {
+                on_connection_state.call(
+                    state.as_str().to_string(),
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            }
+        }),
+    };
+
+    let engine = MediaEngine::start(config, callbacks).map_err(engine_error)?;
+    SESSIONS.lock().unwrap().insert(session_id, engine);
+    Ok(session_id)
+}
+
+/// Stops the given session, resolving once teardown — encoder flush,
+/// transport leave, thread joins — has fully completed, so an immediate
+/// restart never races the old encoder's hardware session. Safe to call
+/// with a stale or unknown handle.
+#[napi]
+pub async fn stop_screen_share(session_id: u32) {
+    let engine = SESSIONS.lock().unwrap().remove(&session_id);
+    if let Some(engine) = engine {
+        // Joining the worker threads blocks, so keep it off the JS thread.
+        let _ = tokio::task::spawn_blocking(move || engine.stop_and_wait()).await;
+    }
+}
+
+/// Aborts a session without waiting for teardown — the "user closed the
+/// share picker" path. Works at any point after `startScreenShare`
+/// returns, including while the session is still dialling the server:
+/// partially-initialized capture and transport unwind in the background.
+/// Safe to call with a stale or unknown handle.
+#[napi]
+pub fn cancel_screen_share(session_id: u32) {
+    let engine = SESSIONS.lock().unwrap().remove(&session_id);
+    if let Some(engine) = engine {
+        engine.stop();
+        // Joining the worker threads can take a moment mid-dial; keep it
+        // off the JS thread since the caller doesn't want the result.
+        std::thread::spawn(move || drop(engine));
+    }
+}
+
+/// Like `stopScreenShare`, but also returns the session's final stats.
+#[napi]
+pub async fn stop_and_wait(session_id: u32) -> Option<JsEngineStats> {
+    let engine = SESSIONS.lock().unwrap().remove(&session_id)?;
+    // Joining the worker threads blocks, so keep it off the JS thread.
+    tokio::task::spawn_blocking(move || engine.stop_and_wait().into())
+        .await
+        .ok()
+}
+
+/// Stops every active session. Used on app shutdown.
+#[napi]
+pub fn stop_all_screen_shares() {
+    let sessions = std::mem::take(&mut *SESSIONS.lock().unwrap());
+    for (_, engine) in sessions {
+        engine.stop();
+        drop(engine);
+    }
+}
+
+/// Pauses the given session: encoding and audio stop but capture and the
+/// LiveKit connection stay warm, so resume is instant.
+#[napi]
+pub fn pause_screen_share(session_id: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.pause();
+    }
+}
+
+/// Resumes a paused session.
+#[napi]
+pub fn resume_screen_share(session_id: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.resume();
+    }
+}
+
+/// Whether the given session is paused.
+#[napi]
+pub fn is_paused(session_id: u32) -> bool {
+    let guard = SESSIONS.lock().unwrap();
+    guard.get(&session_id).map(|e| e.is_paused()).unwrap_or(false)
+}
+
+/// Mutes or unmutes the published video track: RTP stops and the server
+/// gets a MuteTrack signal, so receivers render a muted tile instead of a
+/// frozen frame. Unlike `pauseScreenShare`, audio keeps flowing.
+#[napi]
+pub fn set_video_muted(session_id: u32, muted: bool) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_video_muted(muted);
+    }
+}
+
+/// Mutes or unmutes the screen-share audio track without touching the
+/// video. A no-op when the session started without audio.
+#[napi]
+pub fn set_audio_muted(session_id: u32, muted: bool) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_audio_muted(muted);
+    }
+}
+
+/// Mutes or unmutes the camera track of a session. Unmuting requests a
+/// keyframe so viewers recover immediately.
+#[napi]
+pub fn set_camera_muted(session_id: u32, muted: bool) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_camera_muted(muted);
+    }
+}
+
+/// Changes the camera encoder bitrate (kbps) on a live session.
+#[napi]
+pub fn set_camera_bitrate(session_id: u32, bitrate_kbps: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_camera_bitrate(bitrate_kbps);
+    }
+}
+
+/// Starts or stops audio capture on a live session without touching the
+/// video pipeline. A no-op when the session started without audio.
+#[napi]
+pub fn set_audio_enabled(session_id: u32, enabled: bool) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_audio_enabled(enabled);
+    }
+}
+
+/// Switches the audio source on a live session: `{ kind: "system" }` or
+/// `{ kind: "process", pid }`.
+#[napi]
+pub fn set_audio_mode(session_id: u32, mode: JsAudioMode) -> Result<()> {
+    let mode = parse_audio_mode(mode)?;
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_audio_mode(mode);
+    }
+    Ok(())
+}
+
+/// Changes the encoder bitrate (kbps) on a live session.
+#[napi]
+pub fn set_bitrate(session_id: u32, bitrate_kbps: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_bitrate(bitrate_kbps);
+    }
+}
+
+/// Changes the encode frame rate on a live session.
+#[napi]
+pub fn set_fps(session_id: u32, fps: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_fps(fps);
+    }
+}
+
+/// Changes the output resolution on a live session. The encoder is
+/// rebuilt; viewers see at most one GOP of disruption.
+#[napi]
+pub fn set_resolution(session_id: u32, width: u32, height: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.set_resolution(width, height);
+    }
+}
+
+/// Writes the last `seconds` of buffered video to `path` as raw Annex-B.
+/// Requires `replaySeconds` in the session config.
+#[napi]
+pub fn save_replay(session_id: u32, path: String, seconds: u32) -> Result<u32> {
+    let guard = SESSIONS.lock().unwrap();
+    let engine = guard
+        .get(&session_id)
+        .ok_or_else(|| Error::from_reason("unknown session"))?;
+    engine
+        .save_replay(std::path::Path::new(&path), seconds as u64)
+        .map(|frames| frames as u32)
+        .map_err(engine_error)
+}
+
+/// Exports the last `seconds` of buffered video to `path` as an animated
+/// GIF for quick sharing. `maxWidth` (default 480) and `fps` (default 10)
+/// shape the output. Requires `replaySeconds` in the session config.
+#[napi]
+pub fn export_clip(
+    session_id: u32,
+    path: String,
+    seconds: u32,
+    max_width: Option<u32>,
+    fps: Option<u32>,
+) -> Result<u32> {
+    let guard = SESSIONS.lock().unwrap();
+    let engine = guard
+        .get(&session_id)
+        .ok_or_else(|| Error::from_reason("unknown session"))?;
+    let defaults = crate::clip::ClipOptions::default();
+    let options = crate::clip::ClipOptions {
+        max_width: max_width.unwrap_or(defaults.max_width),
+        fps: fps.unwrap_or(defaults.fps),
+    };
+    engine
+        .export_clip(std::path::Path::new(&path), seconds as u64, &options)
+        .map(|frames| frames as u32)
+        .map_err(engine_error)
+}
+
+/// Requests the next encoded frame of the given session be a keyframe.
+#[napi]
+pub fn force_keyframe(session_id: u32) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.force_keyframe();
+    }
+}
+
+/// Replaces a session's LiveKit token, e.g. after the app fetched a
+/// fresh one. Reconnect attempts use the newest token.
+#[napi]
+pub fn update_token(session_id: u32, token: String) {
+    let guard = SESSIONS.lock().unwrap();
+    if let Some(engine) = guard.get(&session_id) {
+        engine.update_token(token);
+    }
+}
+
+/// Whether the given session is currently active.
+#[napi]
+pub fn is_running(session_id: u32) -> bool {
+    let guard = SESSIONS.lock().unwrap();
+    guard.get(&session_id).map(|e| e.is_running()).unwrap_or(false)
+}
+
+/// Snapshot of a session's current stats, outside the periodic callback.
+/// Returns null for unknown sessions. JS that missed `onStats` calls
+/// (e.g. after devtools paused the renderer) can always recover the
+/// current numbers here.
+#[napi]
+pub fn get_screen_share_stats(session_id: u32) -> Option<JsEngineStats> {
+    let guard = SESSIONS.lock().unwrap();
+    guard.get(&session_id).map(|e| {
+        let mut stats: JsEngineStats = e.current_stats().into();
+        stats.session_id = session_id;
+        stats
+    })
+}
+
+/// The session's current stats in Prometheus text exposition format, for
+/// apps that expose metrics themselves instead of using `metricsPort`.
+#[napi]
+pub fn get_screen_share_metrics(session_id: u32) -> Result<String> {
+    let guard = SESSIONS.lock().unwrap();
+    let engine = guard
+        .get(&session_id)
+        .ok_or_else(|| Error::from_reason("unknown session"))?;
+    Ok(engine.prometheus_metrics())
+}
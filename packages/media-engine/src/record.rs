@@ -0,0 +1,347 @@
+//! Local recording of the encoded stream.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::RecordRotateConfig;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+use crate::mux::mkv::MkvWriter;
+use crate::mux::mp4::Mp4Writer;
+
+/// The container behind a [`Recorder`], picked from the output extension.
+enum Container {
+    /// Raw Annex-B elementary stream (`.h264` and anything unrecognized).
+    AnnexB {
+        writer: BufWriter<File>,
+        frames_written: u64,
+    },
+    /// Proper MP4 with real timestamps and a faststart moov (`.mp4`/`.m4v`).
+    Mp4(Mp4Writer),
+    /// Matroska, written cluster by cluster so a crash mid-recording still
+    /// leaves a playable file (`.mkv`).
+    Mkv(MkvWriter),
+}
+
+/// Segment rollover bookkeeping for [`Recorder::create_rotating`].
+struct Rotation {
+    policy: RecordRotateConfig,
+    /// The configured output path; segments insert `.NNN` before its
+    /// extension.
+    base_path: PathBuf,
+    /// Index of the segment currently being written.
+    index: u32,
+    /// QPC of the current segment's first frame.
+    segment_start_qpc: Option<i64>,
+    /// Encoded payload bytes in the current segment — an estimate of the
+    /// file size that ignores container overhead, which is negligible next
+    /// to the video.
+    segment_bytes: u64,
+    /// Paths of closed segments, oldest first, for the retention limit.
+    closed: VecDeque<PathBuf>,
+}
+
+/// Writes the encoded stream to disk. Used when
+/// `ScreenShareConfig::record_path` is set; runs on the encode thread so
+/// writes must stay cheap (buffered, no flush per frame).
+///
+/// With a rotation policy the output splits into numbered segments
+/// (`capture.001.mp4`, `capture.002.mp4`, …), each starting at a keyframe
+/// so it decodes standalone; a crash then costs only the open segment.
+pub struct Recorder {
+    container: Container,
+    rotation: Option<Rotation>,
+    frames_total: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> EngineResult<Self> {
+        Ok(Self {
+            container: Self::open_container(path)?,
+            rotation: None,
+            frames_total: 0,
+        })
+    }
+
+    /// Like [`create`](Self::create), but splitting the output per
+    /// `policy`. Rollover happens at the first keyframe past a limit, so
+    /// the encoder's GOP length bounds the overshoot.
+    pub fn create_rotating(path: &Path, policy: &RecordRotateConfig) -> EngineResult<Self> {
+        let first = segment_path(path, 1);
+        Ok(Self {
+            container: Self::open_container(&first)?,
+            rotation: Some(Rotation {
+                policy: policy.clone(),
+                base_path: path.to_path_buf(),
+                index: 1,
+                segment_start_qpc: None,
+                segment_bytes: 0,
+                closed: VecDeque::new(),
+            }),
+            frames_total: 0,
+        })
+    }
+
+    fn open_container(path: &Path) -> EngineResult<Container> {
+        let container = match path.extension().and_then(|e| e.to_str()) {
+            Some("mp4") | Some("m4v") => Container::Mp4(Mp4Writer::create(path)?),
+            Some("mkv") => Container::Mkv(MkvWriter::create(path)?),
+            // WebM forbids H.264, so a .webm carrying our stream would be
+            // rejected by exactly the players that check. Fail up front.
+            Some("webm") => {
+                return Err(EngineError::Config(
+                    "webm does not allow H.264 video; use .mkv instead".into(),
+                ))
+            }
+            _ => {
+                let file = File::create(path).map_err(|e| {
+                    EngineError::Config(format!("cannot create {}: {e}", path.display()))
+                })?;
+                Container::AnnexB {
+                    writer: BufWriter::new(file),
+                    frames_written: 0,
+                }
+            }
+        };
+        Ok(container)
+    }
+
+    pub fn write_frame(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        self.maybe_rotate(frame)?;
+        if let Some(rotation) = self.rotation.as_mut() {
+            rotation.segment_start_qpc.get_or_insert(frame.capture_qpc);
+            rotation.segment_bytes += frame.data.len() as u64;
+        }
+        self.frames_total += 1;
+        match &mut self.container {
+            Container::AnnexB {
+                writer,
+                frames_written,
+            } => {
+                writer
+                    .write_all(&frame.data)
+                    .map_err(|e| EngineError::Encode(format!("record write: {e}")))?;
+                *frames_written += 1;
+                Ok(())
+            }
+            Container::Mp4(mp4) => mp4.write_video(frame),
+            Container::Mkv(mkv) => mkv.write_video(frame),
+        }
+    }
+
+    pub fn finish(self) -> EngineResult<u64> {
+        let frames = self.container.finish()?;
+        // With rotation the last container only saw the final segment;
+        // report the whole session.
+        Ok(match self.rotation {
+            Some(_) => self.frames_total,
+            None => frames,
+        })
+    }
+
+    /// Closes the current segment and opens the next once a keyframe
+    /// arrives past a configured limit, then applies the retention limit.
+    fn maybe_rotate(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        let (closed_path, next_path) = {
+            let Some(rotation) = self.rotation.as_ref() else {
+                return Ok(());
+            };
+            // Only cut at keyframes so every segment decodes standalone.
+            if !frame.is_keyframe {
+                return Ok(());
+            }
+            let Some(start) = rotation.segment_start_qpc else {
+                return Ok(());
+            };
+            let seconds = ((frame.capture_qpc - start).max(0) as u64) / 10_000_000;
+            let over_time = rotation
+                .policy
+                .max_seconds
+                .is_some_and(|s| seconds >= s as u64);
+            let over_size = rotation
+                .policy
+                .max_mb
+                .is_some_and(|mb| rotation.segment_bytes >= mb as u64 * 1024 * 1024);
+            if !over_time && !over_size {
+                return Ok(());
+            }
+            (
+                segment_path(&rotation.base_path, rotation.index),
+                segment_path(&rotation.base_path, rotation.index + 1),
+            )
+        };
+
+        let next = Self::open_container(&next_path)?;
+        let closed = std::mem::replace(&mut self.container, next);
+        match closed.finish() {
+            Ok(frames) => {
+                tracing::debug!("segment {} closed: {frames} frames", closed_path.display())
+            }
+            Err(e) => tracing::error!("segment {} close: {e}", closed_path.display()),
+        }
+
+        let rotation = self.rotation.as_mut().unwrap();
+        rotation.index += 1;
+        rotation.segment_start_qpc = None;
+        rotation.segment_bytes = 0;
+        rotation.closed.push_back(closed_path);
+        if let Some(keep) = rotation.policy.keep_segments {
+            // The open segment counts toward the limit.
+            while rotation.closed.len() as u32 + 1 > keep.max(1) {
+                let oldest = rotation.closed.pop_front().unwrap();
+                if let Err(e) = std::fs::remove_file(&oldest) {
+                    tracing::warn!("removing old segment {}: {e}", oldest.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Container {
+    fn finish(self) -> EngineResult<u64> {
+        match self {
+            Container::AnnexB {
+                mut writer,
+                frames_written,
+            } => {
+                writer
+                    .flush()
+                    .map_err(|e| EngineError::Encode(format!("record flush: {e}")))?;
+                Ok(frames_written)
+            }
+            Container::Mp4(mp4) => mp4.finish(),
+            Container::Mkv(mkv) => mkv.finish(),
+        }
+    }
+}
+
+/// `capture.mp4` → `capture.001.mp4` for segment 1; extensionless paths
+/// get the index appended.
+fn segment_path(base: &Path, index: u32) -> PathBuf {
+    match base.extension().and_then(|e| e.to_str()) {
+        Some(ext) => base.with_extension(format!("{index:03}.{ext}")),
+        None => base.with_extension(format!("{index:03}")),
+    }
+}
+
+/// One buffered access unit: the Annex-B bytes plus the capture QPC
+/// timestamp used to measure how many seconds the buffer spans.
+struct BufferedFrame {
+    data: Vec<u8>,
+    qpc: i64,
+}
+
+/// ShadowPlay-style ring of the most recent encoded GOPs. The encode
+/// thread pushes every frame; `save` dumps the last N seconds to disk
+/// starting at a keyframe so the file decodes from the first frame.
+pub struct ReplayBuffer {
+    /// Each inner vec is one GOP: a keyframe followed by its deltas.
+    gops: VecDeque<Vec<BufferedFrame>>,
+    max_seconds: u64,
+}
+
+impl ReplayBuffer {
+    pub fn new(max_seconds: u64) -> Self {
+        Self {
+            gops: VecDeque::new(),
+            max_seconds: max_seconds.max(1),
+        }
+    }
+
+    pub fn push(&mut self, frame: &EncodedFrame) {
+        if frame.is_keyframe || self.gops.is_empty() {
+            self.gops.push_back(Vec::new());
+        }
+        self.gops.back_mut().unwrap().push(BufferedFrame {
+            data: frame.data.clone(),
+            qpc: frame.capture_qpc,
+        });
+        // Trim whole GOPs from the front once the span exceeds the limit;
+        // never trim the GOP currently being filled.
+        while self.gops.len() > 1 && self.span_seconds() > self.max_seconds {
+            self.gops.pop_front();
+        }
+    }
+
+    /// Seconds between the oldest and newest buffered frame (QPC is in
+    /// 100 ns ticks).
+    fn span_seconds(&self) -> u64 {
+        let first = self.gops.front().and_then(|g| g.first()).map(|f| f.qpc);
+        let last = self.gops.back().and_then(|g| g.last()).map(|f| f.qpc);
+        match (first, last) {
+            (Some(first), Some(last)) => ((last - first).max(0) as u64) / 10_000_000,
+            _ => 0,
+        }
+    }
+
+    /// Writes the last `seconds` of buffered video to `path` as a raw
+    /// Annex-B stream, starting at the closest earlier keyframe. Returns
+    /// the number of frames written.
+    pub fn save(&self, path: &Path, seconds: u64) -> EngineResult<u64> {
+        let Some(newest) = self.gops.back().and_then(|g| g.last()).map(|f| f.qpc) else {
+            return Err(EngineError::Config("replay buffer is empty".into()));
+        };
+        let cutoff = newest - (seconds.max(1) as i64) * 10_000_000;
+        // Start at the first GOP whose keyframe is within the window, or
+        // the oldest one if the window exceeds what is buffered.
+        let start = self
+            .gops
+            .iter()
+            .position(|g| g.first().map(|f| f.qpc >= cutoff).unwrap_or(false))
+            .unwrap_or(0);
+
+        let file = File::create(path)
+            .map_err(|e| EngineError::Config(format!("cannot create {}: {e}", path.display())))?;
+        let mut writer = BufWriter::new(file);
+        let mut written = 0u64;
+        for gop in self.gops.iter().skip(start) {
+            for frame in gop {
+                writer
+                    .write_all(&frame.data)
+                    .map_err(|e| EngineError::Encode(format!("replay write: {e}")))?;
+                written += 1;
+            }
+        }
+        writer
+            .flush()
+            .map_err(|e| EngineError::Encode(format!("replay flush: {e}")))?;
+        Ok(written)
+    }
+
+    /// Exports the last `seconds` of buffered video to `path` as an
+    /// animated GIF (decode → downscale → palette encode). Decoding
+    /// starts at the closest earlier keyframe so the window's first frame
+    /// reconstructs correctly. Returns the number of GIF frames written.
+    pub fn export_clip(
+        &self,
+        path: &Path,
+        seconds: u64,
+        options: &crate::clip::ClipOptions,
+    ) -> EngineResult<u64> {
+        if path.extension().and_then(|e| e.to_str()) == Some("webp") {
+            return Err(EngineError::Config(
+                "webp clip export is not supported; use .gif".into(),
+            ));
+        }
+        let Some(newest) = self.gops.back().and_then(|g| g.last()).map(|f| f.qpc) else {
+            return Err(EngineError::Config("replay buffer is empty".into()));
+        };
+        let cutoff = newest - (seconds.max(1) as i64) * 10_000_000;
+        let start = self
+            .gops
+            .iter()
+            .position(|g| g.first().map(|f| f.qpc >= cutoff).unwrap_or(false))
+            .unwrap_or(0);
+        let frames: Vec<(&[u8], i64)> = self
+            .gops
+            .iter()
+            .skip(start)
+            .flatten()
+            .map(|f| (f.data.as_slice(), f.qpc))
+            .collect();
+        crate::clip::export_gif(path, &frames, cutoff, options)
+    }
+}
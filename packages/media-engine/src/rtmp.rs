@@ -0,0 +1,666 @@
+//! RTMP(S) output: muxes the encoded H.264 stream into FLV video tags and
+//! pushes it to a live ingest URL (Twitch, YouTube), next to — or instead
+//! of — the LiveKit transport.
+//!
+//! This is a minimal publishing client, not a general RTMP stack: plain
+//! handshake, AMF0 only, one outgoing video stream. Audio is not pushed:
+//! FLV has no Opus codec id and the engine doesn't ship an AAC encoder,
+//! and the major ingests accept video-only streams.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bitstream::h264::annex_b_units;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+
+/// Chunk size we announce for outgoing messages. Big enough that a video
+/// frame rarely needs more than a handful of continuation headers.
+const OUT_CHUNK_SIZE: u32 = 4096;
+/// Dial/handshake/command timeout.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One parsed `rtmp://` / `rtmps://` ingest URL. The last path segment is
+/// the stream key, everything before it is the application name — the
+/// layout Twitch (`/app/{key}`) and YouTube (`/live2/{key}`) both use.
+struct RtmpTarget {
+    tls: bool,
+    host: String,
+    port: u16,
+    app: String,
+    stream_key: String,
+    /// `scheme://host:port/app`, sent in the connect command.
+    tc_url: String,
+}
+
+fn parse_url(url: &str) -> EngineResult<RtmpTarget> {
+    let bad = |why: &str| EngineError::Config(format!("rtmpUrl {url}: {why}"));
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("rtmps://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("rtmp://") {
+        (false, rest)
+    } else {
+        return Err(bad("must start with rtmp:// or rtmps://"));
+    };
+    let (authority, path) = rest
+        .split_once('/')
+        .ok_or_else(|| bad("missing /app/streamKey path"))?;
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host.to_string(),
+            port.parse::<u16>().map_err(|_| bad("bad port"))?,
+        ),
+        None => (
+            authority.to_string(),
+            if tls { 443 } else { 1935 },
+        ),
+    };
+    if host.is_empty() {
+        return Err(bad("missing host"));
+    }
+    let (app, stream_key) = path
+        .rsplit_once('/')
+        .ok_or_else(|| bad("path must be /app/streamKey"))?;
+    if app.is_empty() || stream_key.is_empty() {
+        return Err(bad("path must be /app/streamKey"));
+    }
+    let scheme = if tls { "rtmps" } else { "rtmp" };
+    Ok(RtmpTarget {
+        tls,
+        tc_url: format!("{scheme}://{host}:{port}/{app}"),
+        host,
+        port,
+        app: app.to_string(),
+        stream_key: stream_key.to_string(),
+    })
+}
+
+/// The socket, with or without TLS. RTMPS uses system roots only; ingest
+/// endpoints have public certificates, so the signal TLS knobs don't apply.
+enum Transport {
+    Plain(TcpStream),
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Transport {
+    fn read_exact(&mut self, buf: &mut [u8]) -> EngineResult<()> {
+        let result = match self {
+            Transport::Plain(s) => s.read_exact(buf),
+            Transport::Tls(s) => s.read_exact(buf),
+        };
+        result.map_err(|e| EngineError::Transport(format!("rtmp read: {e}")))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> EngineResult<()> {
+        let result = match self {
+            Transport::Plain(s) => s.write_all(buf),
+            Transport::Tls(s) => s.write_all(buf),
+        };
+        result.map_err(|e| EngineError::Transport(format!("rtmp write: {e}")))
+    }
+}
+
+fn tls_connect(tcp: TcpStream, host: &str) -> EngineResult<Transport> {
+    let mut roots = rustls::RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs()
+        .map_err(|e| EngineError::Transport(format!("system root certs: {e}")))?;
+    for cert in native {
+        // Individual unparsable system certs are common; skip them.
+        let _ = roots.add(cert);
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::pki_types::ServerName::try_from(host.to_string())
+        .map_err(|e| EngineError::Transport(format!("rtmps server name: {e}")))?;
+    let conn = rustls::ClientConnection::new(Arc::new(config), name)
+        .map_err(|e| EngineError::Transport(format!("rtmps: {e}")))?;
+    Ok(Transport::Tls(Box::new(rustls::StreamOwned::new(conn, tcp))))
+}
+
+// ---- AMF0 encoding --------------------------------------------------------
+
+fn amf_string(out: &mut Vec<u8>, s: &str) {
+    out.push(0x02);
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn amf_number(out: &mut Vec<u8>, n: f64) {
+    out.push(0x00);
+    out.extend_from_slice(&n.to_be_bytes());
+}
+
+fn amf_null(out: &mut Vec<u8>) {
+    out.push(0x05);
+}
+
+fn amf_key(out: &mut Vec<u8>, key: &str) {
+    out.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    out.extend_from_slice(key.as_bytes());
+}
+
+fn amf_object_end(out: &mut Vec<u8>) {
+    out.extend_from_slice(&[0x00, 0x00, 0x09]);
+}
+
+// ---- AMF0 decoding (just enough to read command replies) ------------------
+
+/// The AMF0 values servers put in `_result`/`onStatus` replies. Everything
+/// else is skipped structurally.
+enum AmfValue {
+    Number(f64),
+    String(String),
+    Object(Vec<(String, AmfValue)>),
+    Other,
+}
+
+impl AmfValue {
+    fn property(&self, key: &str) -> Option<&AmfValue> {
+        match self {
+            AmfValue::Object(props) => props.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            AmfValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct AmfReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AmfReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> EngineResult<&'a [u8]> {
+        if self.pos + n > self.buf.len() {
+            return Err(EngineError::Transport("rtmp: truncated AMF value".into()));
+        }
+        let slice = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn read_value(&mut self) -> EngineResult<AmfValue> {
+        let marker = self.take(1)?[0];
+        match marker {
+            // Number.
+            0x00 => Ok(AmfValue::Number(f64::from_be_bytes(
+                self.take(8)?.try_into().unwrap(),
+            ))),
+            // Boolean.
+            0x01 => {
+                self.take(1)?;
+                Ok(AmfValue::Other)
+            }
+            // String.
+            0x02 => {
+                let len = u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as usize;
+                Ok(AmfValue::String(
+                    String::from_utf8_lossy(self.take(len)?).into_owned(),
+                ))
+            }
+            // Object, and ECMA array which is an object with a length prefix.
+            0x03 | 0x08 => {
+                if marker == 0x08 {
+                    self.take(4)?;
+                }
+                let mut props = Vec::new();
+                loop {
+                    let len = u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as usize;
+                    if len == 0 {
+                        // Expect the object-end marker.
+                        self.take(1)?;
+                        break;
+                    }
+                    let key = String::from_utf8_lossy(self.take(len)?).into_owned();
+                    props.push((key, self.read_value()?));
+                }
+                Ok(AmfValue::Object(props))
+            }
+            // Null / undefined.
+            0x05 | 0x06 => Ok(AmfValue::Other),
+            other => Err(EngineError::Transport(format!(
+                "rtmp: unsupported AMF0 marker 0x{other:02x}"
+            ))),
+        }
+    }
+}
+
+// ---- Chunk stream ---------------------------------------------------------
+
+/// Per-chunk-stream header state carried between chunks, so fmt 1–3
+/// headers can inherit the fields they omit.
+#[derive(Clone, Copy, Default)]
+struct ChunkState {
+    timestamp: u32,
+    length: u32,
+    type_id: u8,
+    stream_id: u32,
+}
+
+/// One assembled RTMP message.
+struct Message {
+    type_id: u8,
+    payload: Vec<u8>,
+}
+
+/// Pushes the session's encoded H.264 to one RTMP(S) ingest. Created
+/// connected and publishing; `write_frame` then maps each access unit to
+/// an FLV video tag.
+pub struct RtmpSink {
+    transport: Transport,
+    in_chunk_size: u32,
+    /// Chunk reader state keyed by chunk stream id.
+    states: HashMap<u16, ChunkState>,
+    /// Partially assembled message payloads keyed by chunk stream id.
+    partial: HashMap<u16, Vec<u8>>,
+    stream_id: u32,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    sequence_header_sent: bool,
+    /// QPC of the first written frame; timestamps are milliseconds from it.
+    anchor_qpc: Option<i64>,
+    frames_written: u64,
+}
+
+impl RtmpSink {
+    /// Dials the ingest and runs the full publish setup: handshake,
+    /// connect, createStream, publish, metadata. Returns once the server
+    /// has confirmed `NetStream.Publish.Start`.
+    pub fn connect(url: &str, width: u32, height: u32, fps: u32) -> EngineResult<Self> {
+        let target = parse_url(url)?;
+        let tcp = TcpStream::connect((target.host.as_str(), target.port))
+            .map_err(|e| EngineError::Transport(format!("rtmp dial {}: {e}", target.host)))?;
+        tcp.set_read_timeout(Some(IO_TIMEOUT)).ok();
+        tcp.set_write_timeout(Some(IO_TIMEOUT)).ok();
+        tcp.set_nodelay(true).ok();
+        let transport = if target.tls {
+            tls_connect(tcp, &target.host)?
+        } else {
+            Transport::Plain(tcp)
+        };
+        let mut sink = Self {
+            transport,
+            in_chunk_size: 128,
+            states: HashMap::new(),
+            partial: HashMap::new(),
+            stream_id: 0,
+            sps: None,
+            pps: None,
+            sequence_header_sent: false,
+            anchor_qpc: None,
+            frames_written: 0,
+        };
+        sink.handshake()?;
+
+        // Announce our chunk size, then run the command sequence. Each
+        // command waits for its reply so failures surface with the step
+        // that caused them.
+        sink.write_chunk(2, 1, 0, 0, &OUT_CHUNK_SIZE.to_be_bytes())?;
+        let mut connect = Vec::new();
+        amf_string(&mut connect, "connect");
+        amf_number(&mut connect, 1.0);
+        connect.push(0x03);
+        amf_key(&mut connect, "app");
+        amf_string(&mut connect, &target.app);
+        amf_key(&mut connect, "type");
+        amf_string(&mut connect, "nonprivate");
+        amf_key(&mut connect, "flashVer");
+        amf_string(&mut connect, "FMLE/3.0");
+        amf_key(&mut connect, "tcUrl");
+        amf_string(&mut connect, &target.tc_url);
+        amf_object_end(&mut connect);
+        sink.write_chunk(3, 20, 0, 0, &connect)?;
+        sink.wait_result(1.0, "connect")?;
+
+        let mut create = Vec::new();
+        amf_string(&mut create, "createStream");
+        amf_number(&mut create, 2.0);
+        amf_null(&mut create);
+        sink.write_chunk(3, 20, 0, 0, &create)?;
+        let values = sink.wait_result(2.0, "createStream")?;
+        sink.stream_id = match values.get(3) {
+            Some(AmfValue::Number(id)) => *id as u32,
+            _ => {
+                return Err(EngineError::Transport(
+                    "rtmp: createStream reply carried no stream id".into(),
+                ))
+            }
+        };
+
+        let mut publish = Vec::new();
+        amf_string(&mut publish, "publish");
+        amf_number(&mut publish, 3.0);
+        amf_null(&mut publish);
+        amf_string(&mut publish, &target.stream_key);
+        amf_string(&mut publish, "live");
+        let stream_id = sink.stream_id;
+        sink.write_chunk(4, 20, stream_id, 0, &publish)?;
+        sink.wait_publish_start()?;
+
+        // Metadata tells the ingest what is coming before the first tag.
+        let mut meta = Vec::new();
+        amf_string(&mut meta, "@setDataFrame");
+        amf_string(&mut meta, "onMetaData");
+        // ECMA array.
+        meta.push(0x08);
+        meta.extend_from_slice(&4u32.to_be_bytes());
+        amf_key(&mut meta, "width");
+        amf_number(&mut meta, width as f64);
+        amf_key(&mut meta, "height");
+        amf_number(&mut meta, height as f64);
+        amf_key(&mut meta, "framerate");
+        amf_number(&mut meta, fps as f64);
+        amf_key(&mut meta, "videocodecid");
+        amf_number(&mut meta, 7.0);
+        amf_object_end(&mut meta);
+        sink.write_chunk(4, 18, stream_id, 0, &meta)?;
+
+        tracing::info!("rtmp publishing to {}", target.tc_url);
+        Ok(sink)
+    }
+
+    /// Plain handshake: C0/C1, read S0/S1/S2, echo S1 back as C2. The
+    /// digest variant is only required for flash player clients.
+    fn handshake(&mut self) -> EngineResult<()> {
+        let mut c0c1 = vec![0u8; 1537];
+        c0c1[0] = 3;
+        // C1 after the 8-byte time/zero prefix is arbitrary by spec.
+        for (i, byte) in c0c1[9..].iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        self.transport.write_all(&c0c1)?;
+        let mut s0s1s2 = vec![0u8; 1 + 1536 + 1536];
+        self.transport.read_exact(&mut s0s1s2)?;
+        if s0s1s2[0] != 3 {
+            return Err(EngineError::Transport(format!(
+                "rtmp: server offered protocol version {}",
+                s0s1s2[0]
+            )));
+        }
+        let c2 = s0s1s2[1..1537].to_vec();
+        self.transport.write_all(&c2)?;
+        Ok(())
+    }
+
+    /// Writes one message as a fmt-0 chunk plus fmt-3 continuations.
+    fn write_chunk(
+        &mut self,
+        csid: u8,
+        type_id: u8,
+        stream_id: u32,
+        timestamp: u32,
+        payload: &[u8],
+    ) -> EngineResult<()> {
+        let mut header = Vec::with_capacity(16);
+        header.push(csid & 0x3f);
+        // Timestamps past 24 bits move to the extended field (streams
+        // longer than ~4.6 hours).
+        let extended = timestamp >= 0xff_ffff;
+        let header_ts = if extended { 0xff_ffff } else { timestamp };
+        header.extend_from_slice(&header_ts.to_be_bytes()[1..]);
+        header.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        header.push(type_id);
+        // Message stream id is the one little-endian field in RTMP.
+        header.extend_from_slice(&stream_id.to_le_bytes());
+        if extended {
+            header.extend_from_slice(&timestamp.to_be_bytes());
+        }
+        self.transport.write_all(&header)?;
+        let mut offset = 0;
+        while offset < payload.len() {
+            let end = (offset + OUT_CHUNK_SIZE as usize).min(payload.len());
+            self.transport.write_all(&payload[offset..end])?;
+            offset = end;
+            if offset < payload.len() {
+                self.transport.write_all(&[0xc0 | (csid & 0x3f)])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads chunks until one full message is assembled, servicing
+    /// protocol control messages along the way.
+    fn read_message(&mut self) -> EngineResult<Message> {
+        loop {
+            let mut basic = [0u8; 1];
+            self.transport.read_exact(&mut basic)?;
+            let fmt = basic[0] >> 6;
+            let csid = match basic[0] & 0x3f {
+                0 => {
+                    let mut b = [0u8; 1];
+                    self.transport.read_exact(&mut b)?;
+                    b[0] as u16 + 64
+                }
+                1 => {
+                    let mut b = [0u8; 2];
+                    self.transport.read_exact(&mut b)?;
+                    u16::from_le_bytes(b) + 64
+                }
+                id => id as u16,
+            };
+            let mut state = self.states.get(&csid).copied().unwrap_or_default();
+            match fmt {
+                0 => {
+                    let mut h = [0u8; 11];
+                    self.transport.read_exact(&mut h)?;
+                    state.timestamp = u32::from_be_bytes([0, h[0], h[1], h[2]]);
+                    state.length = u32::from_be_bytes([0, h[3], h[4], h[5]]);
+                    state.type_id = h[6];
+                    state.stream_id = u32::from_le_bytes([h[7], h[8], h[9], h[10]]);
+                }
+                1 => {
+                    let mut h = [0u8; 7];
+                    self.transport.read_exact(&mut h)?;
+                    state.timestamp = state
+                        .timestamp
+                        .wrapping_add(u32::from_be_bytes([0, h[0], h[1], h[2]]));
+                    state.length = u32::from_be_bytes([0, h[3], h[4], h[5]]);
+                    state.type_id = h[6];
+                }
+                2 => {
+                    let mut h = [0u8; 3];
+                    self.transport.read_exact(&mut h)?;
+                    state.timestamp = state
+                        .timestamp
+                        .wrapping_add(u32::from_be_bytes([0, h[0], h[1], h[2]]));
+                }
+                _ => {}
+            }
+            // Extended timestamp (0xffffff sentinel) is never hit during
+            // the short command exchange we read; skip it if present.
+            if state.timestamp == 0xff_ffff && fmt == 0 {
+                let mut ext = [0u8; 4];
+                self.transport.read_exact(&mut ext)?;
+            }
+            self.states.insert(csid, state);
+
+            let mut chunk = self.partial.remove(&csid).unwrap_or_default();
+            let want = (state.length as usize).saturating_sub(chunk.len());
+            let take = want.min(self.in_chunk_size as usize);
+            let start = chunk.len();
+            chunk.resize(start + take, 0);
+            self.transport.read_exact(&mut chunk[start..])?;
+            if chunk.len() < state.length as usize {
+                self.partial.insert(csid, chunk);
+                continue;
+            }
+
+            match state.type_id {
+                // Set chunk size.
+                1 if chunk.len() >= 4 => {
+                    self.in_chunk_size =
+                        u32::from_be_bytes(chunk[..4].try_into().unwrap()).max(1);
+                }
+                // User control: answer ping requests (event 6 → 7), ignore
+                // the rest.
+                4 if chunk.len() >= 2 && u16::from_be_bytes([chunk[0], chunk[1]]) == 6 => {
+                    let mut pong = chunk.clone();
+                    pong[1] = 7;
+                    self.write_chunk(2, 4, 0, 0, &pong)?;
+                }
+                // Window ack size / peer bandwidth: nothing to do, we
+                // never send enough unacknowledged data to be throttled.
+                5 | 6 | 4 | 1 => {}
+                _ => {
+                    return Ok(Message {
+                        type_id: state.type_id,
+                        payload: chunk,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Waits for the `_result` answering transaction `txn`, returning its
+    /// decoded AMF values. `_error` and connection loss fail the setup.
+    fn wait_result(&mut self, txn: f64, what: &str) -> EngineResult<Vec<AmfValue>> {
+        loop {
+            let message = self.read_message()?;
+            if message.type_id != 20 {
+                continue;
+            }
+            let values = decode_command(&message.payload)?;
+            match (values.first().and_then(AmfValue::as_str), values.get(1)) {
+                (Some("_result"), Some(AmfValue::Number(n))) if *n == txn => return Ok(values),
+                (Some("_error"), _) => {
+                    return Err(EngineError::Transport(format!(
+                        "rtmp {what} rejected: {}",
+                        status_description(&values)
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits for `onStatus` with `NetStream.Publish.Start`.
+    fn wait_publish_start(&mut self) -> EngineResult<()> {
+        loop {
+            let message = self.read_message()?;
+            if message.type_id != 20 {
+                continue;
+            }
+            let values = decode_command(&message.payload)?;
+            if values.first().and_then(AmfValue::as_str) != Some("onStatus") {
+                continue;
+            }
+            let code = values
+                .iter()
+                .find_map(|v| v.property("code"))
+                .and_then(AmfValue::as_str)
+                .unwrap_or("");
+            if code == "NetStream.Publish.Start" {
+                return Ok(());
+            }
+            if code.starts_with("NetStream.Publish") || code.ends_with("Failed") {
+                return Err(EngineError::Transport(format!(
+                    "rtmp publish rejected: {}",
+                    status_description(&values)
+                )));
+            }
+        }
+    }
+
+    /// Muxes one encoded access unit into an FLV video tag. The first
+    /// keyframe also emits the AVC sequence header built from its SPS/PPS.
+    pub fn write_frame(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        let anchor = *self.anchor_qpc.get_or_insert(frame.capture_qpc);
+        // 100 ns ticks → milliseconds.
+        let timestamp = ((frame.capture_qpc - anchor).max(0) / 10_000) as u32;
+
+        let mut body = Vec::with_capacity(frame.data.len() + 64);
+        for nal in annex_b_units(&frame.data) {
+            match nal[0] & 0x1f {
+                7 => self.sps = Some(nal.to_vec()),
+                8 => self.pps = Some(nal.to_vec()),
+                // Access unit delimiters are Annex-B framing; FLV carries
+                // length-prefixed NALUs instead.
+                9 => {}
+                _ => {
+                    body.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    body.extend_from_slice(nal);
+                }
+            }
+        }
+
+        if !self.sequence_header_sent {
+            let (Some(sps), Some(pps)) = (self.sps.as_deref(), self.pps.as_deref()) else {
+                // Delta frames before the first parameter sets are useless
+                // to the ingest; wait for a keyframe.
+                return Ok(());
+            };
+            let mut tag = vec![0x17, 0x00, 0x00, 0x00, 0x00];
+            // AVCDecoderConfigurationRecord.
+            tag.extend_from_slice(&[1, sps[1], sps[2], sps[3], 0xff, 0xe1]);
+            tag.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+            tag.extend_from_slice(sps);
+            tag.push(1);
+            tag.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+            tag.extend_from_slice(pps);
+            let stream_id = self.stream_id;
+            self.write_chunk(4, 9, stream_id, timestamp, &tag)?;
+            self.sequence_header_sent = true;
+        }
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        let frame_type = if frame.is_keyframe { 0x17 } else { 0x27 };
+        let mut tag = Vec::with_capacity(body.len() + 5);
+        // Frame type + AVC codec id, NALU packet, zero composition time
+        // (the encoder emits no B-frames).
+        tag.push(frame_type);
+        tag.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        tag.extend_from_slice(&body);
+        let stream_id = self.stream_id;
+        self.write_chunk(4, 9, stream_id, timestamp, &tag)?;
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    /// Tears the stream down politely; the ingest ends the broadcast
+    /// immediately instead of waiting out its timeout.
+    pub fn finish(mut self) -> EngineResult<u64> {
+        let mut delete = Vec::new();
+        amf_string(&mut delete, "deleteStream");
+        amf_number(&mut delete, 0.0);
+        amf_null(&mut delete);
+        amf_number(&mut delete, self.stream_id as f64);
+        self.write_chunk(3, 20, 0, 0, &delete)?;
+        Ok(self.frames_written)
+    }
+}
+
+fn decode_command(payload: &[u8]) -> EngineResult<Vec<AmfValue>> {
+    let mut reader = AmfReader::new(payload);
+    let mut values = Vec::new();
+    while reader.pos < reader.buf.len() {
+        values.push(reader.read_value()?);
+    }
+    Ok(values)
+}
+
+/// Pulls the human-readable description out of a status/error reply, for
+/// error messages.
+fn status_description(values: &[AmfValue]) -> String {
+    values
+        .iter()
+        .find_map(|v| v.property("description").or_else(|| v.property("code")))
+        .and_then(AmfValue::as_str)
+        .unwrap_or("no description")
+        .to_string()
+}
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Capacity of the rolling sample windows — about five seconds at 60 fps.
+const SAMPLE_WINDOW: usize = 300;
+
+/// Nearest-rank percentile of an ascending-sorted, non-empty slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let idx = ((sorted.len() as f64 * p).ceil() as usize).max(1) - 1;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+fn push_sample(window: &mut VecDeque<f64>, value: f64) {
+    if window.len() == SAMPLE_WINDOW {
+        window.pop_front();
+    }
+    window.push_back(value);
+}
+
+/// Rolling statistics for the current session, updated by the worker threads
+/// and snapshotted once per second for the `on_stats` callback.
+#[derive(Debug, Clone, Default)]
+pub struct EngineStats {
+    pub frames_captured: u64,
+    pub frames_encoded: u64,
+    pub frames_dropped: u64,
+    pub keyframes: u64,
+    pub bytes_sent: u64,
+    pub packets_sent: u64,
+    /// Average encode time over the last stats interval, in milliseconds.
+    pub avg_encode_ms: f64,
+    /// Encoded frames per second over the last stats interval.
+    pub fps: f64,
+    /// Outgoing bitrate over the last stats interval, in kbit/s.
+    pub bitrate_kbps: f64,
+    /// Average end-to-end latency — WGC capture timestamp to RTP write —
+    /// over the rolling window, in milliseconds.
+    pub avg_latency_ms: f64,
+    /// 95th percentile of the same latency window, in milliseconds.
+    pub p95_latency_ms: f64,
+    /// Encode-time percentiles over the rolling window, in milliseconds.
+    /// Percentiles catch encoder regressions an average smooths over.
+    pub p50_encode_ms: f64,
+    pub p95_encode_ms: f64,
+    pub p99_encode_ms: f64,
+    /// Keyframes produced by the primary encoder, with their total size;
+    /// the stats emitter derives average keyframe size per interval.
+    pub keyframes_encoded: u64,
+    pub keyframe_bytes: u64,
+    /// Average keyframe size over the last stats interval, in bytes.
+    pub avg_keyframe_bytes: f64,
+    /// Friendly name of the active encoder MFT, e.g. "NVIDIA H.264 Encoder
+    /// MFT"; empty until the encode pipeline comes up.
+    pub encoder_name: String,
+    /// "nvidia", "amd", "intel", or "unknown", inferred from the name.
+    pub encoder_vendor: String,
+    /// Whether a hardware MFT is encoding; false on the openh264 CPU
+    /// fallback (no MFT activated on this machine).
+    pub encoder_hardware: bool,
+    /// Frames stay on the GPU end to end (D3D11 upload → NV12 → MFT).
+    /// False on the CPU fallback, which never uploads at all.
+    pub encoder_d3d: bool,
+    /// Process CPU usage over the last stats interval, as a percentage of
+    /// all logical cores (100 = every core saturated by this process).
+    /// Windows only; zero elsewhere and on the first interval.
+    pub cpu_percent: f64,
+    /// GPU engine utilization attributed to this process over the last
+    /// interval, in percent (PDH "GPU Engine" counters, Windows only).
+    pub gpu_percent: f64,
+    /// Dedicated video memory in use by this process, in megabytes
+    /// (DXGI, Windows only).
+    pub gpu_memory_mb: f64,
+    /// Rolling sample windows feeding the percentile fields above; not
+    /// exposed past the stats snapshot.
+    pub latency_samples: VecDeque<f64>,
+    pub encode_samples: VecDeque<f64>,
+}
+
+impl EngineStats {
+    /// Records one frame's capture→send latency.
+    pub fn push_latency(&mut self, ms: f64) {
+        push_sample(&mut self.latency_samples, ms);
+    }
+
+    /// Records one frame's encode time.
+    pub fn push_encode_ms(&mut self, ms: f64) {
+        push_sample(&mut self.encode_samples, ms);
+    }
+
+    /// Fills the latency and encode-time percentile fields from the
+    /// current windows. Called on the snapshot, not per frame, so the hot
+    /// paths only pay for a push.
+    pub fn compute_percentiles(&mut self) {
+        match sorted(&self.latency_samples) {
+            Some(sorted) => {
+                self.avg_latency_ms = sorted.iter().sum::<f64>() / sorted.len() as f64;
+                self.p95_latency_ms = percentile(&sorted, 0.95);
+            }
+            None => {
+                self.avg_latency_ms = 0.0;
+                self.p95_latency_ms = 0.0;
+            }
+        }
+        match sorted(&self.encode_samples) {
+            Some(sorted) => {
+                self.p50_encode_ms = percentile(&sorted, 0.50);
+                self.p95_encode_ms = percentile(&sorted, 0.95);
+                self.p99_encode_ms = percentile(&sorted, 0.99);
+            }
+            None => {
+                self.p50_encode_ms = 0.0;
+                self.p95_encode_ms = 0.0;
+                self.p99_encode_ms = 0.0;
+            }
+        }
+    }
+}
+
+fn sorted(window: &VecDeque<f64>) -> Option<Vec<f64>> {
+    if window.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<f64> = window.iter().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    Some(sorted)
+}
+
+/// Shared handle threads use to bump counters without further plumbing.
+pub type SharedStats = Arc<Mutex<EngineStats>>;
+
+pub fn new_shared() -> SharedStats {
+    Arc::new(Mutex::new(EngineStats::default()))
+}
@@ -0,0 +1,206 @@
+//! Process CPU and GPU utilization sampling for the stats emitter, so the
+//! client can warn when screen sharing is saturating the machine.
+//!
+//! CPU comes from `GetProcessTimes` deltas normalized across logical
+//! cores; GPU engine utilization from the PDH "GPU Engine" counters for
+//! this PID; VRAM from `IDXGIAdapter3::QueryVideoMemoryInfo`. Everything
+//! degrades to zero rather than failing — utilization is advisory.
+
+/// One utilization sample, covering the interval since the previous call.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utilization {
+    /// Process CPU usage as a percentage of all logical cores
+    /// (100 = every core saturated by this process).
+    pub cpu_percent: f64,
+    /// GPU engine utilization attributed to this process, in percent.
+    pub gpu_percent: f64,
+    /// Dedicated video memory in use by this process, in megabytes.
+    pub gpu_memory_mb: f64,
+}
+
+#[cfg(windows)]
+pub use windows_impl::SystemMonitor;
+
+#[cfg(windows)]
+mod windows_impl {
+    use super::Utilization;
+    use std::time::Instant;
+
+    use windows::core::{Interface, PCWSTR};
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::Graphics::Dxgi::{
+        CreateDXGIFactory1, IDXGIAdapter3, IDXGIFactory1, DXGI_MEMORY_SEGMENT_GROUP_LOCAL,
+        DXGI_QUERY_VIDEO_MEMORY_INFO,
+    };
+    use windows::Win32::System::Performance::{
+        PdhAddEnglishCounterW, PdhCollectQueryData, PdhGetFormattedCounterArrayW, PdhOpenQueryW,
+        PDH_FMT_COUNTERVALUE_ITEM_W, PDH_FMT_DOUBLE,
+    };
+    use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+    /// Samples utilization for the current process. Create once per session
+    /// and call [`SystemMonitor::sample`] at the stats cadence; the first
+    /// interval reports zero CPU/GPU because deltas need two observations.
+    pub struct SystemMonitor {
+        cores: f64,
+        last_cpu_100ns: u64,
+        last_sample: Instant,
+        pdh_query: isize,
+        pdh_counter: isize,
+        adapter: Option<IDXGIAdapter3>,
+    }
+
+    impl SystemMonitor {
+        pub fn new() -> Self {
+            let cores = std::thread::available_parallelism()
+                .map(|n| n.get() as f64)
+                .unwrap_or(1.0);
+
+            // Wildcard over this PID's engines; PDH sums nothing itself, so
+            // sample() adds the per-engine instances up.
+            let mut pdh_query = 0isize;
+            let mut pdh_counter = 0isize;
+            unsafe {
+                if PdhOpenQueryW(PCWSTR::null(), 0, &mut pdh_query) == 0 {
+                    let path: Vec<u16> = format!(
+                        "\\GPU Engine(pid_{}*)\\Utilization Percentage\0",
+                        std::process::id()
+                    )
+                    .encode_utf16()
+                    .collect();
+                    if PdhAddEnglishCounterW(
+                        pdh_query,
+                        PCWSTR::from_raw(path.as_ptr()),
+                        0,
+                        &mut pdh_counter,
+                    ) != 0
+                    {
+                        pdh_counter = 0;
+                    }
+                    // Prime the query; the first real collect needs a baseline.
+                    let _ = PdhCollectQueryData(pdh_query);
+                }
+            }
+
+            let adapter = unsafe {
+                CreateDXGIFactory1::<IDXGIFactory1>()
+                    .and_then(|factory| factory.EnumAdapters1(0))
+                    .ok()
+                    .and_then(|adapter| adapter.cast::<IDXGIAdapter3>().ok())
+            };
+
+            Self {
+                cores,
+                last_cpu_100ns: process_cpu_100ns().unwrap_or(0),
+                last_sample: Instant::now(),
+                pdh_query,
+                pdh_counter,
+                adapter,
+            }
+        }
+
+        pub fn sample(&mut self) -> Utilization {
+            let elapsed = self.last_sample.elapsed().as_secs_f64();
+            self.last_sample = Instant::now();
+
+            let mut util = Utilization::default();
+            if let Some(cpu_100ns) = process_cpu_100ns() {
+                if elapsed > 0.0 {
+                    let delta_s = cpu_100ns.saturating_sub(self.last_cpu_100ns) as f64 / 1e7;
+                    util.cpu_percent = delta_s / elapsed / self.cores * 100.0;
+                }
+                self.last_cpu_100ns = cpu_100ns;
+            }
+            util.gpu_percent = self.gpu_percent().unwrap_or(0.0);
+            if let Some(adapter) = self.adapter.as_ref() {
+                let mut info = DXGI_QUERY_VIDEO_MEMORY_INFO::default();
+                if unsafe {
+                    adapter.QueryVideoMemoryInfo(0, DXGI_MEMORY_SEGMENT_GROUP_LOCAL, &mut info)
+                }
+                .is_ok()
+                {
+                    util.gpu_memory_mb = info.CurrentUsage as f64 / (1024.0 * 1024.0);
+                }
+            }
+            util
+        }
+
+        /// Sums the PDH "GPU Engine" utilization instances for this PID.
+        fn gpu_percent(&self) -> Option<f64> {
+            if self.pdh_counter == 0 {
+                return None;
+            }
+            unsafe {
+                if PdhCollectQueryData(self.pdh_query) != 0 {
+                    return None;
+                }
+                let mut buffer_len = 0u32;
+                let mut item_count = 0u32;
+                // Two-call pattern: first fetch the required buffer size.
+                let _ = PdhGetFormattedCounterArrayW(
+                    self.pdh_counter,
+                    PDH_FMT_DOUBLE,
+                    &mut buffer_len,
+                    &mut item_count,
+                    None,
+                );
+                if buffer_len == 0 {
+                    return None;
+                }
+                let mut buffer = vec![0u8; buffer_len as usize];
+                let items = buffer.as_mut_ptr() as *mut PDH_FMT_COUNTERVALUE_ITEM_W;
+                if PdhGetFormattedCounterArrayW(
+                    self.pdh_counter,
+                    PDH_FMT_DOUBLE,
+                    &mut buffer_len,
+                    &mut item_count,
+                    Some(items),
+                ) != 0
+                {
+                    return None;
+                }
+                let items = std::slice::from_raw_parts(items, item_count as usize);
+                Some(items.iter().map(|i| i.FmtValue.Anonymous.doubleValue).sum())
+            }
+        }
+    }
+
+    /// Total kernel + user CPU time of this process, in 100 ns ticks.
+    fn process_cpu_100ns() -> Option<u64> {
+        let mut creation = FILETIME::default();
+        let mut exit = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        unsafe {
+            GetProcessTimes(
+                GetCurrentProcess(),
+                &mut creation,
+                &mut exit,
+                &mut kernel,
+                &mut user,
+            )
+            .ok()?;
+        }
+        Some(filetime_100ns(kernel) + filetime_100ns(user))
+    }
+
+    fn filetime_100ns(ft: FILETIME) -> u64 {
+        (ft.dwHighDateTime as u64) << 32 | ft.dwLowDateTime as u64
+    }
+}
+
+/// Utilization sampling is only implemented on Windows; everywhere else
+/// the fields stay zero.
+#[cfg(not(windows))]
+pub struct SystemMonitor;
+
+#[cfg(not(windows))]
+impl SystemMonitor {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn sample(&mut self) -> Utilization {
+        Utilization::default()
+    }
+}
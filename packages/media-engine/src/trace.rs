@@ -0,0 +1,106 @@
+//! Pipeline profiling spans, dumpable as a Chrome trace (load in
+//! `chrome://tracing` or Perfetto). Capture, convert, encode, packetize,
+//! and send each record a span per frame, so a frame-time spike can be
+//! attributed to a specific stage instead of guessed at.
+//!
+//! Collection is process-global and armed per session via `trace_path`;
+//! one session traces at a time. Disabled, a span is two atomic loads —
+//! cheap enough to leave in the hot paths permanently.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::error::{EngineError, EngineResult};
+
+/// Event cap so a forgotten trace flag can't eat memory without bound —
+/// roughly ten minutes of a five-span 60 fps pipeline.
+const MAX_EVENTS: usize = 1 << 20;
+
+struct TraceEvent {
+    name: &'static str,
+    /// Microseconds since the trace epoch.
+    ts_us: u64,
+    dur_us: u64,
+    tid: u64,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EVENTS: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Stable per-thread id for the trace's rows; `std::thread::ThreadId`
+    /// has no stable integer form.
+    static TID: u64 = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+}
+
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Arms collection, clearing anything a previous session left behind.
+pub fn start() {
+    epoch();
+    EVENTS.lock().unwrap().clear();
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// An in-flight stage span; records itself on drop when tracing is armed.
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+    live: bool,
+}
+
+/// Opens a span for one pipeline stage. The `name` becomes the event name
+/// in the trace viewer.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: Instant::now(),
+        live: ENABLED.load(Ordering::Relaxed),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.live || !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let ts_us = self.start.duration_since(epoch()).as_micros() as u64;
+        let dur_us = self.start.elapsed().as_micros() as u64;
+        let mut events = EVENTS.lock().unwrap();
+        if events.len() < MAX_EVENTS {
+            events.push(TraceEvent {
+                name: self.name,
+                ts_us,
+                dur_us,
+                tid: TID.with(|t| *t),
+            });
+        }
+    }
+}
+
+/// Disarms collection and writes the collected events to `path` in Chrome
+/// trace event format. Returns the number of events written.
+pub fn stop_and_write(path: &Path) -> EngineResult<u64> {
+    ENABLED.store(false, Ordering::Relaxed);
+    let events = std::mem::take(&mut *EVENTS.lock().unwrap());
+    let mut json = String::from("{\"traceEvents\":[");
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&format!(
+            "{{\"name\":\"{}\",\"cat\":\"pipeline\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+            event.name, event.tid, event.ts_us, event.dur_us
+        ));
+    }
+    json.push_str("]}");
+    std::fs::write(path, json)
+        .map_err(|e| EngineError::Config(format!("cannot write {}: {e}", path.display())))?;
+    Ok(events.len() as u64)
+}
@@ -0,0 +1,17 @@
+//! LiveKit-specific wire formats that aren't protobuf.
+
+use serde::{Deserialize, Serialize};
+
+/// The JSON body of a trickle ICE message (`TrickleRequest.candidate_init`),
+/// matching the W3C `RTCIceCandidateInit` dictionary LiveKit uses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidateInit {
+    pub candidate: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdp_mid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdp_m_line_index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_fragment: Option<String>,
+}
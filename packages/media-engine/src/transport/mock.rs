@@ -0,0 +1,65 @@
+//! In-memory [`Transport`] for headless tests. Records every frame it is
+//! handed and can simulate the two behaviours engine logic reacts to —
+//! keyframe requests and disconnects — on a deterministic schedule.
+
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+use crate::transport::Transport;
+
+/// Transport double that keeps frames instead of sending them.
+#[derive(Default)]
+pub struct MockTransport {
+    sent: Vec<EncodedFrame>,
+    /// Request a keyframe after every n-th delivered frame.
+    keyframe_every: Option<u64>,
+    /// Fail delivery once this many frames have been accepted.
+    disconnect_after: Option<u64>,
+    pending_keyframe: bool,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulates a subscriber issuing a PLI after every `n` frames.
+    pub fn request_keyframe_every(mut self, n: u64) -> Self {
+        self.keyframe_every = Some(n.max(1));
+        self
+    }
+
+    /// Simulates the connection dropping after `n` delivered frames; the
+    /// next delivery fails like a WebRTC disconnect would.
+    pub fn disconnect_after(mut self, n: u64) -> Self {
+        self.disconnect_after = Some(n);
+        self
+    }
+
+    /// Every frame delivered so far, in order.
+    pub fn sent(&self) -> &[EncodedFrame] {
+        &self.sent
+    }
+}
+
+impl Transport for MockTransport {
+    fn send_video(&mut self, frame: &EncodedFrame) -> EngineResult<()> {
+        if self
+            .disconnect_after
+            .is_some_and(|n| self.sent.len() as u64 >= n)
+        {
+            return Err(EngineError::Transport("mock transport disconnected".into()));
+        }
+        self.sent.push(frame.clone());
+        if self
+            .keyframe_every
+            .is_some_and(|n| self.sent.len() as u64 % n == 0)
+        {
+            self.pending_keyframe = true;
+        }
+        Ok(())
+    }
+
+    fn poll_keyframe_request(&mut self) -> bool {
+        std::mem::take(&mut self.pending_keyframe)
+    }
+}
@@ -0,0 +1,840 @@
+//! LiveKit publishing: signal WebSocket + str0m WebRTC, driven on a
+//! dedicated thread with its own single-threaded tokio runtime.
+
+pub mod e2ee;
+pub mod livekit;
+pub mod mock;
+pub mod rtp_out;
+pub mod signal;
+pub mod stun;
+pub mod tls;
+
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use str0m::change::SdpOffer;
+use str0m::format::Codec;
+use str0m::media::{Direction, MediaKind, MediaTime, Mid};
+use str0m::net::{Protocol, Receive};
+use str0m::{Candidate, Event, Input, Output, Rtc};
+
+use crate::audio::AudioPacket;
+use crate::config::ScreenShareConfig;
+use crate::encode::EncodedFrame;
+use crate::engine::{
+    record_stop_reason, ConnectedInfo, ConnectionState, EngineCallbacks, PublishControl,
+    RoomEvent, StartupTracker, StopReason, StopReasonCell,
+};
+use crate::error::{EngineError, EngineResult};
+use crate::stats::SharedStats;
+use livekit::IceCandidateInit;
+use signal::{SignalClient, SignalEvent};
+
+const VIDEO_CLOCK_RATE: u64 = 90_000;
+
+/// The encode loop's view of a transport: somewhere to hand encoded
+/// frames, plus the keyframe backchannel. The WebRTC session implements
+/// this contract implicitly inside [`transport_thread`]; the trait exists
+/// so [`mock::MockTransport`] can stand in for it and engine logic can be
+/// exercised headlessly in CI, without a LiveKit server or a screen.
+pub trait Transport: Send {
+    /// Delivers one encoded access unit. An error means the transport is
+    /// gone and the session should stop, like a WebRTC disconnect.
+    fn send_video(&mut self, frame: &EncodedFrame) -> EngineResult<()>;
+
+    /// True when the far side wants a keyframe; polling clears the flag,
+    /// mirroring how str0m surfaces `KeyframeRequest` events.
+    fn poll_keyframe_request(&mut self) -> bool;
+}
+
+/// Forwards encoded frames from the encode loop into a [`Transport`] until
+/// the channel closes, `stop` is set, or the transport fails — the same
+/// forwarding contract the WebRTC send loop implements, minus the
+/// networking. Keyframe requests propagate through `keyframe_request`
+/// exactly as they do from the real transport.
+pub fn drive(
+    transport: &mut dyn Transport,
+    frame_rx: Receiver<EncodedFrame>,
+    keyframe_request: &AtomicBool,
+    stop: &AtomicBool,
+) -> EngineResult<()> {
+    while !stop.load(Ordering::SeqCst) {
+        let frame = match frame_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(frame) => frame,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        transport.send_video(&frame)?;
+        if transport.poll_keyframe_request() {
+            keyframe_request.store(true, Ordering::SeqCst);
+        }
+    }
+    Ok(())
+}
+
+/// Everything the transport needs to publish the optional camera track
+/// alongside the screen share on the same connection.
+pub struct CameraPublish {
+    pub frame_rx: Receiver<EncodedFrame>,
+    pub keyframe_request: Arc<AtomicBool>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Runs the whole transport lifetime: signal join, publish negotiation, and
+/// the RTP send loop. Called from the engine's transport thread.
+pub fn transport_thread(
+    config: ScreenShareConfig,
+    frame_rx: Receiver<EncodedFrame>,
+    audio_rx: Option<Receiver<AudioPacket>>,
+    camera: Option<CameraPublish>,
+    keyframe_request: Arc<AtomicBool>,
+    publish_control: Arc<PublishControl>,
+    stop: Arc<AtomicBool>,
+    stats: SharedStats,
+    callbacks: Arc<EngineCallbacks>,
+    token: Arc<std::sync::Mutex<String>>,
+    stop_reason: StopReasonCell,
+    startup: Arc<StartupTracker>,
+) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            tracing::error!("transport runtime: {e}");
+            record_stop_reason(&stop_reason, StopReason::Disconnected);
+            (callbacks.on_error)(&EngineError::Transport(format!("runtime: {e}")));
+            stop.store(true, Ordering::SeqCst);
+            return;
+        }
+    };
+    let result = runtime.block_on(run_transport(
+        config,
+        frame_rx,
+        audio_rx,
+        camera,
+        keyframe_request,
+        publish_control,
+        stop.clone(),
+        stats,
+        token,
+        callbacks.clone(),
+        startup,
+    ));
+    (callbacks.on_connection_state)(ConnectionState::Closed);
+    if let Err(e) = result {
+        // Any transport failure — join, str0m, socket — is fatal for the
+        // session: without it we'd keep "running" while sending nothing.
+        tracing::error!("transport thread exited with error: {e}");
+        record_stop_reason(&stop_reason, StopReason::Disconnected);
+        (callbacks.on_error)(&e);
+        stop.store(true, Ordering::SeqCst);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_transport(
+    config: ScreenShareConfig,
+    frame_rx: Receiver<EncodedFrame>,
+    audio_rx: Option<Receiver<AudioPacket>>,
+    camera: Option<CameraPublish>,
+    keyframe_request: Arc<AtomicBool>,
+    publish_control: Arc<PublishControl>,
+    stop: Arc<AtomicBool>,
+    stats: SharedStats,
+    token: Arc<std::sync::Mutex<String>>,
+    callbacks: Arc<EngineCallbacks>,
+    startup: Arc<StartupTracker>,
+) -> EngineResult<()> {
+    // 1. Signal join, failing over across the configured URLs in order.
+    let mut urls = vec![config.server_url.as_str()];
+    urls.extend(config.fallback_urls.iter().map(String::as_str));
+    let signal_timeout = Duration::from_millis(config.signal_connect_timeout_ms);
+    let mut attempt = None;
+    for url in &urls {
+        let connect = SignalClient::connect(url, token.clone(), &config.tls, config.reconnect.clone());
+        let result = tokio::select! {
+            result = tokio::time::timeout(signal_timeout, connect) => result,
+            _ = stopped(&stop) => {
+                tracing::info!("start cancelled during signal connect");
+                return Ok(());
+            }
+        };
+        match result {
+            Ok(Ok(pair)) => {
+                attempt = Some(Ok(pair));
+                break;
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(url, "signal connect failed: {e}");
+                attempt = Some(Err(e));
+            }
+            Err(_) => {
+                let e = EngineError::SignalTimeout(format!(
+                    "no join from {url} within {} ms",
+                    config.signal_connect_timeout_ms
+                ));
+                tracing::warn!("{e}");
+                attempt = Some(Err(e));
+            }
+        }
+    }
+    let (mut signal, join) = attempt
+        .unwrap_or_else(|| Err(EngineError::Signal("no server url configured".into())))?;
+    tracing::info!(
+        room = join.room.as_ref().map(|r| r.name.clone()).unwrap_or_default(),
+        "joined room"
+    );
+    startup.mark_signal_join(&callbacks);
+    (callbacks.on_connection_state)(ConnectionState::SignalConnected);
+    // Everything for on_connected except the track sid, which arrives
+    // with the server's TrackPublished ack in the send loop.
+    let connected_info = ConnectedInfo {
+        room_name: join.room.as_ref().map(|r| r.name.clone()).unwrap_or_default(),
+        room_sid: join.room.as_ref().map(|r| r.sid.clone()).unwrap_or_default(),
+        participant_identity: join
+            .participant
+            .as_ref()
+            .map(|p| p.identity.clone())
+            .unwrap_or_default(),
+        participant_sid: join
+            .participant
+            .as_ref()
+            .map(|p| p.sid.clone())
+            .unwrap_or_default(),
+        track_sid: String::new(),
+    };
+
+    // 2. Announce the track, then negotiate. Name and source default from
+    // the capture target (webcam sessions publish as a Camera-source track);
+    // `config.track` overrides how the share is labelled for receivers.
+    let track_cid = "screen-video".to_string();
+    let (default_name, default_source) = if matches!(config.target, crate::capture::CaptureTarget::Camera(_)) {
+        ("camera", livekit_protocol::TrackSource::Camera)
+    } else {
+        ("screenshare", livekit_protocol::TrackSource::ScreenShare)
+    };
+    let track_name = config.track.name.as_deref().unwrap_or(default_name);
+    let track_source = match config.track.source {
+        Some(crate::config::TrackSource::Screenshare) => livekit_protocol::TrackSource::ScreenShare,
+        Some(crate::config::TrackSource::Camera) => livekit_protocol::TrackSource::Camera,
+        Some(crate::config::TrackSource::Unknown) => livekit_protocol::TrackSource::Unknown,
+        None => default_source,
+    };
+    signal
+        .send_add_track(
+            &track_cid,
+            track_name,
+            track_source,
+            config.encoder.width,
+            config.encoder.height,
+            config.audio_mode.is_some(),
+            config.track.stream.as_deref(),
+        )
+        .await?;
+    if let Some(camera) = camera.as_ref() {
+        signal
+            .send_add_track(
+                "camera-video",
+                "camera",
+                livekit_protocol::TrackSource::Camera,
+                camera.width,
+                camera.height,
+                false,
+                None,
+            )
+            .await?;
+    }
+
+    // 3. Build the str0m peer as the offerer on the publish transport.
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .map_err(|e| EngineError::Transport(format!("udp bind: {e}")))?;
+    let local_addr = socket
+        .local_addr()
+        .map_err(|e| EngineError::Transport(format!("udp addr: {e}")))?;
+    // Server-reflexive gathering happens on the media socket, so the mapped
+    // address is valid for this session. The socket stays blocking: the
+    // gatherer and the send loop each bound their own waits with
+    // `set_read_timeout`, which a nonblocking socket would ignore.
+    let reflexive = gather_server_reflexive(&config, &join.ice_servers, &socket, &callbacks);
+
+    let mut rtc = Rtc::builder().set_rtp_mode(false).build();
+    let candidate = Candidate::host(local_addr, Protocol::Udp)
+        .map_err(|e| EngineError::Transport(format!("candidate: {e}")))?;
+    rtc.add_local_candidate(candidate);
+    for addr in reflexive {
+        match Candidate::server_reflexive(addr, local_addr, Protocol::Udp) {
+            Ok(c) => {
+                rtc.add_local_candidate(c);
+            }
+            Err(e) => tracing::warn!("srflx candidate {addr}: {e}"),
+        }
+    }
+
+    let mut change = rtc.sdp_api();
+    let video_mid = change.add_media(MediaKind::Video, Direction::SendOnly, None, None, None);
+    let camera_mid = camera
+        .as_ref()
+        .map(|_| change.add_media(MediaKind::Video, Direction::SendOnly, None, None, None));
+    let audio_mid = if audio_rx.is_some() {
+        Some(change.add_media(MediaKind::Audio, Direction::SendOnly, None, None, None))
+    } else {
+        None
+    };
+    let (offer, pending) = change.apply().unwrap();
+    signal.send_offer(&offer.to_sdp_string()).await?;
+
+    // 4. Wait for the answer, applying trickle candidates as they arrive.
+    // The ICE deadline spans both negotiation and ICE establishment.
+    let connect_deadline = Instant::now() + Duration::from_millis(config.ice_connect_timeout_ms);
+    let mut pending = Some(pending);
+    while pending.is_some() {
+        if Instant::now() > connect_deadline {
+            return Err(EngineError::IceTimeout(
+                "no SDP answer from server".into(),
+            ));
+        }
+        let event = tokio::select! {
+            event = signal.recv_event() => event?,
+            _ = stopped(&stop) => {
+                tracing::info!("start cancelled during negotiation");
+                signal
+                    .leave(livekit_protocol::DisconnectReason::ClientInitiated)
+                    .await
+                    .ok();
+                return Ok(());
+            }
+        };
+        match event {
+            SignalEvent::Answer(sdp) => {
+                let answer = str0m::change::SdpAnswer::from_sdp_string(&sdp)
+                    .map_err(|e| EngineError::Transport(format!("bad answer sdp: {e}")))?;
+                rtc.sdp_api()
+                    .accept_answer(pending.take().unwrap(), answer)
+                    .map_err(|e| EngineError::Transport(format!("accept answer: {e}")))?;
+            }
+            SignalEvent::Trickle(candidate) => {
+                apply_remote_candidate(&mut rtc, &candidate);
+            }
+            SignalEvent::Close => {
+                return Err(EngineError::Transport("signal closed during join".into()));
+            }
+            _ => {}
+        }
+    }
+
+    // 5. Main loop: drive str0m, forward encoded frames, relay signal events.
+    let result = send_loop(
+        &config,
+        &mut rtc,
+        &socket,
+        video_mid,
+        camera_mid,
+        audio_mid,
+        camera,
+        connect_deadline,
+        &mut signal,
+        frame_rx,
+        audio_rx,
+        keyframe_request,
+        &publish_control,
+        stop.clone(),
+        stats,
+        &callbacks,
+        &startup,
+        connected_info,
+    )
+    .await;
+
+    // Leave with a reason and wait for the ack so the server drops us
+    // immediately instead of keeping a ghost participant around.
+    let reason = if result.is_ok() {
+        livekit_protocol::DisconnectReason::ClientInitiated
+    } else {
+        livekit_protocol::DisconnectReason::UnknownReason
+    };
+    signal.leave(reason).await.ok();
+    result
+}
+
+/// Resolves the mapped address via every STUN-capable server — the ones the
+/// application configured first, then the ones from the join response — and
+/// returns the distinct reflexive addresses found. TURN relay allocation is
+/// not implemented; `turn:` URLs contribute through their STUN side.
+fn gather_server_reflexive(
+    config: &ScreenShareConfig,
+    join_servers: &[livekit_protocol::IceServer],
+    socket: &UdpSocket,
+    callbacks: &EngineCallbacks,
+) -> Vec<std::net::SocketAddr> {
+    const BINDING_TIMEOUT: Duration = Duration::from_millis(500);
+
+    let mut targets: Vec<(String, u16)> = Vec::new();
+    for url in config
+        .ice_servers
+        .iter()
+        .flat_map(|s| s.urls.iter())
+        .chain(join_servers.iter().flat_map(|s| s.urls.iter()))
+    {
+        if let Some(target) = stun::parse_stun_url(url) {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Vec::new();
+    }
+
+    if let Err(e) = socket.set_read_timeout(Some(BINDING_TIMEOUT)) {
+        tracing::warn!("stun read timeout: {e}");
+        return Vec::new();
+    }
+    let mut addrs = Vec::new();
+    for (host, port) in &targets {
+        match stun::binding(socket, host, *port, BINDING_TIMEOUT) {
+            Ok(Some(addr)) => {
+                tracing::info!("reflexive address {addr} via {host}:{port}");
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+            Ok(None) => tracing::debug!("{host}:{port} answered without a mapped address"),
+            Err(e) => tracing::debug!("{host}:{port}: {e}"),
+        }
+    }
+    let _ = socket.set_read_timeout(None);
+    // Gathering nothing is normal behind no NAT, but if the app explicitly
+    // configured servers and none of them answered, surface it.
+    if addrs.is_empty() && !config.ice_servers.is_empty() {
+        (callbacks.on_warning)(
+            "ice",
+            "none of the configured STUN/TURN servers answered".into(),
+        );
+    }
+    addrs
+}
+
+/// Resolves when the engine's stop flag is set. Raced against the connect
+/// awaits so cancelling a starting session aborts the dial immediately
+/// instead of waiting out its timeout.
+async fn stopped(stop: &AtomicBool) {
+    while !stop.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn apply_remote_candidate(rtc: &mut Rtc, init: &IceCandidateInit) {
+    match Candidate::from_sdp_string(&init.candidate) {
+        Ok(c) => rtc.add_remote_candidate(c),
+        Err(e) => tracing::warn!("ignoring bad remote candidate: {e}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send_loop(
+    config: &ScreenShareConfig,
+    rtc: &mut Rtc,
+    socket: &UdpSocket,
+    video_mid: Mid,
+    camera_mid: Option<Mid>,
+    audio_mid: Option<Mid>,
+    camera: Option<CameraPublish>,
+    connect_deadline: Instant,
+    signal: &mut SignalClient,
+    frame_rx: Receiver<EncodedFrame>,
+    audio_rx: Option<Receiver<AudioPacket>>,
+    keyframe_request: Arc<AtomicBool>,
+    publish_control: &PublishControl,
+    stop: Arc<AtomicBool>,
+    stats: SharedStats,
+    callbacks: &EngineCallbacks,
+    startup: &StartupTracker,
+    connected_info: ConnectedInfo,
+) -> EngineResult<()> {
+    let mut buf = vec![0u8; 2000];
+    let mut video_pt = None;
+    let mut camera_pt = None;
+    let mut audio_pt = None;
+    let mut cryptor = match config.e2ee_key.as_deref() {
+        Some(key) => Some(e2ee::FrameCryptor::new(key)?),
+        None => None,
+    };
+    // Anchor for converting WGC QPC timestamps (100 ns SystemRelativeTime
+    // ticks) into the RTP clock and into wallclock instants for RTCP SRs.
+    // Synthesizing timestamps from the frame index drifts under variable
+    // capture rates and breaks receiver jitter buffers.
+    let mut capture_anchor: Option<(i64, Instant)> = None;
+    let mut camera_anchor: Option<(i64, Instant)> = None;
+    let mut connected = false;
+    let mut first_packet_marked = false;
+    // Held until both ICE and the track ack are in, then fired once.
+    let mut connected_info = Some(connected_info);
+    // Server-assigned sid for our video track, once published; needed for
+    // layer updates.
+    let mut video_track_sid: Option<String> = None;
+    let mut audio_track_sid: Option<String> = None;
+    let mut published_dims: Option<(u32, u32)> = None;
+    // Last mute state actually signalled, so a toggle is sent exactly once.
+    let mut signalled_video_mute = false;
+    let mut signalled_audio_mute = false;
+
+    while !stop.load(Ordering::SeqCst) && rtc.is_alive() {
+        if !connected && Instant::now() > connect_deadline {
+            return Err(EngineError::IceTimeout(format!(
+                "ICE did not connect within {} ms",
+                config.ice_connect_timeout_ms
+            )));
+        }
+        // Drain signal events without blocking the RTP loop.
+        while let Some(event) = signal.try_recv_event() {
+            match event {
+                SignalEvent::Trickle(candidate) => apply_remote_candidate(rtc, &candidate),
+                SignalEvent::Close => {
+                    // A dropped signal socket shouldn't end the share: the
+                    // peer connection is still alive, so resume the session.
+                    tracing::warn!("signal connection dropped, reconnecting");
+                    (callbacks.on_warning)("signal", "connection dropped, reconnecting".into());
+                    (callbacks.on_room_event)(RoomEvent::Reconnecting);
+                    (callbacks.on_connection_state)(ConnectionState::Reconnecting);
+                    signal.reconnect().await?;
+                    (callbacks.on_room_event)(RoomEvent::Reconnected);
+                    (callbacks.on_connection_state)(ConnectionState::SignalConnected);
+                    // Viewers may have missed frames during the gap.
+                    keyframe_request.store(true, Ordering::SeqCst);
+                }
+                SignalEvent::ParticipantUpdate(participants) => {
+                    (callbacks.on_room_event)(RoomEvent::ParticipantUpdate(participants));
+                }
+                SignalEvent::ConnectionQuality(updates) => {
+                    // Track our own delivery health; the startup bitrate
+                    // ramp holds while it is poor or lost.
+                    if let Some(ours) = updates
+                        .iter()
+                        .find(|u| u.participant_sid == signal.participant_sid())
+                    {
+                        let degraded = ours.quality
+                            == livekit_protocol::ConnectionQuality::Poor as i32
+                            || ours.quality == livekit_protocol::ConnectionQuality::Lost as i32;
+                        let was_degraded = publish_control
+                            .delivery_degraded
+                            .swap(degraded, Ordering::SeqCst);
+                        if degraded && !was_degraded {
+                            (callbacks.on_warning)(
+                                "bandwidth",
+                                "server reports degraded delivery, holding bitrate".into(),
+                            );
+                        }
+                    }
+                    (callbacks.on_room_event)(RoomEvent::ConnectionQuality(updates));
+                }
+                SignalEvent::SpeakersChanged(speakers) => {
+                    (callbacks.on_room_event)(RoomEvent::SpeakersChanged(speakers));
+                }
+                SignalEvent::TrackPublished(published) => {
+                    if let Some(track) = published.track {
+                        match published.cid.as_str() {
+                            "screen-video" => video_track_sid = Some(track.sid),
+                            "screen-audio" => audio_track_sid = Some(track.sid),
+                            _ => {}
+                        }
+                    }
+                }
+                SignalEvent::SubscribedQuality(update) => {
+                    let was_enabled = publish_control
+                        .encoding_enabled
+                        .load(Ordering::SeqCst);
+                    let enabled_max = update
+                        .subscribed_qualities
+                        .iter()
+                        .filter(|q| q.enabled)
+                        .map(|q| q.quality as u32)
+                        .max();
+                    match enabled_max {
+                        Some(max) => {
+                            publish_control.max_quality.store(max, Ordering::SeqCst);
+                            publish_control
+                                .encoding_enabled
+                                .store(true, Ordering::SeqCst);
+                            if !was_enabled {
+                                // Resuming after a pause: viewers need an IDR.
+                                keyframe_request.store(true, Ordering::SeqCst);
+                                tracing::info!("subscribers returned, resuming encode");
+                            }
+                        }
+                        None => {
+                            if was_enabled {
+                                tracing::info!("no subscribers, pausing encode");
+                            }
+                            publish_control
+                                .encoding_enabled
+                                .store(false, Ordering::SeqCst);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Relay user mute toggles to the server once the track sids are
+        // known; the RTP for muted tracks is dropped further down.
+        let video_muted = publish_control.video_muted.load(Ordering::SeqCst);
+        if video_muted != signalled_video_mute {
+            if let Some(sid) = video_track_sid.as_deref() {
+                if let Err(e) = signal.send_mute_track(sid, video_muted).await {
+                    tracing::warn!("mute video track: {e}");
+                }
+                signalled_video_mute = video_muted;
+            }
+        }
+        let audio_muted = publish_control.audio_muted.load(Ordering::SeqCst);
+        if audio_muted != signalled_audio_mute {
+            if let Some(sid) = audio_track_sid.as_deref() {
+                if let Err(e) = signal.send_mute_track(sid, audio_muted).await {
+                    tracing::warn!("mute audio track: {e}");
+                }
+                signalled_audio_mute = audio_muted;
+            }
+        }
+
+        // Both ICE and the server's track ack are in: publishing has
+        // demonstrably succeeded, tell the app once.
+        if connected && video_track_sid.is_some() {
+            if let Some(mut info) = connected_info.take() {
+                info.track_sid = video_track_sid.clone().unwrap_or_default();
+                (callbacks.on_connected)(info);
+            }
+        }
+
+        // Drive str0m output until it asks us to wait.
+        let timeout = loop {
+            match rtc
+                .poll_output()
+                .map_err(|e| EngineError::Transport(format!("rtc: {e}")))?
+            {
+                Output::Transmit(t) => {
+                    let _span = crate::trace::span("send");
+                    if socket.send_to(&t.contents, t.destination).is_ok() {
+                        // Only RTP counts as the first packet; STUN and
+                        // DTLS fly before the session is live.
+                        if connected && !first_packet_marked {
+                            first_packet_marked = true;
+                            startup.mark_first_packet(callbacks);
+                        }
+                        let mut s = stats.lock().unwrap();
+                        s.packets_sent += 1;
+                        s.bytes_sent += t.contents.len() as u64;
+                    }
+                }
+                Output::Timeout(t) => break t,
+                Output::Event(event) => match event {
+                    Event::Connected => {
+                        tracing::info!("ICE connected");
+                        connected = true;
+                        startup.mark_ice_connected(callbacks);
+                        // Resolve the negotiated payload types once connected.
+                        if let Some(media) = rtc.media(video_mid) {
+                            video_pt = media
+                                .payload_params()
+                                .iter()
+                                .find(|p| p.spec().codec == Codec::H264)
+                                .map(|p| p.pt());
+                        }
+                        if let Some(mid) = camera_mid {
+                            if let Some(media) = rtc.media(mid) {
+                                camera_pt = media
+                                    .payload_params()
+                                    .iter()
+                                    .find(|p| p.spec().codec == Codec::H264)
+                                    .map(|p| p.pt());
+                            }
+                        }
+                        if let (Some(mid), None) = (audio_mid, audio_pt) {
+                            if let Some(media) = rtc.media(mid) {
+                                audio_pt = media
+                                    .payload_params()
+                                    .iter()
+                                    .find(|p| p.spec().codec == Codec::Opus)
+                                    .map(|p| p.pt());
+                            }
+                        }
+                    }
+                    Event::KeyframeRequest(req) => {
+                        // Route to whichever encoder owns the mid.
+                        if Some(req.mid) == camera_mid {
+                            if let Some(camera) = camera.as_ref() {
+                                camera.keyframe_request.store(true, Ordering::SeqCst);
+                            }
+                        } else {
+                            keyframe_request.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    Event::IceConnectionStateChange(state) => {
+                        tracing::debug!("ice state: {state:?}");
+                        use str0m::IceConnectionState;
+                        let mapped = match state {
+                            IceConnectionState::Checking => {
+                                Some(ConnectionState::IceChecking)
+                            }
+                            IceConnectionState::Connected
+                            | IceConnectionState::Completed => {
+                                Some(ConnectionState::IceConnected)
+                            }
+                            IceConnectionState::Disconnected => {
+                                Some(ConnectionState::IceDisconnected)
+                            }
+                            _ => None,
+                        };
+                        if let Some(mapped) = mapped {
+                            (callbacks.on_connection_state)(mapped);
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        };
+
+        // Forward any encoded video frames that are ready. While muted they
+        // are drained and dropped so unmuting doesn't burst the backlog.
+        while let Ok(frame) = frame_rx.try_recv() {
+            if video_muted {
+                continue;
+            }
+            let Some(pt) = video_pt else { continue };
+            // Tell the SFU about dimension changes before sending frames at
+            // the new size.
+            if published_dims != Some((frame.width, frame.height)) {
+                published_dims = Some((frame.width, frame.height));
+                if let Some(sid) = video_track_sid.as_deref() {
+                    if let Err(e) = signal
+                        .send_update_video_layers(sid, frame.width, frame.height)
+                        .await
+                    {
+                        tracing::warn!("update video layers: {e}");
+                    }
+                }
+            }
+            let (anchor_qpc, anchor_instant) =
+                *capture_anchor.get_or_insert_with(|| (frame.capture_qpc, Instant::now()));
+            let elapsed_ticks = (frame.capture_qpc - anchor_qpc).max(0);
+            // 100 ns ticks → 90 kHz: * 90_000 / 10_000_000.
+            let rtp_time = elapsed_ticks as i128 * VIDEO_CLOCK_RATE as i128 / 10_000_000;
+            let time = MediaTime::from_90khz(rtp_time as i64);
+            // Wallclock for this frame's capture moment, so RTCP SR NTP/RTP
+            // mappings reflect the real capture clock rather than send time.
+            let capture_instant =
+                anchor_instant + Duration::from_nanos(elapsed_ticks as u64 * 100);
+            let _span = crate::trace::span("packetize");
+            let payload = match cryptor.as_mut() {
+                Some(cryptor) => cryptor.encrypt(&frame.data)?,
+                None => frame.data,
+            };
+            if let Some(writer) = rtc.writer(video_mid) {
+                if let Err(e) = writer.write(pt, capture_instant, time, payload) {
+                    tracing::error!("rtp write: {e}");
+                }
+                let latency_ms = Instant::now()
+                    .saturating_duration_since(capture_instant)
+                    .as_secs_f64()
+                    * 1000.0;
+                let mut s = stats.lock().unwrap();
+                s.push_latency(latency_ms);
+                if frame.is_keyframe {
+                    s.keyframes += 1;
+                }
+            }
+        }
+
+        // Forward camera frames on their own mid and timestamp anchor.
+        if let (Some(camera), Some(mid)) = (camera.as_ref(), camera_mid) {
+            while let Ok(frame) = camera.frame_rx.try_recv() {
+                let Some(pt) = camera_pt else { continue };
+                let (anchor_qpc, anchor_instant) =
+                    *camera_anchor.get_or_insert_with(|| (frame.capture_qpc, Instant::now()));
+                let elapsed_ticks = (frame.capture_qpc - anchor_qpc).max(0);
+                let rtp_time = elapsed_ticks as i128 * VIDEO_CLOCK_RATE as i128 / 10_000_000;
+                let time = MediaTime::from_90khz(rtp_time as i64);
+                let capture_instant =
+                    anchor_instant + Duration::from_nanos(elapsed_ticks as u64 * 100);
+                let payload = match cryptor.as_mut() {
+                    Some(cryptor) => cryptor.encrypt(&frame.data)?,
+                    None => frame.data,
+                };
+                if let Some(writer) = rtc.writer(mid) {
+                    if let Err(e) = writer.write(pt, capture_instant, time, payload) {
+                        tracing::error!("camera rtp write: {e}");
+                    }
+                }
+            }
+        }
+
+        // And audio packets. While paused or muted, drain and drop them so
+        // the backlog doesn't burst out on resume.
+        if let (Some(audio_rx), Some(mid)) = (audio_rx.as_ref(), audio_mid) {
+            let paused = publish_control.paused.load(Ordering::SeqCst) || audio_muted;
+            while let Ok(packet) = audio_rx.try_recv() {
+                if paused {
+                    continue;
+                }
+                let Some(pt) = audio_pt else { continue };
+                let time = MediaTime::new(
+                    packet.sample_offset as i64,
+                    str0m::media::Frequency::FORTY_EIGHT_KHZ,
+                );
+                let payload = match cryptor.as_mut() {
+                    Some(cryptor) => cryptor.encrypt(&packet.data)?,
+                    None => packet.data,
+                };
+                if let Some(writer) = rtc.writer(mid) {
+                    if let Err(e) = writer.write(pt, Instant::now(), time, payload) {
+                        tracing::error!("audio rtp write: {e}");
+                    }
+                }
+            }
+        }
+
+        // Socket receive with a short poll so the loop also services the
+        // frame channels at a reasonable cadence.
+        let wait = timeout
+            .saturating_duration_since(Instant::now())
+            .min(Duration::from_millis(5));
+        socket.set_read_timeout(Some(wait.max(Duration::from_millis(1)))).ok();
+        match socket.recv_from(&mut buf) {
+            Ok((n, source)) => {
+                let input = Input::Receive(
+                    Instant::now(),
+                    Receive {
+                        proto: Protocol::Udp,
+                        source,
+                        destination: socket.local_addr().unwrap(),
+                        contents: buf[..n].try_into().map_err(|_| {
+                            EngineError::Transport("oversized packet".into())
+                        })?,
+                    },
+                );
+                rtc.handle_input(input)
+                    .map_err(|e| EngineError::Transport(format!("rtc input: {e}")))?;
+            }
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                rtc.handle_input(Input::Timeout(Instant::now()))
+                    .map_err(|e| EngineError::Transport(format!("rtc timeout: {e}")))?;
+            }
+            Err(e) => return Err(EngineError::Transport(format!("socket: {e}"))),
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the SDP offer string produced by str0m. Exposed for tests.
+pub fn parse_offer(sdp: &str) -> EngineResult<SdpOffer> {
+    SdpOffer::from_sdp_string(sdp).map_err(|e| EngineError::Transport(format!("offer: {e}")))
+}
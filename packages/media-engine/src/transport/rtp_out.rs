@@ -0,0 +1,190 @@
+//! Raw RTP/UDP output: packetizes the encoded H.264 (and Opus) into RTP
+//! and fires it at a fixed address — no signaling, no SFU. Lets the whole
+//! pipeline be verified with `ffplay`/GStreamer from the SDP file this
+//! mode can write, and feeds custom SFU experiments.
+
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::audio::AudioPacket;
+use crate::config::RtpOutConfig;
+use crate::bitstream::h264::annex_b_units;
+use crate::encode::EncodedFrame;
+use crate::error::{EngineError, EngineResult};
+
+/// Payload types from the dynamic range, matching the written SDP.
+const H264_PT: u8 = 96;
+const OPUS_PT: u8 = 97;
+/// Conservative payload ceiling: fits a 1500-byte MTU with headroom for
+/// IP/UDP/RTP headers and tunnels.
+const MAX_PAYLOAD: usize = 1200;
+/// Fixed SSRCs; with exactly one sender per address there is nothing to
+/// randomize against.
+const VIDEO_SSRC: u32 = 0x4d49_474f;
+const AUDIO_SSRC: u32 = VIDEO_SSRC + 1;
+
+/// Sequence/SSRC state for one outgoing RTP stream.
+struct RtpStream {
+    socket: UdpSocket,
+    payload_type: u8,
+    ssrc: u32,
+    sequence: u16,
+}
+
+impl RtpStream {
+    fn connect(address: &str, payload_type: u8, ssrc: u32) -> EngineResult<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| EngineError::Transport(format!("rtp bind: {e}")))?;
+        socket
+            .connect(address)
+            .map_err(|e| EngineError::Transport(format!("rtp connect {address}: {e}")))?;
+        Ok(Self {
+            socket,
+            payload_type,
+            ssrc,
+            sequence: 0,
+        })
+    }
+
+    fn send(&mut self, timestamp: u32, marker: bool, payload: &[u8]) -> EngineResult<()> {
+        let mut packet = Vec::with_capacity(12 + payload.len());
+        packet.push(0x80);
+        packet.push(self.payload_type | ((marker as u8) << 7));
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+        packet.extend_from_slice(payload);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.socket
+            .send(&packet)
+            .map_err(|e| EngineError::Transport(format!("rtp send: {e}")))?;
+        Ok(())
+    }
+}
+
+/// Sends one access unit as RFC 6184 packets: single-NALU where it fits,
+/// FU-A fragmentation where it doesn't. The marker bit goes on the last
+/// packet of the unit.
+fn send_access_unit(stream: &mut RtpStream, data: &[u8], timestamp: u32) -> EngineResult<()> {
+    let units: Vec<&[u8]> = annex_b_units(data)
+        // Access unit delimiters are Annex-B framing, not RTP payloads.
+        .filter(|nal| nal[0] & 0x1f != 9)
+        .collect();
+    for (i, nal) in units.iter().enumerate() {
+        let last_unit = i + 1 == units.len();
+        if nal.len() <= MAX_PAYLOAD {
+            stream.send(timestamp, last_unit, nal)?;
+            continue;
+        }
+        // FU-A: indicator keeps NRI, type 28; the header carries the real
+        // type plus start/end bits.
+        let indicator = (nal[0] & 0x60) | 28;
+        let nal_type = nal[0] & 0x1f;
+        let mut offset = 1;
+        while offset < nal.len() {
+            let end = (offset + MAX_PAYLOAD - 2).min(nal.len());
+            let start_bit = (offset == 1) as u8;
+            let end_bit = (end == nal.len()) as u8;
+            let mut payload = Vec::with_capacity(2 + end - offset);
+            payload.push(indicator);
+            payload.push((start_bit << 7) | (end_bit << 6) | nal_type);
+            payload.extend_from_slice(&nal[offset..end]);
+            stream.send(timestamp, last_unit && end_bit == 1, &payload)?;
+            offset = end;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the SDP describing the outgoing streams, playable directly:
+/// `ffplay -protocol_whitelist file,rtp,udp out.sdp`.
+fn write_sdp(config: &RtpOutConfig, path: &str) -> EngineResult<()> {
+    let (host, port) = split_address(&config.address)?;
+    let mut sdp = format!(
+        "v=0\r\n\
+         o=- 0 0 IN IP4 127.0.0.1\r\n\
+         s=migo screen share\r\n\
+         c=IN IP4 {host}\r\n\
+         t=0 0\r\n\
+         m=video {port} RTP/AVP {H264_PT}\r\n\
+         a=rtpmap:{H264_PT} H264/90000\r\n\
+         a=fmtp:{H264_PT} packetization-mode=1\r\n"
+    );
+    if let Some(audio) = config.audio_address.as_deref() {
+        let (_, audio_port) = split_address(audio)?;
+        sdp.push_str(&format!(
+            "m=audio {audio_port} RTP/AVP {OPUS_PT}\r\n\
+             a=rtpmap:{OPUS_PT} opus/48000/2\r\n"
+        ));
+    }
+    std::fs::write(path, sdp)
+        .map_err(|e| EngineError::Config(format!("cannot write {path}: {e}")))?;
+    Ok(())
+}
+
+fn split_address(address: &str) -> EngineResult<(&str, u16)> {
+    let (host, port) = address
+        .rsplit_once(':')
+        .ok_or_else(|| EngineError::Config(format!("rtpOut address {address}: missing port")))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| EngineError::Config(format!("rtpOut address {address}: bad port")))?;
+    Ok((host, port))
+}
+
+/// Runs the raw output until the session stops or the encode thread hangs
+/// up. `audio_rx` is only handed over in stream-only mode; next to the
+/// LiveKit transport the audio packets belong to the publish.
+pub fn rtp_out_thread(
+    config: &RtpOutConfig,
+    frame_rx: Receiver<EncodedFrame>,
+    audio_rx: Option<Receiver<AudioPacket>>,
+    stop: &AtomicBool,
+) -> EngineResult<()> {
+    if let Some(path) = config.sdp_path.as_deref() {
+        write_sdp(config, path)?;
+        tracing::info!("rtp out SDP written to {path}");
+    }
+    let mut video = RtpStream::connect(&config.address, H264_PT, VIDEO_SSRC)?;
+    let mut audio = match config.audio_address.as_deref().filter(|_| audio_rx.is_some()) {
+        Some(address) => Some(RtpStream::connect(address, OPUS_PT, AUDIO_SSRC)?),
+        None => None,
+    };
+
+    // QPC of the first frame anchors the 90 kHz video clock, same mapping
+    // as the LiveKit transport.
+    let mut anchor_qpc: Option<i64> = None;
+    let started = Instant::now();
+    let mut last_report = started;
+    let mut frames = 0u64;
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        match frame_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(frame) => {
+                let anchor = *anchor_qpc.get_or_insert(frame.capture_qpc);
+                // 100 ns ticks → 90 kHz.
+                let elapsed = (frame.capture_qpc - anchor).max(0) as i128;
+                let timestamp = (elapsed * 90_000 / 10_000_000) as u32;
+                send_access_unit(&mut video, &frame.data, timestamp)?;
+                frames += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let (Some(stream), Some(audio_rx)) = (audio.as_mut(), audio_rx.as_ref()) {
+            while let Ok(packet) = audio_rx.try_recv() {
+                stream.send(packet.sample_offset as u32, false, &packet.data)?;
+            }
+        }
+        if last_report.elapsed() >= Duration::from_secs(30) {
+            last_report = Instant::now();
+            tracing::debug!("rtp out: {frames} frames sent to {}", config.address);
+        }
+    }
+    tracing::info!("rtp out finished: {frames} frames to {}", config.address);
+    Ok(())
+}
@@ -0,0 +1,476 @@
+//! LiveKit signal WebSocket client (protobuf over WS at `/rtc`).
+
+use futures_util::{SinkExt, StreamExt};
+use prost::Message as _;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream};
+
+use livekit_protocol as proto;
+
+use crate::config::{ReconnectPolicy, TlsConfig};
+use crate::error::{EngineError, EngineResult};
+
+use super::livekit::IceCandidateInit;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Decoded signal messages the transport cares about. Everything else is
+/// dropped in `signal_recv_loop`.
+pub enum SignalEvent {
+    Answer(String),
+    Trickle(IceCandidateInit),
+    TrackPublished(proto::TrackPublishedResponse),
+    /// Full or partial participant list refresh.
+    ParticipantUpdate(Vec<proto::ParticipantInfo>),
+    /// Per-participant connection quality from the SFU.
+    ConnectionQuality(Vec<proto::ConnectionQualityInfo>),
+    /// Active speaker changes.
+    SpeakersChanged(Vec<proto::SpeakerInfo>),
+    /// The SFU's view of which qualities (if any) are subscribed for one of
+    /// our published tracks.
+    SubscribedQuality(proto::SubscribedQualityUpdate),
+    /// Server acknowledged (or initiated) a leave.
+    Leave,
+    Close,
+}
+
+/// How long to wait for the server to acknowledge a Leave before tearing
+/// the socket down anyway.
+const LEAVE_ACK_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(750);
+
+/// Wraps the signal WebSocket: a writer task owned here, plus a reader task
+/// feeding decoded `SignalEvent`s through a channel.
+pub struct SignalClient {
+    url: String,
+    /// Access token; refreshed in place when the server sends a
+    /// RefreshToken message, so reconnects always use a valid token.
+    token: std::sync::Arc<std::sync::Mutex<String>>,
+    /// Our participant sid from the JoinResponse, required for session
+    /// resume (`reconnect=1&sid=...`).
+    participant_sid: String,
+    ping: PingConfig,
+    /// TLS connector built from the session config, reused on reconnect.
+    connector: Option<Connector>,
+    reconnect_policy: ReconnectPolicy,
+    outgoing_tx: mpsc::UnboundedSender<proto::signal_request::Message>,
+    event_rx: mpsc::UnboundedReceiver<SignalEvent>,
+}
+
+/// Keepalive settings, taken from the JoinResponse (server-dictated) with
+/// sane fallbacks for older servers that don't send them.
+#[derive(Debug, Clone, Copy)]
+struct PingConfig {
+    interval: std::time::Duration,
+    timeout: std::time::Duration,
+}
+
+impl PingConfig {
+    fn from_join(join: &proto::JoinResponse) -> Self {
+        let interval = if join.ping_interval > 0 {
+            std::time::Duration::from_secs(join.ping_interval as u64)
+        } else {
+            std::time::Duration::from_secs(30)
+        };
+        let timeout = if join.ping_timeout > 0 {
+            std::time::Duration::from_secs(join.ping_timeout as u64)
+        } else {
+            std::time::Duration::from_secs(15)
+        };
+        Self { interval, timeout }
+    }
+}
+
+/// Splits a fresh WebSocket into writer/reader/ping tasks and returns the
+/// channel endpoints the client holds on to.
+fn spawn_io_tasks(
+    ws: WsStream,
+    ping: PingConfig,
+    token: std::sync::Arc<std::sync::Mutex<String>>,
+) -> (
+    mpsc::UnboundedSender<proto::signal_request::Message>,
+    mpsc::UnboundedReceiver<SignalEvent>,
+) {
+    let (mut write, read) = ws.split();
+    let (outgoing_tx, mut outgoing_rx) =
+        mpsc::unbounded_channel::<proto::signal_request::Message>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<SignalEvent>();
+
+    // Writer task: serialize and send everything queued on outgoing_tx.
+    tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let req = proto::SignalRequest {
+                message: Some(message),
+            };
+            if write.send(Message::Binary(req.encode_to_vec())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Last time we saw a server pong; the reader updates it.
+    let last_pong = std::sync::Arc::new(std::sync::Mutex::new(std::time::Instant::now()));
+
+    // Reader task: decode responses into SignalEvents.
+    tokio::spawn(signal_recv_loop(
+        read,
+        event_tx.clone(),
+        last_pong.clone(),
+        token,
+    ));
+
+    // Keepalive task: the server closes idle signal connections, so send a
+    // ping at the interval it asked for and treat a missing pong as a drop.
+    {
+        let outgoing_tx = outgoing_tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(ping.interval);
+            loop {
+                ticker.tick().await;
+                let now_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as i64;
+                if outgoing_tx
+                    .send(proto::signal_request::Message::Ping(now_ms))
+                    .is_err()
+                {
+                    break;
+                }
+                if last_pong.lock().unwrap().elapsed() > ping.interval + ping.timeout {
+                    tracing::warn!("no pong from server, treating signal as dead");
+                    let _ = event_tx.send(SignalEvent::Close);
+                    break;
+                }
+            }
+        });
+    }
+
+    (outgoing_tx, event_rx)
+}
+
+impl SignalClient {
+    /// Connects to `{url}/rtc`, performs the join, and returns the client
+    /// together with the server's `JoinResponse`.
+    pub async fn connect(
+        url: &str,
+        token: std::sync::Arc<std::sync::Mutex<String>>,
+        tls: &TlsConfig,
+        reconnect_policy: ReconnectPolicy,
+    ) -> EngineResult<(Self, proto::JoinResponse)> {
+        let base = url.trim_end_matches('/');
+        let ws_url = {
+            let token = token.lock().unwrap();
+            format!("{base}/rtc?access_token={token}&auto_subscribe=0&sdk=rust&protocol=9")
+        };
+        let connector = super::tls::build_connector(tls)?;
+        let (mut ws, _) = connect_async_tls_with_config(&ws_url, None, false, connector.clone())
+            .await
+            .map_err(|e| EngineError::Signal(format!("connect: {e}")))?;
+
+        // First message must be the JoinResponse.
+        let join = loop {
+            let msg = ws
+                .next()
+                .await
+                .ok_or_else(|| EngineError::Signal("closed before join".into()))?
+                .map_err(|e| EngineError::Signal(format!("read: {e}")))?;
+            if let Message::Binary(data) = msg {
+                let resp = proto::SignalResponse::decode(data.as_slice())
+                    .map_err(|e| EngineError::Signal(format!("decode: {e}")))?;
+                if let Some(proto::signal_response::Message::Join(join)) = resp.message {
+                    break join;
+                }
+            }
+        };
+
+        let participant_sid = join
+            .participant
+            .as_ref()
+            .map(|p| p.sid.clone())
+            .unwrap_or_default();
+        let ping = PingConfig::from_join(&join);
+        let (outgoing_tx, event_rx) = spawn_io_tasks(ws, ping, token.clone());
+
+        Ok((
+            Self {
+                url: url.to_string(),
+                token,
+                participant_sid,
+                ping,
+                connector,
+                reconnect_policy,
+                outgoing_tx,
+                event_rx,
+            },
+            join,
+        ))
+    }
+
+    /// Our server-assigned participant sid from the join response.
+    pub fn participant_sid(&self) -> &str {
+        &self.participant_sid
+    }
+
+    /// Re-establishes the signal WebSocket after a drop, resuming the
+    /// existing LiveKit session (`reconnect=1&sid=...`) so the publisher
+    /// peer connection survives. Retries with exponential backoff.
+    pub async fn reconnect(&mut self) -> EngineResult<()> {
+        let base = self.url.trim_end_matches('/').to_string();
+        let ws_url = format!(
+            "{base}/rtc?access_token={token}&auto_subscribe=0&sdk=rust&protocol=9&reconnect=1&sid={sid}",
+            token = self.token.lock().unwrap(),
+            sid = self.participant_sid,
+        );
+
+        let policy = self.reconnect_policy.clone();
+        let mut backoff = std::time::Duration::from_millis(policy.backoff_ms);
+        for attempt in 1..=policy.max_retries {
+            match connect_async_tls_with_config(&ws_url, None, false, self.connector.clone())
+                .await
+            {
+                Ok((mut ws, _)) => {
+                    // The server acknowledges a resume with a
+                    // ReconnectResponse (or a fresh Join on full reconnect).
+                    let acked = loop {
+                        match ws.next().await {
+                            Some(Ok(Message::Binary(data))) => {
+                                match proto::SignalResponse::decode(data.as_slice()) {
+                                    Ok(resp) => match resp.message {
+                                        Some(proto::signal_response::Message::Reconnect(_))
+                                        | Some(proto::signal_response::Message::Join(_)) => {
+                                            break true
+                                        }
+                                        _ => continue,
+                                    },
+                                    Err(_) => continue,
+                                }
+                            }
+                            Some(Ok(_)) => continue,
+                            Some(Err(_)) | None => break false,
+                        }
+                    };
+                    if acked {
+                        let (outgoing_tx, event_rx) =
+                            spawn_io_tasks(ws, self.ping, self.token.clone());
+                        self.outgoing_tx = outgoing_tx;
+                        self.event_rx = event_rx;
+                        tracing::info!(attempt, "signal reconnected");
+                        return Ok(());
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(attempt, "signal reconnect failed: {e}");
+                }
+            }
+            // Jitter keeps a fleet of clients from re-dialing in lockstep
+            // after a server restart.
+            let jitter = if policy.jitter_ms > 0 {
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .subsec_nanos() as u64;
+                std::time::Duration::from_millis(nanos % policy.jitter_ms)
+            } else {
+                std::time::Duration::ZERO
+            };
+            tokio::time::sleep(backoff + jitter).await;
+            backoff *= 2;
+        }
+        Err(EngineError::Signal(format!(
+            "signal reconnect gave up after {} attempts",
+            policy.max_retries
+        )))
+    }
+
+    /// Announces a video track, optionally with its companion audio track.
+    /// `stream` is the grouping key; empty means the server infers one from
+    /// the source. The audio track always joins the video track's stream.
+    pub async fn send_add_track(
+        &mut self,
+        cid: &str,
+        name: &str,
+        source: proto::TrackSource,
+        width: u32,
+        height: u32,
+        with_audio: bool,
+        stream: Option<&str>,
+    ) -> EngineResult<()> {
+        let stream = stream.unwrap_or_default().to_string();
+        let req = proto::AddTrackRequest {
+            cid: cid.to_string(),
+            name: name.to_string(),
+            r#type: proto::TrackType::Video as i32,
+            source: source as i32,
+            width,
+            height,
+            stream: stream.clone(),
+            ..Default::default()
+        };
+        self.send(proto::signal_request::Message::AddTrack(req))?;
+        if with_audio {
+            let req = proto::AddTrackRequest {
+                cid: "screen-audio".to_string(),
+                name: "screenshare-audio".to_string(),
+                r#type: proto::TrackType::Audio as i32,
+                source: proto::TrackSource::ScreenShareAudio as i32,
+                stream,
+                ..Default::default()
+            };
+            self.send(proto::signal_request::Message::AddTrack(req))?;
+        }
+        Ok(())
+    }
+
+    pub async fn send_offer(&mut self, sdp: &str) -> EngineResult<()> {
+        self.send(proto::signal_request::Message::Offer(
+            proto::SessionDescription {
+                r#type: "offer".to_string(),
+                sdp: sdp.to_string(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Tells the SFU the published dimensions changed (window resize,
+    /// target switch) so it and remote clients stop assuming the AddTrack
+    /// dimensions.
+    pub async fn send_update_video_layers(
+        &mut self,
+        track_sid: &str,
+        width: u32,
+        height: u32,
+    ) -> EngineResult<()> {
+        self.send(proto::signal_request::Message::UpdateLayers(
+            proto::UpdateVideoLayers {
+                track_sid: track_sid.to_string(),
+                layers: vec![proto::VideoLayer {
+                    quality: proto::VideoQuality::High as i32,
+                    width,
+                    height,
+                    bitrate: 0,
+                    ssrc: 0,
+                }],
+            },
+        ))
+    }
+
+    /// Tells the server a published track was muted or unmuted, so
+    /// receivers render a muted tile instead of a frozen frame.
+    pub async fn send_mute_track(&mut self, track_sid: &str, muted: bool) -> EngineResult<()> {
+        self.send(proto::signal_request::Message::Mute(
+            proto::MuteTrackRequest {
+                sid: track_sid.to_string(),
+                muted,
+            },
+        ))
+    }
+
+    /// Sends a Leave with the given reason and waits briefly for the
+    /// server's acknowledgement (its Leave echo or the socket closing).
+    /// Tearing the WebSocket down immediately after queueing the message
+    /// can drop it before it is flushed, leaving a ghost participant in
+    /// the room until the session times out.
+    pub async fn leave(&mut self, reason: proto::DisconnectReason) -> EngineResult<()> {
+        self.send(proto::signal_request::Message::Leave(proto::LeaveRequest {
+            can_reconnect: false,
+            reason: reason as i32,
+            ..Default::default()
+        }))?;
+        let _ = tokio::time::timeout(LEAVE_ACK_TIMEOUT, async {
+            while let Ok(event) = self.recv_event().await {
+                if matches!(event, SignalEvent::Leave | SignalEvent::Close) {
+                    break;
+                }
+            }
+        })
+        .await;
+        Ok(())
+    }
+
+    fn send(&self, message: proto::signal_request::Message) -> EngineResult<()> {
+        self.outgoing_tx
+            .send(message)
+            .map_err(|_| EngineError::Signal("signal writer gone".into()))
+    }
+
+    /// Awaits the next decoded signal event.
+    pub async fn recv_event(&mut self) -> EngineResult<SignalEvent> {
+        self.event_rx
+            .recv()
+            .await
+            .ok_or_else(|| EngineError::Signal("signal reader gone".into()))
+    }
+
+    /// Non-blocking variant for use inside the RTP send loop.
+    pub fn try_recv_event(&mut self) -> Option<SignalEvent> {
+        self.event_rx.try_recv().ok()
+    }
+}
+
+/// Reads signal responses off the WebSocket and forwards the ones the
+/// transport handles.
+async fn signal_recv_loop(
+    mut read: futures_util::stream::SplitStream<WsStream>,
+    event_tx: mpsc::UnboundedSender<SignalEvent>,
+    last_pong: std::sync::Arc<std::sync::Mutex<std::time::Instant>>,
+    token: std::sync::Arc<std::sync::Mutex<String>>,
+) {
+    while let Some(msg) = read.next().await {
+        let data = match msg {
+            Ok(Message::Binary(data)) => data,
+            Ok(Message::Close(_)) | Err(_) => break,
+            _ => continue,
+        };
+        let resp = match proto::SignalResponse::decode(data.as_slice()) {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::warn!("undecodable signal message: {e}");
+                continue;
+            }
+        };
+        let event = match resp.message {
+            Some(proto::signal_response::Message::Answer(desc)) => SignalEvent::Answer(desc.sdp),
+            Some(proto::signal_response::Message::Trickle(trickle)) => {
+                match serde_json::from_str::<IceCandidateInit>(&trickle.candidate_init) {
+                    Ok(init) => SignalEvent::Trickle(init),
+                    Err(e) => {
+                        tracing::warn!("bad candidate init: {e}");
+                        continue;
+                    }
+                }
+            }
+            Some(proto::signal_response::Message::TrackPublished(published)) => {
+                SignalEvent::TrackPublished(published)
+            }
+            Some(proto::signal_response::Message::Update(update)) => {
+                SignalEvent::ParticipantUpdate(update.participants)
+            }
+            Some(proto::signal_response::Message::ConnectionQuality(update)) => {
+                SignalEvent::ConnectionQuality(update.updates)
+            }
+            Some(proto::signal_response::Message::SpeakersChanged(changed)) => {
+                SignalEvent::SpeakersChanged(changed.speakers)
+            }
+            Some(proto::signal_response::Message::SubscribedQualityUpdate(update)) => {
+                SignalEvent::SubscribedQuality(update)
+            }
+            Some(proto::signal_response::Message::Leave(_)) => SignalEvent::Leave,
+            Some(proto::signal_response::Message::RefreshToken(refreshed)) => {
+                *token.lock().unwrap() = refreshed;
+                continue;
+            }
+            Some(proto::signal_response::Message::Pong(_))
+            | Some(proto::signal_response::Message::PongResp(_)) => {
+                *last_pong.lock().unwrap() = std::time::Instant::now();
+                continue;
+            }
+            _ => continue,
+        };
+        if event_tx.send(event).is_err() {
+            break;
+        }
+    }
+    let _ = event_tx.send(SignalEvent::Close);
+}
@@ -0,0 +1,104 @@
+//! Minimal RFC 5389 STUN binding, shared by the transport (server-reflexive
+//! candidate gathering on the media socket) and the diagnostics ICE stage.
+
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::error::{EngineError, EngineResult};
+
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// Parses `stun:host[:port]` / `turn:host[:port]?transport=udp` into a
+/// host/port pair. TCP-only TURN entries are skipped.
+pub(crate) fn parse_stun_url(url: &str) -> Option<(String, u16)> {
+    let rest = url
+        .strip_prefix("stun:")
+        .or_else(|| url.strip_prefix("turn:"))?;
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    if query.is_some_and(|q| q.contains("transport=tcp")) {
+        return None;
+    }
+    match rest.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None => Some((rest.to_string(), 3478)),
+    }
+}
+
+/// Sends a binding request from `socket` and returns the XOR-MAPPED-ADDRESS,
+/// or `None` if the server answered without one. Uses `send_to`/`recv_from`
+/// so the socket stays unconnected and can carry media afterwards; the caller
+/// owns the socket's blocking mode and must set a read timeout first.
+pub(crate) fn binding(
+    socket: &UdpSocket,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> EngineResult<Option<SocketAddr>> {
+    let server = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| EngineError::Transport(format!("resolve {host}: {e}")))?
+        .next()
+        .ok_or_else(|| EngineError::Transport(format!("resolve {host}: no addresses")))?;
+
+    let mut txid = [0u8; 12];
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        ^ (std::process::id() as u128);
+    txid.copy_from_slice(&seed.to_be_bytes()[4..16]);
+
+    let mut request = [0u8; 20];
+    request[0..2].copy_from_slice(&0x0001u16.to_be_bytes());
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..20].copy_from_slice(&txid);
+    socket
+        .send_to(&request, server)
+        .map_err(|e| EngineError::Transport(format!("send: {e}")))?;
+
+    // Ignore datagrams from other peers until the matching response or the
+    // deadline; nothing else should be talking to this socket yet.
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 256];
+    loop {
+        let (len, from) = socket
+            .recv_from(&mut buf)
+            .map_err(|e| EngineError::Transport(format!("no STUN response: {e}")))?;
+        if from != server || len < 20 || buf[0..2] != 0x0101u16.to_be_bytes() || buf[8..20] != txid
+        {
+            if Instant::now() > deadline {
+                return Err(EngineError::Transport("no STUN response".into()));
+            }
+            continue;
+        }
+        return Ok(parse_xor_mapped(&buf[..len]));
+    }
+}
+
+/// Walks the attributes of a binding success response looking for
+/// XOR-MAPPED-ADDRESS (0x0020, IPv4).
+fn parse_xor_mapped(buf: &[u8]) -> Option<SocketAddr> {
+    let mut offset = 20;
+    while offset + 4 <= buf.len() {
+        let attr_type = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+        let attr_len = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+        let value = &buf[offset + 4..(offset + 4 + attr_len).min(buf.len())];
+        if attr_type == 0x0020 && value.len() >= 8 && value[1] == 0x01 {
+            let port = u16::from_be_bytes([value[2], value[3]]) ^ (MAGIC_COOKIE >> 16) as u16;
+            let cookie = MAGIC_COOKIE.to_be_bytes();
+            let ip = Ipv4Addr::new(
+                value[4] ^ cookie[0],
+                value[5] ^ cookie[1],
+                value[6] ^ cookie[2],
+                value[7] ^ cookie[3],
+            );
+            return Some(SocketAddr::from((ip, port)));
+        }
+        // Attributes are padded to 4 bytes.
+        offset += 4 + attr_len.div_ceil(4) * 4;
+    }
+    None
+}
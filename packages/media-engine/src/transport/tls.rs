@@ -0,0 +1,128 @@
+//! TLS setup for the signal WebSocket.
+//!
+//! Self-hosted deployments often terminate `wss://` with an internal CA or
+//! a self-signed cert. The defaults (system roots, full verification) stay
+//! untouched unless the config asks for something else.
+
+use std::sync::Arc;
+
+use tokio_tungstenite::Connector;
+
+use crate::config::TlsConfig;
+use crate::error::{EngineError, EngineResult};
+
+/// Builds the tungstenite connector for the session's TLS settings.
+/// Returns `None` when the defaults apply, letting tungstenite use its own
+/// native-roots config.
+pub fn build_connector(tls: &TlsConfig) -> EngineResult<Option<Connector>> {
+    if tls.ca_pem.is_none() && !tls.no_system_roots && !tls.accept_invalid_certs {
+        return Ok(None);
+    }
+
+    let builder = rustls::ClientConfig::builder();
+    let config = if tls.accept_invalid_certs {
+        tracing::warn!("TLS certificate verification is DISABLED for this session");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCert::default()))
+            .with_no_client_auth()
+    } else {
+        let mut roots = rustls::RootCertStore::empty();
+        if !tls.no_system_roots {
+            let native = rustls_native_certs::load_native_certs()
+                .map_err(|e| EngineError::Signal(format!("system root certs: {e}")))?;
+            for cert in native {
+                // Individual unparsable system certs are common; skip them.
+                let _ = roots.add(cert);
+            }
+        }
+        if let Some(pem) = tls.ca_pem.as_deref() {
+            let mut added = 0usize;
+            for cert in rustls_pemfile::certs(&mut pem.as_bytes()) {
+                let cert =
+                    cert.map_err(|e| EngineError::Signal(format!("bad CA bundle: {e}")))?;
+                roots
+                    .add(cert)
+                    .map_err(|e| EngineError::Signal(format!("bad CA certificate: {e}")))?;
+                added += 1;
+            }
+            if added == 0 {
+                return Err(EngineError::Signal(
+                    "CA bundle contained no certificates".into(),
+                ));
+            }
+        }
+        if roots.is_empty() {
+            return Err(EngineError::Signal(
+                "no trusted roots: system roots disabled and no CA bundle given".into(),
+            ));
+        }
+        builder.with_root_certificates(roots).with_no_client_auth()
+    };
+
+    Ok(Some(Connector::Rustls(Arc::new(config))))
+}
+
+/// Verifier that accepts any server certificate. Only reachable through the
+/// explicit `accept_invalid_certs` opt-in.
+#[derive(Debug)]
+struct AcceptAnyCert {
+    provider: Arc<rustls::crypto::CryptoProvider>,
+}
+
+impl Default for AcceptAnyCert {
+    fn default() -> Self {
+        Self {
+            provider: rustls::crypto::CryptoProvider::get_default()
+                .cloned()
+                .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider())),
+        }
+    }
+}
+
+impl rustls::client::danger::ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
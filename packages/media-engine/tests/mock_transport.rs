@@ -0,0 +1,89 @@
+//! Headless transport-contract tests. Unlike the step tests these need no
+//! LiveKit server, screen, or hardware encoder, so they run in CI.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+
+use media_engine::encode::EncodedFrame;
+use media_engine::transport::mock::MockTransport;
+use media_engine::transport::{drive, Transport};
+
+fn frame(index: u64, keyframe: bool) -> EncodedFrame {
+    EncodedFrame {
+        data: vec![0u8; 16],
+        is_keyframe: keyframe,
+        width: 640,
+        height: 360,
+        frame_index: index,
+        capture_qpc: index as i64 * 166_667,
+    }
+}
+
+#[test]
+fn forwards_frames_in_order() {
+    let (tx, rx) = mpsc::channel();
+    for i in 0..10 {
+        tx.send(frame(i, i == 0)).unwrap();
+    }
+    drop(tx);
+
+    let mut transport = MockTransport::new();
+    let keyframe_request = AtomicBool::new(false);
+    let stop = AtomicBool::new(false);
+    drive(&mut transport, rx, &keyframe_request, &stop).expect("drive");
+
+    let sent = transport.sent();
+    assert_eq!(sent.len(), 10);
+    assert!(sent[0].is_keyframe);
+    assert!(sent
+        .windows(2)
+        .all(|pair| pair[0].frame_index < pair[1].frame_index));
+    assert!(!keyframe_request.load(Ordering::SeqCst));
+}
+
+#[test]
+fn keyframe_requests_reach_the_encoder_flag() {
+    let (tx, rx) = mpsc::channel();
+    for i in 0..3 {
+        tx.send(frame(i, false)).unwrap();
+    }
+    drop(tx);
+
+    let mut transport = MockTransport::new().request_keyframe_every(3);
+    let keyframe_request = AtomicBool::new(false);
+    let stop = AtomicBool::new(false);
+    drive(&mut transport, rx, &keyframe_request, &stop).expect("drive");
+
+    assert!(
+        keyframe_request.load(Ordering::SeqCst),
+        "keyframe request did not propagate to the encoder flag"
+    );
+}
+
+#[test]
+fn disconnect_surfaces_as_an_error() {
+    let (tx, rx) = mpsc::channel();
+    for i in 0..5 {
+        tx.send(frame(i, false)).unwrap();
+    }
+    drop(tx);
+
+    let mut transport = MockTransport::new().disconnect_after(2);
+    let keyframe_request = AtomicBool::new(false);
+    let stop = AtomicBool::new(false);
+    let result = drive(&mut transport, rx, &keyframe_request, &stop);
+
+    assert!(result.is_err(), "disconnect should end the drive loop");
+    assert_eq!(transport.sent().len(), 2);
+}
+
+#[test]
+fn stop_flag_ends_the_loop() {
+    let (tx, rx) = mpsc::channel::<EncodedFrame>();
+    let mut transport = MockTransport::new();
+    let keyframe_request = AtomicBool::new(false);
+    let stop = AtomicBool::new(true);
+    drive(&mut transport, rx, &keyframe_request, &stop).expect("drive");
+    assert!(transport.sent().is_empty());
+    drop(tx);
+}
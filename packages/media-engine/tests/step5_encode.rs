@@ -0,0 +1,62 @@
+//! Step 5: capture a few frames from the primary display and run them
+//! through the encode pipeline. Needs a real desktop session, so it is
+//! ignored by default: `cargo test --test step5_encode -- --ignored`.
+
+#![cfg(windows)]
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use media_engine::capture::{self, CaptureTarget};
+use media_engine::config::EncoderConfig;
+use media_engine::encode::d3d::create_d3d_device;
+use media_engine::encode::pipeline::EncodePipeline;
+
+#[test]
+#[ignore = "needs a desktop session and a hardware encoder"]
+fn capture_and_encode_frames() {
+    let (frame_tx, frame_rx) = capture::frame_slot();
+    let stop = Arc::new(AtomicBool::new(false));
+    let capture_stop = stop.clone();
+    let handle = std::thread::spawn(move || {
+        capture::run_capture(CaptureTarget::Display(0), 30, true, frame_tx, capture_stop)
+    });
+
+    let first = frame_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("no frame within 5s");
+    let (device, context) = create_d3d_device().expect("d3d device");
+    let mut pipeline = EncodePipeline::new(
+        device,
+        context,
+        first.width,
+        first.height,
+        &EncoderConfig::default(),
+    )
+    .expect("pipeline");
+
+    let mut encoded = 0;
+    let mut keyframes = 0;
+    let mut next = Some(first);
+    for _ in 0..60 {
+        let frame = match next.take() {
+            Some(frame) => frame,
+            None => frame_rx
+                .recv_timeout(Duration::from_secs(1))
+                .expect("capture stalled"),
+        };
+        if let Some(out) = pipeline.encode(&frame).expect("encode") {
+            assert!(!out.data.is_empty());
+            encoded += 1;
+            if out.is_keyframe {
+                keyframes += 1;
+            }
+        }
+    }
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    handle.join().unwrap().expect("capture thread");
+
+    assert!(encoded > 0, "encoder produced no output");
+    assert!(keyframes >= 1, "no keyframe in the first GOP");
+}
@@ -0,0 +1,25 @@
+//! Step 6: connect to a local LiveKit dev server and complete the join
+//! handshake. Needs `pnpm dev` (or `start-livekit.mjs`) running plus a valid
+//! token in `LIVEKIT_TOKEN`, so it is ignored by default.
+
+use std::sync::{Arc, Mutex};
+
+use media_engine::config::{ReconnectPolicy, TlsConfig};
+use media_engine::transport::signal::SignalClient;
+
+#[tokio::test]
+#[ignore = "needs a local LiveKit server and LIVEKIT_TOKEN"]
+async fn signal_join_handshake() {
+    let url = std::env::var("LIVEKIT_URL").unwrap_or_else(|_| "ws://localhost:7880".into());
+    let token = std::env::var("LIVEKIT_TOKEN").expect("set LIVEKIT_TOKEN");
+
+    let token = Arc::new(Mutex::new(token));
+    let (_client, join) =
+        SignalClient::connect(&url, token, &TlsConfig::default(), ReconnectPolicy::default())
+            .await
+            .expect("signal connect");
+    let room = join.room.expect("join response has room");
+    assert!(!room.sid.is_empty());
+    let participant = join.participant.expect("join response has participant");
+    assert!(!participant.identity.is_empty());
+}
@@ -0,0 +1,55 @@
+//! Step 7: the full pipeline — capture, encode, and publish to a local
+//! LiveKit server for a few seconds. Verify with a browser subscriber.
+//! Ignored by default: needs a desktop session, hardware encoder, and a
+//! running server.
+
+#![cfg(windows)]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use media_engine::capture::CaptureTarget;
+use media_engine::config::{EncoderConfig, ScreenShareConfig};
+use media_engine::engine::{EngineCallbacks, MediaEngine};
+
+#[test]
+#[ignore = "needs a desktop session, hardware encoder, and LiveKit server"]
+fn publish_screen_for_five_seconds() {
+    let config = ScreenShareConfig {
+        server_url: std::env::var("LIVEKIT_URL").unwrap_or_else(|_| "ws://localhost:7880".into()),
+        token: std::env::var("LIVEKIT_TOKEN").expect("set LIVEKIT_TOKEN"),
+        target: CaptureTarget::Display(0),
+        encoder: EncoderConfig::default(),
+        show_cursor: true,
+        ..Default::default()
+    };
+
+    let errored = Arc::new(AtomicBool::new(false));
+    let errored_cb = errored.clone();
+    let callbacks = EngineCallbacks {
+        on_stats: Box::new(|stats| {
+            println!("fps={:.1} sent={}B", stats.fps, stats.bytes_sent);
+        }),
+        on_error: Box::new(move |error| {
+            eprintln!("error [{}]: {error}", error.code());
+            errored_cb.store(true, Ordering::SeqCst);
+        }),
+        on_warning: Box::new(|code, message| eprintln!("warning [{code}]: {message}")),
+        on_stopped: Box::new(|_| {}),
+        on_room_event: Box::new(|_| {}),
+        on_started: Box::new(|_| {}),
+        on_connected: Box::new(|info| println!("connected: {info:?}")),
+        on_connection_state: Box::new(|state| println!("state: {}", state.as_str())),
+    };
+
+    let engine = MediaEngine::start(config, callbacks).expect("start");
+    std::thread::sleep(Duration::from_secs(5));
+    let stats = engine.current_stats();
+    engine.stop();
+    drop(engine);
+
+    assert!(!errored.load(Ordering::SeqCst), "engine reported errors");
+    assert!(stats.frames_encoded > 0, "nothing was encoded");
+    assert!(stats.bytes_sent > 0, "nothing was sent");
+}
@@ -0,0 +1,142 @@
+//! Step 8: long soak — run the full pipeline against a local LiveKit dev
+//! server for `SOAK_MINUTES` (default 30) and assert nothing drifts: the
+//! frame rate at the end matches the start, process memory stays flat,
+//! pipeline resource counters don't grow, and no worker thread dies.
+//! Catches the slow leaks five-second tests never see. Ignored by
+//! default: needs a desktop session, hardware encoder, a running server,
+//! and half an hour of wall clock.
+
+#![cfg(windows)]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use media_engine::capture::CaptureTarget;
+use media_engine::config::{EncoderConfig, ScreenShareConfig};
+use media_engine::engine::{EngineCallbacks, MediaEngine};
+
+/// Private working set of this process, in bytes.
+fn working_set_bytes() -> u64 {
+    use windows::Win32::System::ProcessStatus::{
+        GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+    };
+    use windows::Win32::System::Threading::GetCurrentProcess;
+    let mut counters = PROCESS_MEMORY_COUNTERS {
+        cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb)
+            .expect("GetProcessMemoryInfo");
+    }
+    counters.WorkingSetSize as u64
+}
+
+fn avg(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len().max(1) as f64
+}
+
+#[test]
+#[ignore = "30+ minute soak; needs a desktop session, hardware encoder, and LiveKit server"]
+fn soak_full_pipeline() {
+    let minutes: u64 = std::env::var("SOAK_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+    let config = ScreenShareConfig {
+        server_url: std::env::var("LIVEKIT_URL").unwrap_or_else(|_| "ws://localhost:7880".into()),
+        token: std::env::var("LIVEKIT_TOKEN").expect("set LIVEKIT_TOKEN"),
+        target: CaptureTarget::Display(0),
+        encoder: EncoderConfig::default(),
+        show_cursor: true,
+        ..Default::default()
+    };
+
+    let errored = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let fps_samples: Arc<Mutex<Vec<f64>>> = Arc::new(Mutex::new(Vec::new()));
+    let errored_cb = errored.clone();
+    let stopped_cb = stopped.clone();
+    let fps_cb = fps_samples.clone();
+    let callbacks = EngineCallbacks {
+        on_stats: Box::new(move |stats| {
+            fps_cb.lock().unwrap().push(stats.fps);
+        }),
+        on_error: Box::new(move |error| {
+            eprintln!("error [{}]: {error}", error.code());
+            errored_cb.store(true, Ordering::SeqCst);
+        }),
+        on_warning: Box::new(|code, message| eprintln!("warning [{code}]: {message}")),
+        on_stopped: Box::new(move |_| stopped_cb.store(true, Ordering::SeqCst)),
+        on_room_event: Box::new(|_| {}),
+        on_started: Box::new(|_| {}),
+        on_connected: Box::new(|info| println!("connected: {info:?}")),
+        on_connection_state: Box::new(|state| println!("state: {}", state.as_str())),
+    };
+
+    let engine = MediaEngine::start(config, callbacks).expect("start");
+
+    // Let startup transients (encoder spin-up, bitrate ramp) settle before
+    // taking the baseline the drift checks compare against.
+    std::thread::sleep(Duration::from_secs(60));
+    let baseline_fps = avg(&fps_samples.lock().unwrap());
+    let baseline_memory = working_set_bytes();
+    let baseline_textures = media_engine::leak::TEXTURES.live();
+    fps_samples.lock().unwrap().clear();
+    println!(
+        "baseline: {baseline_fps:.1} fps, {} MB working set",
+        baseline_memory / (1024 * 1024)
+    );
+    assert!(baseline_fps > 1.0, "pipeline never reached a usable frame rate");
+
+    let deadline = Instant::now() + Duration::from_secs(minutes * 60);
+    while Instant::now() < deadline {
+        std::thread::sleep(Duration::from_secs(30));
+        assert!(
+            !stopped.load(Ordering::SeqCst),
+            "session stopped mid-soak (worker thread died)"
+        );
+        let memory = working_set_bytes();
+        println!(
+            "soak: {:.1} fps, {} MB working set, {} live textures",
+            fps_samples.lock().unwrap().last().copied().unwrap_or(0.0),
+            memory / (1024 * 1024),
+            media_engine::leak::TEXTURES.live(),
+        );
+    }
+
+    // Frame-rate decay: the last minute should hold the baseline rate.
+    let samples = fps_samples.lock().unwrap();
+    let tail = &samples[samples.len().saturating_sub(60)..];
+    let final_fps = avg(tail);
+    drop(samples);
+    assert!(
+        final_fps >= baseline_fps * 0.9,
+        "frame rate decayed: {baseline_fps:.1} fps at start, {final_fps:.1} fps at end"
+    );
+
+    // Memory growth: a flat pipeline allows for allocator slack, not for
+    // half an hour of accumulated frames.
+    let final_memory = working_set_bytes();
+    assert!(
+        final_memory < baseline_memory + 256 * 1024 * 1024,
+        "working set grew from {} MB to {} MB",
+        baseline_memory / (1024 * 1024),
+        final_memory / (1024 * 1024),
+    );
+
+    // Resource growth: live texture count should hover at the baseline,
+    // not climb with runtime.
+    let final_textures = media_engine::leak::TEXTURES.live();
+    assert!(
+        final_textures <= baseline_textures + 8,
+        "live textures grew from {baseline_textures} to {final_textures}"
+    );
+
+    assert!(!errored.load(Ordering::SeqCst), "engine reported errors");
+    // Joining the threads runs the debug leak assertion over every
+    // counter (textures, input views, MF samples/buffers).
+    let stats = engine.stop_and_wait();
+    assert!(stats.frames_encoded > 0, "nothing was encoded");
+}
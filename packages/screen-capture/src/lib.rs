@@ -0,0 +1,1299 @@
+//! Lightweight scap-based screen capture exposed to JS. Used by the share
+//! picker for source previews; the full share pipeline lives in
+//! `@migo/media-engine`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{noop_finalize, Env, JsBuffer};
+use napi_derive::napi;
+use scap::capturer::{Capturer, Options, Resolution};
+use scap::frame::Frame;
+use scap::Target;
+
+/// Cap on concurrently running capture sessions. Each one costs a
+/// thread's worth of copy/scale/convert work, so a preview grid should
+/// `pause()` offscreen tiles rather than run dozens at once.
+const MAX_ACTIVE_CAPTURES: usize = 8;
+static ACTIVE_CAPTURES: AtomicUsize = AtomicUsize::new(0);
+
+/// scap's capturer setup touches process-global state on some platforms,
+/// so builds are serialized here. The capture loops themselves share
+/// nothing and run fully independently.
+static BUILD_LOCK: Mutex<()> = Mutex::new(());
+
+/// A capturable source, either a display or a window.
+#[napi(object)]
+#[derive(Clone)]
+pub struct CaptureSource {
+    /// `screen:{id}` or `window:{id}`, where `{id}` is the OS-level target
+    /// id — stable across enumerations, unlike a list index, so the id
+    /// still names the same source when windows open or close between
+    /// `list_sources()` and `start()`.
+    pub id: String,
+    pub name: String,
+    pub is_display: bool,
+}
+
+/// Filters for `list_sources()`; all default to off. The window
+/// attributes these need (minimized state, size, styles, owning process)
+/// aren't part of scap's target list, so on Windows they're looked up
+/// with a separate `EnumWindows` pass matched by title. On other
+/// platforms the attribute filters are no-ops.
+#[napi(object)]
+#[derive(Default)]
+pub struct SourceFilter {
+    /// Drop minimized windows.
+    pub exclude_minimized: Option<bool>,
+    /// Drop tool/utility windows (`WS_EX_TOOLWINDOW`) — floating palettes,
+    /// tooltips, and other helpers that clutter a picker.
+    pub exclude_tool_windows: Option<bool>,
+    /// Drop windows narrower than this, in pixels.
+    pub min_width: Option<u32>,
+    /// Drop windows shorter than this, in pixels.
+    pub min_height: Option<u32>,
+    /// Drop this process's own windows.
+    pub exclude_own_process: Option<bool>,
+}
+
+#[cfg(windows)]
+mod window_info {
+    //! Win32 lookup for the window attributes scap doesn't expose. Raw
+    //! FFI declarations rather than a `windows` crate dependency — five
+    //! calls don't justify one in a preview crate.
+
+    use std::collections::HashMap;
+
+    #[derive(Clone, Copy)]
+    pub struct WindowInfo {
+        pub minimized: bool,
+        pub width: u32,
+        pub height: u32,
+        pub tool_window: bool,
+        pub own_process: bool,
+    }
+
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    type Hwnd = isize;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumWindows(cb: extern "system" fn(Hwnd, isize) -> i32, lparam: isize) -> i32;
+        fn GetWindowTextW(hwnd: Hwnd, buf: *mut u16, len: i32) -> i32;
+        fn IsIconic(hwnd: Hwnd) -> i32;
+        fn GetWindowRect(hwnd: Hwnd, rect: *mut Rect) -> i32;
+        fn GetWindowLongW(hwnd: Hwnd, index: i32) -> i32;
+        fn GetWindowThreadProcessId(hwnd: Hwnd, pid: *mut u32) -> u32;
+    }
+
+    const GWL_EXSTYLE: i32 = -20;
+    const WS_EX_TOOLWINDOW: i32 = 0x0000_0080;
+
+    extern "system" fn visit(hwnd: Hwnd, lparam: isize) -> i32 {
+        let map = unsafe { &mut *(lparam as *mut HashMap<String, WindowInfo>) };
+        let mut buf = [0u16; 512];
+        let len = unsafe { GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32) };
+        if len <= 0 {
+            return 1;
+        }
+        let title = String::from_utf16_lossy(&buf[..len as usize]);
+        let mut rect = Rect {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        unsafe { GetWindowRect(hwnd, &mut rect) };
+        let mut pid = 0u32;
+        unsafe { GetWindowThreadProcessId(hwnd, &mut pid) };
+        let info = WindowInfo {
+            minimized: unsafe { IsIconic(hwnd) } != 0,
+            width: (rect.right - rect.left).max(0) as u32,
+            height: (rect.bottom - rect.top).max(0) as u32,
+            tool_window: unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } & WS_EX_TOOLWINDOW != 0,
+            own_process: pid == std::process::id(),
+        };
+        // First match wins on duplicate titles; EnumWindows walks
+        // top-level windows in z-order, so that's the topmost one.
+        map.entry(title).or_insert(info);
+        1
+    }
+
+    /// Attributes of every titled top-level window, keyed by title.
+    pub fn collect() -> HashMap<String, WindowInfo> {
+        let mut map = HashMap::new();
+        unsafe { EnumWindows(visit, &mut map as *mut _ as isize) };
+        map
+    }
+}
+
+/// Enumerates capturable displays and windows. Displays always pass the
+/// filter; windows with no looked-up attributes (title mismatch, other
+/// platforms) pass the attribute filters rather than vanish.
+#[napi]
+pub fn list_sources(filter: Option<SourceFilter>) -> Result<Vec<CaptureSource>> {
+    if !scap::has_permission() {
+        return Err(Error::from_reason("screen capture permission not granted"));
+    }
+    let filter = filter.unwrap_or_default();
+    #[cfg(not(windows))]
+    let _ = &filter;
+    #[cfg(windows)]
+    let window_info = window_info::collect();
+    let targets = scap::get_all_targets();
+    let mut out = Vec::with_capacity(targets.len());
+    for target in targets {
+        match target {
+            Target::Display(display) => {
+                out.push(CaptureSource {
+                    id: format!("screen:{}", display.id),
+                    name: display.title,
+                    is_display: true,
+                });
+            }
+            Target::Window(window) => {
+                if window.title.is_empty() {
+                    continue;
+                }
+                #[cfg(windows)]
+                if let Some(info) = window_info.get(&window.title) {
+                    if filter.exclude_minimized.unwrap_or(false) && info.minimized {
+                        continue;
+                    }
+                    if filter.exclude_tool_windows.unwrap_or(false) && info.tool_window {
+                        continue;
+                    }
+                    if filter.exclude_own_process.unwrap_or(false) && info.own_process {
+                        continue;
+                    }
+                    if info.width < filter.min_width.unwrap_or(0)
+                        || info.height < filter.min_height.unwrap_or(0)
+                    {
+                        continue;
+                    }
+                }
+                out.push(CaptureSource {
+                    id: format!("window:{}", window.id),
+                    name: window.title,
+                    is_display: false,
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// One change to the capturable source list.
+#[napi(object)]
+pub struct SourceChange {
+    /// `"added"`, `"removed"`, or `"renamed"`.
+    pub kind: String,
+    /// The affected source; for `"renamed"` it carries the new name.
+    pub source: CaptureSource,
+}
+
+/// Watches the source list and fires a callback when sources appear,
+/// disappear, or are renamed. scap has no change notifications, so the
+/// watcher re-enumerates on a native thread every `intervalMs` and only
+/// ships the diff across the NAPI boundary — the picker never polls.
+#[napi]
+pub struct SourceWatcher {
+    interval_ms: u32,
+    on_change: Option<Arc<ThreadsafeFunction<SourceChange, ErrorStrategy::Fatal>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[napi]
+impl SourceWatcher {
+    /// `intervalMs` defaults to 1000.
+    #[napi(constructor)]
+    pub fn new(interval_ms: Option<u32>) -> Result<Self> {
+        Ok(Self {
+            interval_ms: interval_ms.unwrap_or(1000).max(100),
+            on_change: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    /// Registers the change callback. Must be called before `start()`.
+    #[napi]
+    pub fn on_change(
+        &mut self,
+        #[napi(ts_arg_type = "(change: SourceChange) => void")] callback: ThreadsafeFunction<
+            SourceChange,
+            ErrorStrategy::Fatal,
+        >,
+    ) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("watcher already started"));
+        }
+        self.on_change = Some(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Starts watching. The first enumeration seeds the baseline without
+    /// firing events; only changes after that are reported.
+    #[napi]
+    pub fn start(&mut self) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("watcher already started"));
+        }
+        let on_change = self
+            .on_change
+            .clone()
+            .ok_or_else(|| Error::from_reason("onChange was not called"))?;
+        if !scap::has_permission() {
+            return Err(Error::from_reason("screen capture permission not granted"));
+        }
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = self.stop.clone();
+        let interval = std::time::Duration::from_millis(self.interval_ms as u64);
+        self.thread = Some(std::thread::spawn(move || {
+            let emit = |kind: &str, source: CaptureSource| {
+                on_change.call(
+                    SourceChange {
+                        kind: kind.into(),
+                        source,
+                    },
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            };
+            let mut known = snapshot_sources();
+            while !stop.load(Ordering::SeqCst) {
+                // Sleep in short steps so stop() doesn't block a full
+                // interval waiting for the join.
+                let deadline = std::time::Instant::now() + interval;
+                while std::time::Instant::now() < deadline {
+                    if stop.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+                let current = snapshot_sources();
+                for (id, source) in &current {
+                    match known.get(id) {
+                        None => emit("added", source.clone()),
+                        Some(old) if old.name != source.name => emit("renamed", source.clone()),
+                        Some(_) => {}
+                    }
+                }
+                for (id, source) in &known {
+                    if !current.contains_key(id) {
+                        emit("removed", source.clone());
+                    }
+                }
+                known = current;
+            }
+        }));
+        Ok(())
+    }
+
+    /// Stops the watcher thread.
+    #[napi]
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The current source list keyed by id, for diffing.
+fn snapshot_sources() -> HashMap<String, CaptureSource> {
+    scap::get_all_targets()
+        .into_iter()
+        .filter_map(|target| {
+            let source = match target {
+                Target::Display(display) => CaptureSource {
+                    id: format!("screen:{}", display.id),
+                    name: display.title,
+                    is_display: true,
+                },
+                Target::Window(window) => {
+                    if window.title.is_empty() {
+                        return None;
+                    }
+                    CaptureSource {
+                        id: format!("window:{}", window.id),
+                        name: window.title,
+                        is_display: false,
+                    }
+                }
+            };
+            Some((source.id.clone(), source))
+        })
+        .collect()
+}
+
+/// Re-enumerates and finds the target carrying the id from a
+/// `CaptureSource`. Ids are OS-level, so a source that has gone away (its
+/// window closed) is reported as not found rather than silently resolving
+/// to whatever now occupies its old list position.
+fn resolve_target(source_id: &str) -> Result<Target> {
+    let (kind, id) = source_id
+        .split_once(':')
+        .ok_or_else(|| Error::from_reason(format!("bad source id: {source_id}")))?;
+    let id: u32 = id
+        .parse()
+        .map_err(|_| Error::from_reason(format!("bad source id: {source_id}")))?;
+    let want_display = match kind {
+        "screen" => true,
+        "window" => false,
+        _ => return Err(Error::from_reason(format!("bad source id: {source_id}"))),
+    };
+
+    for target in scap::get_all_targets() {
+        let matches = match &target {
+            Target::Display(display) => want_display && display.id == id,
+            Target::Window(window) => !want_display && window.id == id,
+        };
+        if matches {
+            return Ok(target);
+        }
+    }
+    Err(Error::from_reason(format!("source not found: {source_id}")))
+}
+
+/// How a captured frame is mapped onto the exact requested output size.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScaleMode {
+    /// Aspect-preserving, letterboxed with black bars.
+    Fit,
+    /// Aspect-preserving, cropping whatever overflows the output.
+    Fill,
+    /// Ignore aspect ratio.
+    Stretch,
+}
+
+impl ScaleMode {
+    fn parse(raw: &str) -> Result<Self> {
+        match raw {
+            "fit" => Ok(ScaleMode::Fit),
+            "fill" => Ok(ScaleMode::Fill),
+            "stretch" => Ok(ScaleMode::Stretch),
+            other => Err(Error::from_reason(format!("unknown scale mode: {other}"))),
+        }
+    }
+}
+
+/// Nearest-neighbour rescale of a tightly packed BGRA frame to exactly
+/// `dw`x`dh`. Preview quality — the share pipeline proper scales on the
+/// GPU in `@migo/media-engine`.
+fn scale_bgra(data: &[u8], sw: u32, sh: u32, dw: u32, dh: u32, mode: ScaleMode) -> Vec<u8> {
+    let mut out = vec![0u8; dw as usize * dh as usize * 4];
+    // A destination rect and the source rect sampled into it; anything
+    // outside the destination rect stays black (letterbox bars).
+    let (dst, src) = match mode {
+        ScaleMode::Stretch => ((0, 0, dw, dh), (0, 0, sw, sh)),
+        ScaleMode::Fit => {
+            let scale = (dw as f64 / sw as f64).min(dh as f64 / sh as f64);
+            let out_w = ((sw as f64 * scale).round() as u32).clamp(1, dw);
+            let out_h = ((sh as f64 * scale).round() as u32).clamp(1, dh);
+            (
+                ((dw - out_w) / 2, (dh - out_h) / 2, out_w, out_h),
+                (0, 0, sw, sh),
+            )
+        }
+        ScaleMode::Fill => {
+            let scale = (dw as f64 / sw as f64).max(dh as f64 / sh as f64);
+            let crop_w = ((dw as f64 / scale).round() as u32).clamp(1, sw);
+            let crop_h = ((dh as f64 / scale).round() as u32).clamp(1, sh);
+            (
+                (0, 0, dw, dh),
+                ((sw - crop_w) / 2, (sh - crop_h) / 2, crop_w, crop_h),
+            )
+        }
+    };
+    let (dx, dy, dst_w, dst_h) = dst;
+    let (sx, sy, src_w, src_h) = src;
+    for row in 0..dst_h {
+        let from_y = sy + (row as u64 * src_h as u64 / dst_h as u64) as u32;
+        for col in 0..dst_w {
+            let from_x = sx + (col as u64 * src_w as u64 / dst_w as u64) as u32;
+            let from = ((from_y * sw + from_x) * 4) as usize;
+            let to = (((dy + row) * dw + dx + col) * 4) as usize;
+            out[to..to + 4].copy_from_slice(&data[from..from + 4]);
+        }
+    }
+    out
+}
+
+/// Pixel layout of delivered frames.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PixelFormat {
+    /// 32-bit BGRA as scap delivers it.
+    Bgra,
+    /// 4:2:0 — Y plane followed by an interleaved UV plane.
+    Nv12,
+    /// 4:2:0 — Y, U, and V planes.
+    I420,
+}
+
+impl PixelFormat {
+    fn parse(raw: &str) -> Result<Self> {
+        match raw {
+            "bgra" => Ok(PixelFormat::Bgra),
+            "nv12" => Ok(PixelFormat::Nv12),
+            "i420" => Ok(PixelFormat::I420),
+            other => Err(Error::from_reason(format!("unknown pixel format: {other}"))),
+        }
+    }
+
+    /// Bytes per row of the widest (luma) plane.
+    fn stride(self, width: u32) -> u32 {
+        match self {
+            PixelFormat::Bgra => width * 4,
+            PixelFormat::Nv12 | PixelFormat::I420 => width,
+        }
+    }
+
+    /// Total bytes in one tightly packed frame.
+    fn frame_bytes(self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        match self {
+            PixelFormat::Bgra => w * h * 4,
+            PixelFormat::Nv12 | PixelFormat::I420 => w * h + 2 * (w.div_ceil(2) * h.div_ceil(2)),
+        }
+    }
+}
+
+/// CPU BGRA → 4:2:0 conversion (BT.601 limited range), sampling chroma
+/// from the top-left pixel of each 2x2 block. Halves the bytes crossing
+/// the NAPI boundary for consumers that feed a video encoder anyway.
+fn bgra_to_yuv420(data: &[u8], width: u32, height: u32, format: PixelFormat) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+    let mut out = vec![0u8; w * h + 2 * cw * ch];
+    for y in 0..h {
+        for x in 0..w {
+            let p = (y * w + x) * 4;
+            let (b, g, r) = (data[p] as i32, data[p + 1] as i32, data[p + 2] as i32);
+            out[y * w + x] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        }
+    }
+    for cy in 0..ch {
+        for cx in 0..cw {
+            let p = (cy * 2 * w + cx * 2) * 4;
+            let (b, g, r) = (data[p] as i32, data[p + 1] as i32, data[p + 2] as i32);
+            let u = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            let v = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+            match format {
+                PixelFormat::Nv12 => {
+                    let uv = w * h + (cy * cw + cx) * 2;
+                    out[uv] = u;
+                    out[uv + 1] = v;
+                }
+                PixelFormat::I420 => {
+                    out[w * h + cy * cw + cx] = u;
+                    out[w * h + cw * ch + cy * cw + cx] = v;
+                }
+                PixelFormat::Bgra => unreachable!(),
+            }
+        }
+    }
+    out
+}
+
+/// Current refresh rate of the primary display, in Hz. Windows-only;
+/// elsewhere the common 60 is assumed. Window targets also use the
+/// primary display's rate — close enough for cadence alignment.
+#[cfg(windows)]
+fn display_refresh_rate() -> u32 {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetDC(hwnd: isize) -> isize;
+        fn ReleaseDC(hwnd: isize, hdc: isize) -> i32;
+    }
+    #[link(name = "gdi32")]
+    extern "system" {
+        fn GetDeviceCaps(hdc: isize, index: i32) -> i32;
+    }
+    const VREFRESH: i32 = 116;
+    unsafe {
+        let hdc = GetDC(0);
+        let hz = GetDeviceCaps(hdc, VREFRESH);
+        ReleaseDC(0, hdc);
+        // 0 and 1 mean "hardware default" per the docs.
+        if hz > 1 {
+            hz as u32
+        } else {
+            60
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn display_refresh_rate() -> u32 {
+    60
+}
+
+fn snap_resolution(width: u32, height: u32) -> Resolution {
+    let _ = width;
+    if height >= 2000 {
+        Resolution::_2160p
+    } else if height >= 1300 {
+        Resolution::_1440p
+    } else {
+        Resolution::_1080p
+    }
+}
+
+/// One captured frame with everything needed to interpret the buffer, so
+/// consumers never have to pair `get_frame()` with racy size getters.
+#[napi(object)]
+pub struct CaptureFrame {
+    /// Pixels in the session's output format — BGRA by default, tightly
+    /// packed 4:2:0 planes for `"nv12"`/`"i420"`.
+    pub data: Buffer,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row of the widest (luma) plane. Rows are tightly packed,
+    /// but consumers should read this rather than assume.
+    pub stride: u32,
+    /// Capture timestamp from the OS presentation clock, in milliseconds.
+    pub timestamp_ms: f64,
+}
+
+impl CaptureFrame {
+    fn new(data: Vec<u8>, width: u32, height: u32, display_time: u64, format: PixelFormat) -> Self {
+        Self {
+            data: Buffer::from(data),
+            width,
+            height,
+            stride: format.stride(width),
+            timestamp_ms: display_time as f64 / 1_000_000.0,
+        }
+    }
+}
+
+/// FRESH bit on `TripleBuffer::mid` — the middle slot holds a frame the
+/// reader hasn't taken yet.
+const FRESH: u8 = 0b100;
+const INDEX: u8 = 0b011;
+
+/// Triple buffer behind the `get_frame()` path. The capture thread always
+/// owns a slot to write into and publishes it with one atomic swap, so it
+/// never waits on JS; the reader swaps the published slot out and copies
+/// at its leisure. Each slot has its own mutex, but writer and reader are
+/// never pointed at the same slot — the locks only order the handoff and
+/// are always uncontended.
+struct TripleBuffer {
+    /// (data, width, height, display_time), `None` until first written.
+    slots: [Mutex<Option<(Vec<u8>, u32, u32, u64)>>; 3],
+    /// Index of the most recently published slot, plus `FRESH` when the
+    /// reader hasn't taken it. The writer's index lives on its thread and
+    /// the reader's here, so the three stay distinct.
+    mid: AtomicU8,
+    reader: AtomicU8,
+}
+
+impl TripleBuffer {
+    /// The writer thread starts on slot 0.
+    const WRITER_START: u8 = 0;
+
+    fn new() -> Self {
+        Self {
+            slots: [Mutex::new(None), Mutex::new(None), Mutex::new(None)],
+            mid: AtomicU8::new(1),
+            reader: AtomicU8::new(2),
+        }
+    }
+
+    /// Publishes a frame from the writer's slot and hands the writer the
+    /// retired middle slot. Returns true when the previous published
+    /// frame was never read — i.e. this publish dropped it.
+    fn publish(&self, write_idx: &mut u8, frame: (Vec<u8>, u32, u32, u64)) -> bool {
+        *self.slots[*write_idx as usize].lock().unwrap() = Some(frame);
+        let prev = self.mid.swap(*write_idx | FRESH, Ordering::SeqCst);
+        *write_idx = prev & INDEX;
+        prev & FRESH != 0
+    }
+
+    /// Takes the newest published frame, or `None` when nothing new has
+    /// been published since the last take.
+    fn take(&self) -> Option<(Vec<u8>, u32, u32, u64)> {
+        if self.mid.load(Ordering::SeqCst) & FRESH == 0 {
+            return None;
+        }
+        let reader = self.reader.load(Ordering::SeqCst);
+        let prev = self.mid.swap(reader, Ordering::SeqCst);
+        self.reader.store(prev & INDEX, Ordering::SeqCst);
+        self.slots[(prev & INDEX) as usize].lock().unwrap().take()
+    }
+
+    /// Dimensions of the newest published frame without consuming it.
+    fn dims(&self) -> Option<(u32, u32)> {
+        let mid = self.mid.load(Ordering::SeqCst);
+        if mid & FRESH == 0 {
+            return None;
+        }
+        self.slots[(mid & INDEX) as usize]
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(_, w, h, _)| (*w, *h))
+    }
+
+    /// Drops any published frame; called after the writer has joined.
+    fn clear(&self) {
+        for slot in &self.slots {
+            *slot.lock().unwrap() = None;
+        }
+        self.mid.fetch_and(INDEX, Ordering::SeqCst);
+    }
+}
+
+const SLOT_FREE: u8 = 0;
+const SLOT_FILLED: u8 = 1;
+const SLOT_ACQUIRED: u8 = 2;
+
+/// One reusable frame buffer in the ring. JS sees the backing memory
+/// through a borrowed `Buffer` created once, so frames cross the NAPI
+/// boundary without per-frame allocation or copying.
+struct RingSlot {
+    state: AtomicU8,
+    /// (width, height, display_time) of the frame currently in `data`.
+    meta: Mutex<(u32, u32, u64)>,
+    /// Fixed-size allocation; its address must stay stable for the life of
+    /// the ring because JS holds borrowed views into it.
+    data: Mutex<Box<[u8]>>,
+}
+
+/// A fixed ring of frame buffers with explicit acquire/release. The
+/// capture thread fills free slots; JS acquires the newest filled slot,
+/// reads it through its pre-created buffer view, and releases it.
+struct FrameRing {
+    slots: Vec<RingSlot>,
+}
+
+impl FrameRing {
+    fn new(count: usize, bytes: usize) -> Arc<Self> {
+        Arc::new(Self {
+            slots: (0..count)
+                .map(|_| RingSlot {
+                    state: AtomicU8::new(SLOT_FREE),
+                    meta: Mutex::new((0, 0, 0)),
+                    data: Mutex::new(vec![0u8; bytes].into_boxed_slice()),
+                })
+                .collect(),
+        })
+    }
+
+    /// Stores a frame in a free slot and retires any older undelivered
+    /// frame, so `acquire` always returns the newest. When JS holds every
+    /// slot the frame is dropped — backpressure instead of allocation.
+    /// Returns the number of frames lost: the incoming one when the ring
+    /// is full, plus any filled slot this frame superseded.
+    fn push(&self, data: &[u8], width: u32, height: u32, display_time: u64) -> u64 {
+        let Some(index) = self.slots.iter().position(|slot| {
+            slot.state
+                .compare_exchange(SLOT_FREE, SLOT_ACQUIRED, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        }) else {
+            return 1;
+        };
+        {
+            let mut slot_data = self.slots[index].data.lock().unwrap();
+            let len = data.len().min(slot_data.len());
+            slot_data[..len].copy_from_slice(&data[..len]);
+            *self.slots[index].meta.lock().unwrap() = (width, height, display_time);
+        }
+        self.slots[index].state.store(SLOT_FILLED, Ordering::SeqCst);
+        let mut retired = 0;
+        for (i, slot) in self.slots.iter().enumerate() {
+            if i != index
+                && slot
+                    .state
+                    .compare_exchange(SLOT_FILLED, SLOT_FREE, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                retired += 1;
+            }
+        }
+        retired
+    }
+}
+
+/// Counters shared between the capture thread and `get_stats()`.
+struct CaptureCounters {
+    captured: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A snapshot of session counters. `framesDropped` counts frames
+/// overwritten before the consumer read them — slot overwrites and ring
+/// retirements — so a rising value means JS is reading too slowly.
+#[napi(object)]
+pub struct CaptureStats {
+    pub frames_captured: f64,
+    pub frames_dropped: f64,
+    /// Frames processed per second since the previous `getStats()` call;
+    /// well below the requested fps with no drops means the capturer
+    /// itself is starving (occluded window, idle desktop).
+    pub fps: f64,
+}
+
+/// A chunk of captured audio: interleaved f32 PCM samples,
+/// `channels * frames` floats per chunk.
+#[napi(object)]
+pub struct AudioChunk {
+    /// Interleaved f32le samples.
+    pub data: Buffer,
+    pub sample_rate: u32,
+    pub channels: u32,
+    /// Capture timestamp from the OS presentation clock, in milliseconds.
+    pub timestamp_ms: f64,
+}
+
+/// Whether this build can capture audio alongside video. The pinned scap
+/// version has no audio frame type on any platform, so this is currently
+/// always false; the `AudioChunk`/`onAudio` surface exists so consumers
+/// don't need an API break when scap grows audio support. Until then,
+/// share audio comes from `@migo/media-engine`.
+#[napi]
+pub fn audio_supported() -> bool {
+    false
+}
+
+/// Metadata for an acquired ring slot; the pixels are read through the
+/// matching buffer from `ringBuffers()`.
+#[napi(object)]
+pub struct RingFrame {
+    /// Slot index — pass back to `releaseFrame` when done.
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row (`width * 4`).
+    pub stride: u32,
+    /// Capture timestamp from the OS presentation clock, in milliseconds.
+    pub timestamp_ms: f64,
+}
+
+/// A capture session delivering BGRA frames to JS, either by `get_frame()`
+/// polling or pushed through an `on_frame` callback.
+#[napi]
+pub struct NativeCapture {
+    source_id: String,
+    fps: u32,
+    /// When set, capture at the display refresh rate divided by this
+    /// instead of the fixed `fps`.
+    native_rate_divisor: Option<u32>,
+    requested_width: u32,
+    requested_height: u32,
+    scale_mode: ScaleMode,
+    format: PixelFormat,
+    show_cursor: bool,
+    frame: Arc<TripleBuffer>,
+    on_frame: Option<Arc<ThreadsafeFunction<CaptureFrame, ErrorStrategy::Fatal>>>,
+    on_error: Option<Arc<ThreadsafeFunction<String, ErrorStrategy::Fatal>>>,
+    on_stopped: Option<Arc<ThreadsafeFunction<String, ErrorStrategy::Fatal>>>,
+    ring: Option<Arc<FrameRing>>,
+    counters: Arc<CaptureCounters>,
+    /// (time of, captured count at) the previous `get_stats()` call, for
+    /// the effective-fps window.
+    stats_mark: Mutex<(std::time::Instant, u64)>,
+    paused: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[napi]
+impl NativeCapture {
+    /// `width`/`height` are delivered exactly (scap captures at the nearest
+    /// preset above them, then frames are rescaled). Pass 0x0 for the
+    /// preset-snapped native size. `scaleMode` is `"fit"` (letterbox,
+    /// default), `"fill"` (crop), or `"stretch"`. `showCursor` defaults to
+    /// true. `format` is `"bgra"` (default), `"nv12"`, or `"i420"` — the
+    /// YUV formats are converted on the capture thread and carry half the
+    /// bytes of BGRA.
+    #[napi(constructor)]
+    pub fn new(
+        source_id: String,
+        width: u32,
+        height: u32,
+        fps: u32,
+        scale_mode: Option<String>,
+        show_cursor: Option<bool>,
+        format: Option<String>,
+    ) -> Result<Self> {
+        if fps == 0 {
+            return Err(Error::from_reason("fps must be > 0"));
+        }
+        Ok(Self {
+            source_id,
+            fps,
+            native_rate_divisor: None,
+            requested_width: width,
+            requested_height: height,
+            scale_mode: scale_mode
+                .as_deref()
+                .map(ScaleMode::parse)
+                .transpose()?
+                .unwrap_or(ScaleMode::Fit),
+            format: format
+                .as_deref()
+                .map(PixelFormat::parse)
+                .transpose()?
+                .unwrap_or(PixelFormat::Bgra),
+            show_cursor: show_cursor.unwrap_or(true),
+            frame: Arc::new(TripleBuffer::new()),
+            on_frame: None,
+            on_error: None,
+            on_stopped: None,
+            ring: None,
+            counters: Arc::new(CaptureCounters {
+                captured: AtomicU64::new(0),
+                dropped: AtomicU64::new(0),
+            }),
+            stats_mark: Mutex::new((std::time::Instant::now(), 0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    /// Switches the session to push delivery: the callback fires with each
+    /// new BGRA frame instead of frames waiting in the `get_frame()` slot,
+    /// so the renderer gets consistent pacing without a poll loop. Must be
+    /// called before `start()`.
+    #[napi]
+    pub fn on_frame(
+        &mut self,
+        #[napi(ts_arg_type = "(frame: CaptureFrame) => void")] callback: ThreadsafeFunction<
+            CaptureFrame,
+            ErrorStrategy::Fatal,
+        >,
+    ) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        self.on_frame = Some(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Reports capture-thread failures (permission revoked, capturer build
+    /// failed, frame errors) that otherwise only stop frame delivery. Must
+    /// be called before `start()`.
+    #[napi]
+    pub fn on_error(
+        &mut self,
+        #[napi(ts_arg_type = "(message: string) => void")] callback: ThreadsafeFunction<
+            String,
+            ErrorStrategy::Fatal,
+        >,
+    ) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        self.on_error = Some(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Fires when the capture thread dies without `stop()` being called —
+    /// the source went away or capturing failed. Not fired for a normal
+    /// `stop()`. Must be called before `start()`.
+    #[napi]
+    pub fn on_stopped(
+        &mut self,
+        #[napi(ts_arg_type = "(reason: string) => void")] callback: ThreadsafeFunction<
+            String,
+            ErrorStrategy::Fatal,
+        >,
+    ) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        self.on_stopped = Some(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Switches delivery to a fixed ring of `slots` reusable buffers read
+    /// with `acquireFrame`/`releaseFrame` — no per-frame allocation or
+    /// copy at the NAPI boundary, which matters at 4K60. Requires explicit
+    /// output dimensions (the ring is sized up front) and must be called
+    /// before `start()`.
+    #[napi]
+    pub fn use_frame_ring(&mut self, slots: u32) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        if self.requested_width == 0 || self.requested_height == 0 {
+            return Err(Error::from_reason(
+                "frame ring requires explicit width/height",
+            ));
+        }
+        if slots == 0 {
+            return Err(Error::from_reason("slots must be > 0"));
+        }
+        let bytes = self
+            .format
+            .frame_bytes(self.requested_width, self.requested_height);
+        self.ring = Some(FrameRing::new(slots as usize, bytes));
+        Ok(())
+    }
+
+    /// The ring's backing buffers, in slot order. These are borrowed views
+    /// into native memory, created once and reused for every frame; only
+    /// read a buffer between acquiring and releasing its slot.
+    #[napi]
+    pub fn ring_buffers(&self, env: Env) -> Result<Vec<JsBuffer>> {
+        let ring = self
+            .ring
+            .as_ref()
+            .ok_or_else(|| Error::from_reason("useFrameRing was not called"))?;
+        ring.slots
+            .iter()
+            .map(|slot| {
+                let mut data = slot.data.lock().unwrap();
+                let (ptr, len) = (data.as_mut_ptr(), data.len());
+                // The ring Arc rides along as the finalize hint so the
+                // memory outlives any buffer JS still holds.
+                let view = unsafe {
+                    env.create_buffer_with_borrowed_data(ptr, len, ring.clone(), noop_finalize)
+                }?;
+                Ok(view.into_raw())
+            })
+            .collect()
+    }
+
+    /// Takes the newest undelivered frame's slot, or null when nothing new
+    /// has arrived. The slot's buffer contents stay valid until
+    /// `releaseFrame(index)`.
+    #[napi]
+    pub fn acquire_frame(&self) -> Option<RingFrame> {
+        let ring = self.ring.as_ref()?;
+        for (index, slot) in ring.slots.iter().enumerate() {
+            if slot
+                .state
+                .compare_exchange(SLOT_FILLED, SLOT_ACQUIRED, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                let (width, height, display_time) = *slot.meta.lock().unwrap();
+                return Some(RingFrame {
+                    index: index as u32,
+                    width,
+                    height,
+                    stride: self.format.stride(width),
+                    timestamp_ms: display_time as f64 / 1_000_000.0,
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns an acquired slot to the ring for reuse.
+    #[napi]
+    pub fn release_frame(&self, index: u32) {
+        if let Some(slot) = self
+            .ring
+            .as_ref()
+            .and_then(|ring| ring.slots.get(index as usize))
+        {
+            let _ = slot.state.compare_exchange(
+                SLOT_ACQUIRED,
+                SLOT_FREE,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+
+    /// Starts the capture thread. Frames go to the frame ring when
+    /// `useFrameRing` was called, else to the `on_frame` callback when one
+    /// is registered, else to an internal slot read with `get_frame()`.
+    /// Instances are independent and can run concurrently, up to
+    /// `MAX_ACTIVE_CAPTURES` sessions process-wide.
+    #[napi]
+    pub fn start(&mut self) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        let target = resolve_target(&self.source_id)?;
+        if ACTIVE_CAPTURES
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_ACTIVE_CAPTURES).then_some(n + 1)
+            })
+            .is_err()
+        {
+            return Err(Error::from_reason(format!(
+                "too many concurrent captures (max {MAX_ACTIVE_CAPTURES}); \
+                 pause or stop another session first"
+            )));
+        }
+        let fps = match self.native_rate_divisor {
+            Some(divisor) => (display_refresh_rate() / divisor).max(1),
+            None => self.fps,
+        };
+        let options = Options {
+            fps,
+            target: Some(target),
+            show_cursor: self.show_cursor,
+            show_highlight: false,
+            output_resolution: snap_resolution(self.requested_width, self.requested_height),
+            ..Default::default()
+        };
+
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = self.stop.clone();
+        let paused = self.paused.clone();
+        let counters = self.counters.clone();
+        let frame_slot = self.frame.clone();
+        let on_frame = self.on_frame.clone();
+        let on_error = self.on_error.clone();
+        let on_stopped = self.on_stopped.clone();
+        let ring = self.ring.clone();
+        let out_size = (self.requested_width > 0 && self.requested_height > 0)
+            .then_some((self.requested_width, self.requested_height));
+        let scale_mode = self.scale_mode;
+        let format = self.format;
+        self.thread = Some(std::thread::spawn(move || {
+            // Releases this session's ACTIVE_CAPTURES slot on every exit
+            // path, including a panic in the loop.
+            struct ActiveGuard;
+            impl Drop for ActiveGuard {
+                fn drop(&mut self) {
+                    ACTIVE_CAPTURES.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+            let _active = ActiveGuard;
+            let mut write_idx = TripleBuffer::WRITER_START;
+            // Falls back to stderr when no error callback is registered.
+            let report = |message: String| match on_error.as_ref() {
+                Some(on_error) => {
+                    on_error.call(message, ThreadsafeFunctionCallMode::NonBlocking);
+                }
+                None => eprintln!("screen-capture: {message}"),
+            };
+            let built = {
+                let _build = BUILD_LOCK.lock().unwrap();
+                Capturer::build(options)
+            };
+            let mut capturer = match built {
+                Ok(c) => c,
+                Err(e) => {
+                    report(format!("failed to build capturer: {e}"));
+                    if let Some(on_stopped) = on_stopped.as_ref() {
+                        on_stopped.call(
+                            "capturer build failed".into(),
+                            ThreadsafeFunctionCallMode::NonBlocking,
+                        );
+                    }
+                    return;
+                }
+            };
+            capturer.start_capture();
+            while !stop.load(Ordering::SeqCst) {
+                match capturer.get_next_frame() {
+                    Ok(Frame::BGRA(frame)) => {
+                        // Paused: keep draining the capturer but skip the
+                        // scale/convert/deliver work.
+                        if paused.load(Ordering::SeqCst) {
+                            continue;
+                        }
+                        counters.captured.fetch_add(1, Ordering::Relaxed);
+                        let (mut data, mut width, mut height) =
+                            (frame.data, frame.width as u32, frame.height as u32);
+                        if let Some((dw, dh)) = out_size {
+                            if (width, height) != (dw, dh) {
+                                data = scale_bgra(&data, width, height, dw, dh, scale_mode);
+                                width = dw;
+                                height = dh;
+                            }
+                        }
+                        if format != PixelFormat::Bgra {
+                            data = bgra_to_yuv420(&data, width, height, format);
+                        }
+                        if let Some(ring) = ring.as_ref() {
+                            let lost = ring.push(&data, width, height, frame.display_time);
+                            counters.dropped.fetch_add(lost, Ordering::Relaxed);
+                        } else if let Some(on_frame) = on_frame.as_ref() {
+                            on_frame.call(
+                                CaptureFrame::new(data, width, height, frame.display_time, format),
+                                ThreadsafeFunctionCallMode::NonBlocking,
+                            );
+                        } else if frame_slot.publish(
+                            &mut write_idx,
+                            (data, width, height, frame.display_time),
+                        ) {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    Ok(_) => {
+                        // Other pixel formats aren't requested.
+                    }
+                    Err(e) => {
+                        report(format!("frame error: {e}"));
+                        break;
+                    }
+                }
+            }
+            capturer.stop_capture();
+            // An exit without the stop flag means the capturer died on its
+            // own — the window closed, permission was revoked, and so on.
+            if !stop.load(Ordering::SeqCst) {
+                if let Some(on_stopped) = on_stopped.as_ref() {
+                    on_stopped.call(
+                        "capture ended".into(),
+                        ThreadsafeFunctionCallMode::NonBlocking,
+                    );
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    /// Returns the most recent frame with its dimensions, stride, and
+    /// timestamp, or null when no new frame has arrived since the last call.
+    #[napi]
+    pub fn get_frame(&self) -> Option<CaptureFrame> {
+        self.frame.take().map(|(data, width, height, display_time)| {
+            CaptureFrame::new(data, width, height, display_time, self.format)
+        })
+    }
+
+    /// Width of the last unread frame (0 before the first frame). Prefer
+    /// the dimensions on `CaptureFrame`, which can't race the read.
+    #[napi(getter)]
+    pub fn width(&self) -> u32 {
+        self.frame.dims().map(|(w, _)| w).unwrap_or(0)
+    }
+
+    /// Height of the last unread frame (0 before the first frame). Prefer
+    /// the dimensions on `CaptureFrame`, which can't race the read.
+    #[napi(getter)]
+    pub fn height(&self) -> u32 {
+        self.frame.dims().map(|(_, h)| h).unwrap_or(0)
+    }
+
+    /// Hides or shows the cursor. scap can't toggle this on a live
+    /// capturer, so a running session is restarted with the new setting —
+    /// expect a brief gap in frames.
+    #[napi]
+    pub fn set_show_cursor(&mut self, show: bool) -> Result<()> {
+        if self.show_cursor == show {
+            return Ok(());
+        }
+        self.show_cursor = show;
+        if self.thread.is_some() {
+            self.stop();
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Registers a PCM audio callback. Errors while `audioSupported()` is
+    /// false rather than silently never firing, so callers find out at
+    /// registration time instead of debugging a silent share.
+    #[napi]
+    pub fn on_audio(
+        &mut self,
+        #[napi(ts_arg_type = "(chunk: AudioChunk) => void")] _callback: ThreadsafeFunction<
+            AudioChunk,
+            ErrorStrategy::Fatal,
+        >,
+    ) -> Result<()> {
+        Err(Error::from_reason(
+            "audio capture is not supported by this build (audioSupported() is false); \
+             use @migo/media-engine for share audio",
+        ))
+    }
+
+    /// Retargets the session to another source, keeping the registered
+    /// callbacks, frame ring, and output settings. A running session is
+    /// restarted on the new source; a stopped one just picks it up on the
+    /// next `start()`. The id is validated up front so a bad id doesn't
+    /// kill a running preview.
+    #[napi]
+    pub fn switch_source(&mut self, source_id: String) -> Result<()> {
+        resolve_target(&source_id)?;
+        if self.source_id == source_id {
+            return Ok(());
+        }
+        self.source_id = source_id;
+        if self.thread.is_some() {
+            self.stop();
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Captures at the display's native refresh rate divided by `divisor`
+    /// (default 1) instead of the constructor's fixed fps, so the cadence
+    /// always divides the refresh rate evenly and frames don't judder.
+    /// The rate is read at `start()` from the primary display; on
+    /// non-Windows platforms 60 Hz is assumed. Must be called before
+    /// `start()`.
+    #[napi]
+    pub fn use_native_rate(&mut self, divisor: Option<u32>) -> Result<()> {
+        if self.thread.is_some() {
+            return Err(Error::from_reason("capture already started"));
+        }
+        let divisor = divisor.unwrap_or(1);
+        if divisor == 0 {
+            return Err(Error::from_reason("divisor must be > 0"));
+        }
+        self.native_rate_divisor = Some(divisor);
+        Ok(())
+    }
+
+    /// Session counters plus the effective fps since the previous call.
+    /// Counters run from `start()` and survive the restarts behind
+    /// `setShowCursor`/`switchSource`.
+    #[napi]
+    pub fn get_stats(&self) -> CaptureStats {
+        let captured = self.counters.captured.load(Ordering::Relaxed);
+        let dropped = self.counters.dropped.load(Ordering::Relaxed);
+        let mut mark = self.stats_mark.lock().unwrap();
+        let now = std::time::Instant::now();
+        let elapsed = now.duration_since(mark.0).as_secs_f64();
+        let fps = if elapsed > 0.0 {
+            captured.saturating_sub(mark.1) as f64 / elapsed
+        } else {
+            0.0
+        };
+        *mark = (now, captured);
+        CaptureStats {
+            frames_captured: captured as f64,
+            frames_dropped: dropped as f64,
+            fps,
+        }
+    }
+
+    /// Suspends frame delivery without tearing down the capturer. Frames
+    /// are still drained from scap but dropped before the scale/convert
+    /// work, so a hidden preview costs almost nothing and `resume()` is
+    /// instant — no restart gap like `setShowCursor`.
+    #[napi]
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes frame delivery after `pause()`.
+    #[napi]
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Stops the capture thread and clears the frame slot.
+    #[napi]
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.frame.clear();
+    }
+}